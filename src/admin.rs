@@ -0,0 +1,457 @@
+//! Admin API: deletion of nodes, bans and operator annotations.
+//!
+//! Banned node ids and IP addresses are rejected at ingestion. The bans are stored in the database
+//! of their chain and cached in memory, reloaded periodically to pick up the bans made through
+//! other instances. Annotations attach a note and tags to a node id, and are kept when the node is
+//! deleted or pruned.
+
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::NaiveDateTime;
+use sea_orm::{
+    sea_query::OnConflict, ActiveValue, DatabaseConnection, EntityTrait, Iterable, QueryOrder,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    entities::{ban, node, node_annotation},
+    nodes::ChainId,
+    request_id::{error_body, RequestId},
+    server::ServerState,
+    Error,
+};
+
+/// Banned node ids and IP addresses of a chain.
+#[derive(Debug, Default)]
+pub(crate) struct Bans {
+    node_ids: HashSet<String>,
+    ips: HashSet<IpAddr>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BanKind {
+    Node,
+    Ip,
+}
+
+impl BanKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            BanKind::Node => "node",
+            BanKind::Ip => "ip",
+        }
+    }
+
+    fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "node" => Some(BanKind::Node),
+            "ip" => Some(BanKind::Ip),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct BanRequest {
+    node_id: Option<String>,
+    ip: Option<IpAddr>,
+    reason: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct BanView {
+    kind: String,
+    value: String,
+    reason: Option<String>,
+    created_at: NaiveDateTime,
+}
+
+impl From<ban::Model> for BanView {
+    fn from(ban: ban::Model) -> Self {
+        Self {
+            kind: ban.kind,
+            value: ban.value,
+            reason: ban.reason,
+            created_at: ban.created_at,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct AnnotationRequest {
+    note: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct AnnotationView {
+    node_id: String,
+    note: Option<String>,
+    tags: serde_json::Value,
+    updated_at: NaiveDateTime,
+}
+
+impl From<node_annotation::Model> for AnnotationView {
+    fn from(annotation: node_annotation::Model) -> Self {
+        Self {
+            node_id: annotation.node_id,
+            note: annotation.note,
+            tags: annotation.tags,
+            updated_at: annotation.updated_at,
+        }
+    }
+}
+
+/// Returns the database of `chain`, or the response to send if unknown.
+fn chain_database(
+    state: &ServerState,
+    chain: &str,
+) -> Result<(ChainId, Arc<DatabaseConnection>), (StatusCode, String)> {
+    let chain = ChainId::from(chain);
+    match state.database(&chain) {
+        Some(db) => Ok((chain, db.clone())),
+        None => Err((StatusCode::NOT_FOUND, format!("unknown chain: {chain}"))),
+    }
+}
+
+/// Logs `err` and returns an internal server error without its details.
+fn internal_error(action: &str, err: Error, request_id: Option<Extension<RequestId>>) -> Response {
+    error!("error {action}: {err:#?}");
+    let request_id = request_id.map(|Extension(RequestId(id))| id);
+    let message = format!("error {action}: {err}");
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        error_body(&message, request_id.as_deref()),
+    )
+        .into_response()
+}
+
+pub(crate) async fn delete_node_handler(
+    state: State<ServerState>,
+    request_id: Option<Extension<RequestId>>,
+    Path((chain, node_id)): Path<(String, String)>,
+) -> Response {
+    let (chain, db) = match chain_database(&state, &chain) {
+        Ok(chain_db) => chain_db,
+        Err(response) => return response.into_response(),
+    };
+    match node::Entity::delete_by_id(node_id.clone())
+        .exec(db.as_ref())
+        .await
+    {
+        Ok(result) if result.rows_affected == 0 => {
+            (StatusCode::NOT_FOUND, format!("unknown node: {node_id}")).into_response()
+        }
+        Ok(_) => {
+            info!("deleted {chain} node {node_id}");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(err) => internal_error("deleting node", err.into(), request_id),
+    }
+}
+
+pub(crate) async fn bans_handler(
+    state: State<ServerState>,
+    request_id: Option<Extension<RequestId>>,
+    Path(chain): Path<String>,
+) -> Response {
+    let (_, db) = match chain_database(&state, &chain) {
+        Ok(chain_db) => chain_db,
+        Err(response) => return response.into_response(),
+    };
+    match load_bans(&db).await {
+        Ok(bans) => Json(bans.into_iter().map(BanView::from).collect::<Vec<_>>()).into_response(),
+        Err(err) => internal_error("loading bans", err, request_id),
+    }
+}
+
+pub(crate) async fn create_ban_handler(
+    state: State<ServerState>,
+    request_id: Option<Extension<RequestId>>,
+    Path(chain): Path<String>,
+    Json(request): Json<BanRequest>,
+) -> Response {
+    let (chain, db) = match chain_database(&state, &chain) {
+        Ok(chain_db) => chain_db,
+        Err(response) => return response.into_response(),
+    };
+    let (kind, value) = match (request.node_id, request.ip) {
+        (Some(node_id), None) => (BanKind::Node, node_id),
+        (None, Some(ip)) => (BanKind::Ip, ip.to_string()),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "either node_id or ip must be set".to_string(),
+            )
+                .into_response()
+        }
+    };
+    let ban = ban::Model {
+        kind: kind.as_str().to_string(),
+        value,
+        reason: request.reason,
+        created_at: chrono::offset::Utc::now().naive_utc(),
+    };
+    let on_conflict = OnConflict::columns([ban::Column::Kind, ban::Column::Value])
+        .update_column(ban::Column::Reason)
+        .to_owned();
+    let active_ban = ban::ActiveModel {
+        kind: ActiveValue::Set(ban.kind.clone()),
+        value: ActiveValue::Set(ban.value.clone()),
+        reason: ActiveValue::Set(ban.reason.clone()),
+        created_at: ActiveValue::Set(ban.created_at),
+    };
+    if let Err(err) = ban::Entity::insert(active_ban)
+        .on_conflict(on_conflict)
+        .exec_without_returning(db.as_ref())
+        .await
+    {
+        return internal_error("storing ban", err.into(), request_id);
+    }
+    info!("banned {chain} {} {}", ban.kind, ban.value);
+    state
+        .bans
+        .write()
+        .expect("bans lock poisoned")
+        .entry(chain)
+        .or_default()
+        .insert(kind, &ban.value);
+    (StatusCode::CREATED, Json(BanView::from(ban))).into_response()
+}
+
+pub(crate) async fn delete_ban_handler(
+    state: State<ServerState>,
+    request_id: Option<Extension<RequestId>>,
+    Path((chain, kind, value)): Path<(String, String, String)>,
+) -> Response {
+    let (chain, db) = match chain_database(&state, &chain) {
+        Ok(chain_db) => chain_db,
+        Err(response) => return response.into_response(),
+    };
+    let Some(kind) = BanKind::parse(&kind) else {
+        return (StatusCode::NOT_FOUND, format!("unknown ban kind: {kind}")).into_response();
+    };
+    // Match the canonical form in which the addresses are stored.
+    let value = match (kind, value.parse::<IpAddr>()) {
+        (BanKind::Ip, Ok(ip)) => ip.to_string(),
+        _ => value,
+    };
+    match ban::Entity::delete_by_id((kind.as_str().to_string(), value.clone()))
+        .exec(db.as_ref())
+        .await
+    {
+        Ok(result) if result.rows_affected == 0 => {
+            (StatusCode::NOT_FOUND, format!("unknown ban: {value}")).into_response()
+        }
+        Ok(_) => {
+            info!("unbanned {chain} {} {value}", kind.as_str());
+            if let Some(bans) = state
+                .bans
+                .write()
+                .expect("bans lock poisoned")
+                .get_mut(&chain)
+            {
+                bans.remove(kind, &value);
+            }
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(err) => internal_error("deleting ban", err.into(), request_id),
+    }
+}
+
+pub(crate) async fn annotations_handler(
+    state: State<ServerState>,
+    request_id: Option<Extension<RequestId>>,
+    Path(chain): Path<String>,
+) -> Response {
+    let (_, db) = match chain_database(&state, &chain) {
+        Ok(chain_db) => chain_db,
+        Err(response) => return response.into_response(),
+    };
+    match node_annotation::Entity::find()
+        .order_by_asc(node_annotation::Column::NodeId)
+        .all(db.as_ref())
+        .await
+    {
+        Ok(annotations) => Json(
+            annotations
+                .into_iter()
+                .map(AnnotationView::from)
+                .collect::<Vec<_>>(),
+        )
+        .into_response(),
+        Err(err) => internal_error("loading annotations", err.into(), request_id),
+    }
+}
+
+pub(crate) async fn annotate_node_handler(
+    state: State<ServerState>,
+    request_id: Option<Extension<RequestId>>,
+    Path((chain, node_id)): Path<(String, String)>,
+    Json(request): Json<AnnotationRequest>,
+) -> Response {
+    let (chain, db) = match chain_database(&state, &chain) {
+        Ok(chain_db) => chain_db,
+        Err(response) => return response.into_response(),
+    };
+    let annotation = node_annotation::Model {
+        node_id,
+        note: request.note,
+        tags: serde_json::Value::from(request.tags),
+        updated_at: chrono::offset::Utc::now().naive_utc(),
+    };
+    let on_conflict = OnConflict::column(node_annotation::Column::NodeId)
+        .update_columns(
+            node_annotation::Column::iter()
+                .filter(|col| !matches!(*col, node_annotation::Column::NodeId)),
+        )
+        .to_owned();
+    let active_annotation = node_annotation::ActiveModel {
+        node_id: ActiveValue::Set(annotation.node_id.clone()),
+        note: ActiveValue::Set(annotation.note.clone()),
+        tags: ActiveValue::Set(annotation.tags.clone()),
+        updated_at: ActiveValue::Set(annotation.updated_at),
+    };
+    match node_annotation::Entity::insert(active_annotation)
+        .on_conflict(on_conflict)
+        .exec_without_returning(db.as_ref())
+        .await
+    {
+        Ok(_) => {
+            info!("annotated {chain} node {}", annotation.node_id);
+            Json(AnnotationView::from(annotation)).into_response()
+        }
+        Err(err) => internal_error("storing annotation", err.into(), request_id),
+    }
+}
+
+pub(crate) async fn delete_annotation_handler(
+    state: State<ServerState>,
+    request_id: Option<Extension<RequestId>>,
+    Path((chain, node_id)): Path<(String, String)>,
+) -> Response {
+    let (_, db) = match chain_database(&state, &chain) {
+        Ok(chain_db) => chain_db,
+        Err(response) => return response.into_response(),
+    };
+    match node_annotation::Entity::delete_by_id(node_id.clone())
+        .exec(db.as_ref())
+        .await
+    {
+        Ok(result) if result.rows_affected == 0 => (
+            StatusCode::NOT_FOUND,
+            format!("unknown annotation: {node_id}"),
+        )
+            .into_response(),
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => internal_error("deleting annotation", err.into(), request_id),
+    }
+}
+
+impl Bans {
+    fn insert(&mut self, kind: BanKind, value: &str) {
+        match kind {
+            BanKind::Node => {
+                self.node_ids.insert(value.to_string());
+            }
+            BanKind::Ip => {
+                if let Ok(ip) = value.parse() {
+                    self.ips.insert(ip);
+                }
+            }
+        }
+    }
+
+    fn remove(&mut self, kind: BanKind, value: &str) {
+        match kind {
+            BanKind::Node => {
+                self.node_ids.remove(value);
+            }
+            BanKind::Ip => {
+                if let Ok(ip) = value.parse::<IpAddr>() {
+                    self.ips.remove(&ip);
+                }
+            }
+        }
+    }
+}
+
+async fn load_bans(db: &DatabaseConnection) -> Result<Vec<ban::Model>, Error> {
+    Ok(ban::Entity::find()
+        .order_by_asc(ban::Column::Kind)
+        .order_by_asc(ban::Column::Value)
+        .all(db)
+        .await?)
+}
+
+/// Reloads the bans of every chain from the databases.
+pub(crate) async fn refresh_bans(state: &ServerState) -> Result<(), Error> {
+    let mut refreshed = HashMap::new();
+    for (chain, db) in state.databases() {
+        let mut bans = Bans::default();
+        for ban in load_bans(db).await? {
+            if let Some(kind) = BanKind::parse(&ban.kind) {
+                bans.insert(kind, &ban.value);
+            }
+        }
+        refreshed.insert(chain, bans);
+    }
+    *state.bans.write().expect("bans lock poisoned") = refreshed;
+    Ok(())
+}
+
+/// Returns the reason the telemetry of `node_id` sent from `ip` is rejected, if banned.
+pub(crate) fn ban_reason(
+    state: &ServerState,
+    chain: &ChainId,
+    node_id: Option<&str>,
+    ip: Option<IpAddr>,
+) -> Option<&'static str> {
+    let bans = state.bans.read().expect("bans lock poisoned");
+    let bans = bans.get(chain)?;
+    if node_id.is_some_and(|node_id| bans.node_ids.contains(node_id)) {
+        Some("banned_node")
+    } else if ip.is_some_and(|ip| bans.ips.contains(&ip)) {
+        Some("banned_ip")
+    } else {
+        None
+    }
+}
+
+/// Returns the IP address of the client, from the configured header or the connection.
+///
+/// Only the addresses appended by the trusted proxies are considered in the header, the client
+/// controlling the others.
+pub(crate) fn client_ip(
+    state: &ServerState,
+    headers: &HeaderMap,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+) -> Option<IpAddr> {
+    let peer = connect_info.map(|ConnectInfo(address)| address.ip());
+    let Some(header) = &state.admin.client_ip_header else {
+        return peer;
+    };
+    let trusted = state.admin.trusted_proxies.max(1);
+    let addresses: Vec<&str> = headers
+        .get_all(header.as_str())
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+    match addresses.len().checked_sub(trusted) {
+        Some(index) => addresses[index].trim().parse().ok(),
+        None => peer,
+    }
+}
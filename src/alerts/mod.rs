@@ -0,0 +1,275 @@
+//! Alerting subsystem: rules periodically evaluated over the nodes, firing webhook notifications.
+//!
+//! An alert exists for every (rule, chain, node) whose conditions currently hold. It is `pending`
+//! until the conditions held for the rule duration, then `firing`. Notifications are sent once when
+//! an alert starts firing and once when it is resolved. They are sent concurrently, apart from the
+//! evaluation, and those still failing after their retries are sent again at the next evaluation.
+
+use std::collections::HashMap;
+
+use axum::{extract::State, response::IntoResponse, Json};
+use chrono::NaiveDateTime;
+use futures::future::join_all;
+use serde::Serialize;
+use tracing::{error, info, warn};
+
+use crate::{
+    metrics::RuleLabels,
+    nodes::{all_nodes, ChainId},
+    server::ServerState,
+    webhook::Webhook,
+    Error,
+};
+
+mod rules;
+pub(crate) use rules::{load_rules, AlertRule};
+
+/// Maximum number of undelivered notifications kept to be sent again.
+const MAX_UNDELIVERED: usize = 1000;
+
+#[derive(Debug, Default)]
+pub(crate) struct AlertsState {
+    last_evaluation: Option<NaiveDateTime>,
+    active: HashMap<AlertKey, ActiveAlert>,
+    /// Webhooks of the rules by URL, built once to reuse their connections.
+    webhooks: HashMap<String, Webhook>,
+    /// Notifications that failed after their retries, to be sent again.
+    undelivered: Vec<Delivery>,
+}
+
+/// Notification of an alert to a webhook.
+#[derive(Debug)]
+struct Delivery {
+    rule: String,
+    webhook: Webhook,
+    payload: serde_json::Value,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct AlertKey {
+    rule: String,
+    chain: ChainId,
+    node_id: String,
+}
+
+#[derive(Clone, Debug)]
+struct ActiveAlert {
+    account_id: Option<String>,
+    pending_since: NaiveDateTime,
+    firing_since: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum AlertStatus {
+    Pending,
+    Firing,
+    Resolved,
+}
+
+#[derive(Serialize, Debug)]
+struct AlertView<'a> {
+    rule: &'a str,
+    chain: String,
+    node_id: &'a str,
+    account_id: Option<&'a str>,
+    status: AlertStatus,
+    pending_since: NaiveDateTime,
+    firing_since: Option<NaiveDateTime>,
+}
+
+impl<'a> AlertView<'a> {
+    fn new(key: &'a AlertKey, alert: &'a ActiveAlert, status: AlertStatus) -> Self {
+        Self {
+            rule: &key.rule,
+            chain: key.chain.to_string(),
+            node_id: &key.node_id,
+            account_id: alert.account_id.as_deref(),
+            status,
+            pending_since: alert.pending_since,
+            firing_since: alert.firing_since,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct Notification<'a> {
+    severity: Option<&'a str>,
+    #[serde(flatten)]
+    alert: AlertView<'a>,
+}
+
+#[derive(Serialize, Debug)]
+struct AlertsView<'a> {
+    last_evaluation: Option<NaiveDateTime>,
+    rules: &'a [AlertRule],
+    alerts: Vec<AlertView<'a>>,
+}
+
+pub(crate) async fn alerts_handler(state: State<ServerState>) -> impl IntoResponse {
+    let alerts = state.alerts.read().expect("alerts lock poisoned");
+    let mut views: Vec<AlertView> = alerts
+        .active
+        .iter()
+        .map(|(key, alert)| {
+            let status = match alert.firing_since {
+                Some(_) => AlertStatus::Firing,
+                None => AlertStatus::Pending,
+            };
+            AlertView::new(key, alert, status)
+        })
+        .collect();
+    views.sort_by(|a, b| (a.rule, &a.chain, a.node_id).cmp(&(b.rule, &b.chain, b.node_id)));
+    Json(AlertsView {
+        last_evaluation: alerts.last_evaluation,
+        rules: &state.alert_rules(),
+        alerts: views,
+    })
+    .into_response()
+}
+
+/// Evaluates every rule and notifies the alerts that started firing or got resolved.
+pub(crate) async fn evaluate_alerts(state: &ServerState) -> Result<(), Error> {
+    let rules = state.alert_rules();
+    if rules.is_empty() {
+        return Ok(());
+    }
+    let now = chrono::offset::Utc::now().naive_utc();
+
+    let mut matching = HashMap::new();
+    for (chain, db) in state.read_databases() {
+        let nodes = all_nodes(db).await?;
+        for rule in rules.iter() {
+            for node in &nodes {
+                if rule.matches(&chain, node, now) {
+                    let key = AlertKey {
+                        rule: rule.name.clone(),
+                        chain: chain.clone(),
+                        node_id: node.id.clone(),
+                    };
+                    matching.insert(key, node.account_id.clone());
+                }
+            }
+        }
+    }
+
+    let mut notifications = Vec::new();
+    {
+        let mut alerts = state.alerts.write().expect("alerts lock poisoned");
+        alerts.active.retain(|key, alert| {
+            let resolved = !matching.contains_key(key);
+            if resolved && alert.firing_since.is_some() {
+                notifications.push((key.clone(), alert.clone(), AlertStatus::Resolved));
+            }
+            !resolved
+        });
+        for (key, account_id) in matching {
+            let Some(rule) = rules.iter().find(|rule| rule.name == key.rule) else {
+                continue;
+            };
+            let alert = alerts
+                .active
+                .entry(key.clone())
+                .or_insert_with(|| ActiveAlert {
+                    account_id,
+                    pending_since: now,
+                    firing_since: None,
+                });
+            let held_for = (now - alert.pending_since).to_std().unwrap_or_default();
+            if alert.firing_since.is_none() && held_for >= rule.for_duration {
+                alert.firing_since = Some(now);
+                notifications.push((key, alert.clone(), AlertStatus::Firing));
+            }
+        }
+        alerts.last_evaluation = Some(now);
+
+        let mut firing: HashMap<&str, i64> =
+            rules.iter().map(|rule| (rule.name.as_str(), 0)).collect();
+        for (key, alert) in &alerts.active {
+            if alert.firing_since.is_some() {
+                *firing.entry(key.rule.as_str()).or_default() += 1;
+            }
+        }
+        // Clear the gauges of the rules removed by a reload.
+        state.metrics.alerts_firing.clear();
+        for (rule, count) in firing {
+            state
+                .metrics
+                .alerts_firing
+                .get_or_create(&RuleLabels::new(rule.to_string()))
+                .set(count);
+        }
+    }
+
+    let deliveries = {
+        let mut alerts = state.alerts.write().expect("alerts lock poisoned");
+        let mut deliveries = std::mem::take(&mut alerts.undelivered);
+        for (key, alert, status) in &notifications {
+            let Some(rule) = rules.iter().find(|rule| rule.name == key.rule) else {
+                continue;
+            };
+            info!(
+                "alert {} for {} node {} is {status:?}",
+                key.rule, key.chain, key.node_id
+            );
+            let webhook = match &rule.webhook {
+                Some(url) => alerts
+                    .webhooks
+                    .entry(url.clone())
+                    .or_insert_with(|| Webhook::new(url.clone()))
+                    .clone(),
+                None => match &state.alerts_webhook {
+                    Some(webhook) => webhook.clone(),
+                    None => continue,
+                },
+            };
+            let notification = Notification {
+                severity: rule.severity.as_deref(),
+                alert: AlertView::new(key, alert, *status),
+            };
+            deliveries.push(Delivery {
+                rule: key.rule.clone(),
+                webhook,
+                payload: serde_json::to_value(notification)
+                    .expect("notifications are serializable"),
+            });
+        }
+        deliveries
+    };
+    if !deliveries.is_empty() {
+        let state = state.clone();
+        tokio::spawn(async move { deliver(&state, deliveries).await });
+    }
+    Ok(())
+}
+
+/// Sends the notifications concurrently, keeping the failed ones to send them again.
+async fn deliver(state: &ServerState, deliveries: Vec<Delivery>) {
+    let results = join_all(
+        deliveries
+            .iter()
+            .map(|delivery| delivery.webhook.send(&delivery.payload)),
+    )
+    .await;
+    let failed: Vec<Delivery> = deliveries
+        .into_iter()
+        .zip(results)
+        .filter_map(|(delivery, result)| match result {
+            Ok(()) => None,
+            Err(err) => {
+                error!("failed to notify alert {}: {err:#?}", delivery.rule);
+                Some(delivery)
+            }
+        })
+        .collect();
+    if failed.is_empty() {
+        return;
+    }
+    let mut alerts = state.alerts.write().expect("alerts lock poisoned");
+    alerts.undelivered.extend(failed);
+    let dropped = alerts.undelivered.len().saturating_sub(MAX_UNDELIVERED);
+    if dropped > 0 {
+        warn!("dropping the {dropped} oldest undelivered alert notifications");
+        alerts.undelivered.drain(..dropped);
+    }
+}
@@ -0,0 +1,314 @@
+//! Declaration and evaluation of alert rules.
+//!
+//! Rules are declared in a TOML file, for example:
+//! ```toml
+//! [[rule]]
+//! name = "high-cpu"
+//! when = ["cpu_usage > 90"]
+//! for = "10m"
+//!
+//! [[rule]]
+//! name = "outdated-validators"
+//! chain = "mainnet"
+//! validators_only = true
+//! when = ["protocol_version < 68"]
+//! ```
+//! A rule fires for every node matching all its conditions for at least the `for` duration.
+
+use std::{collections::HashSet, fmt, fs, path::Path, str::FromStr, time::Duration};
+
+use chrono::NaiveDateTime;
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use crate::{entities::node, nodes::ChainId, Error};
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default, rename = "rule")]
+    rules: Vec<AlertRule>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct AlertRule {
+    /// Unique name of the rule.
+    pub(crate) name: String,
+    /// Free-form severity forwarded in notifications.
+    #[serde(default)]
+    pub(crate) severity: Option<String>,
+    /// Restricts the rule to a single chain.
+    #[serde(default)]
+    pub(crate) chain: Option<String>,
+    /// Restricts the rule to validator nodes.
+    #[serde(default)]
+    pub(crate) validators_only: bool,
+    /// Conditions that must all be satisfied by a node.
+    pub(crate) when: Vec<Condition>,
+    /// How long the conditions must hold before the alert fires.
+    #[serde(
+        default,
+        rename = "for",
+        serialize_with = "serialize_duration",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub(crate) for_duration: Duration,
+    /// Webhook notified instead of the default one.
+    #[serde(default)]
+    pub(crate) webhook: Option<String>,
+}
+
+impl AlertRule {
+    /// Returns whether `node` of `chain` currently satisfies the rule.
+    pub(crate) fn matches(&self, chain: &ChainId, node: &node::Model, now: NaiveDateTime) -> bool {
+        if self
+            .chain
+            .as_deref()
+            .is_some_and(|rule_chain| ChainId::from(rule_chain) != *chain)
+        {
+            return false;
+        }
+        if self.validators_only && !node.is_validator {
+            return false;
+        }
+        self.when
+            .iter()
+            .all(|condition| condition.matches(node, now))
+    }
+}
+
+/// Loads the rules declared in the TOML file at `path`.
+pub(crate) fn load_rules(path: &Path) -> Result<Vec<AlertRule>, Error> {
+    let content = fs::read_to_string(path)?;
+    let file: RulesFile = toml::from_str(&content).map_err(|err| {
+        Error::ConfigError(format!("invalid alert rules in {}: {err}", path.display()))
+    })?;
+    let mut names = HashSet::new();
+    for rule in &file.rules {
+        if !names.insert(&rule.name) {
+            return Err(Error::ConfigError(format!(
+                "duplicated alert rule: {}",
+                rule.name
+            )));
+        }
+        if let Some(chain) = &rule.chain {
+            if matches!(ChainId::from(chain.as_str()), ChainId::Other(_)) {
+                return Err(Error::ConfigError(format!(
+                    "alert rule {} references unknown chain {chain}",
+                    rule.name
+                )));
+            }
+        }
+    }
+    Ok(file.rules)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operator {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Operator {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Operator::Lt => "<",
+            Operator::Le => "<=",
+            Operator::Gt => ">",
+            Operator::Ge => ">=",
+            Operator::Eq => "==",
+            Operator::Ne => "!=",
+        }
+    }
+}
+
+/// Value of a node field.
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+}
+
+/// A comparison between a node field and a constant, such as `peer_count < 5`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Condition {
+    field: String,
+    operator: Operator,
+    value: Value,
+}
+
+impl Condition {
+    fn matches(&self, node: &node::Model, now: NaiveDateTime) -> bool {
+        let Some(actual) = field_value(node, &self.field, now) else {
+            return false;
+        };
+        let ordering = match (&actual, &self.value) {
+            (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+            (Value::Text(a), Value::Text(b)) => Some(a.cmp(b)),
+            (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+            _ => None,
+        };
+        let Some(ordering) = ordering else {
+            return false;
+        };
+        match self.operator {
+            Operator::Lt => ordering.is_lt(),
+            Operator::Le => ordering.is_le(),
+            Operator::Gt => ordering.is_gt(),
+            Operator::Ge => ordering.is_ge(),
+            Operator::Eq => ordering.is_eq(),
+            Operator::Ne => ordering.is_ne(),
+        }
+    }
+}
+
+/// Kind of value held by every field usable in conditions.
+const FIELDS: [(&str, FieldKind); 20] = [
+    ("id", FieldKind::Text),
+    ("account_id", FieldKind::Text),
+    ("last_height", FieldKind::Number),
+    ("last_seen_seconds_ago", FieldKind::Number),
+    ("agent_name", FieldKind::Text),
+    ("agent_version", FieldKind::Text),
+    ("agent_build", FieldKind::Text),
+    ("peer_count", FieldKind::Number),
+    ("is_validator", FieldKind::Bool),
+    ("status", FieldKind::Text),
+    ("bandwidth_download", FieldKind::Number),
+    ("bandwidth_upload", FieldKind::Number),
+    ("cpu_usage", FieldKind::Number),
+    ("memory_usage", FieldKind::Number),
+    ("boot_time_seconds", FieldKind::Number),
+    ("block_production_tracking_delay", FieldKind::Number),
+    ("min_block_production_delay", FieldKind::Number),
+    ("max_block_production_delay", FieldKind::Number),
+    ("max_block_wait_delay", FieldKind::Number),
+    ("protocol_version", FieldKind::Number),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FieldKind {
+    Number,
+    Text,
+    Bool,
+}
+
+fn field_value(node: &node::Model, field: &str, now: NaiveDateTime) -> Option<Value> {
+    let value = match field {
+        "id" => Value::Text(node.id.clone()),
+        "account_id" => Value::Text(node.account_id.clone()?),
+        "last_height" => Value::Number(node.last_height as f64),
+        "last_seen_seconds_ago" => Value::Number((now - node.last_seen).num_seconds() as f64),
+        "agent_name" => Value::Text(node.agent_name.clone()),
+        "agent_version" => Value::Text(node.agent_version.clone()),
+        "agent_build" => Value::Text(node.agent_build.clone()),
+        "peer_count" => Value::Number(node.peer_count as f64),
+        "is_validator" => Value::Bool(node.is_validator),
+        "status" => Value::Text(node.status.clone()),
+        "bandwidth_download" => Value::Number(node.bandwidth_download as f64),
+        "bandwidth_upload" => Value::Number(node.bandwidth_upload as f64),
+        "cpu_usage" => Value::Number(node.cpu_usage as f64),
+        "memory_usage" => Value::Number(node.memory_usage as f64),
+        "boot_time_seconds" => Value::Number(node.boot_time_seconds as f64),
+        "block_production_tracking_delay" => Value::Number(node.block_production_tracking_delay),
+        "min_block_production_delay" => Value::Number(node.min_block_production_delay),
+        "max_block_production_delay" => Value::Number(node.max_block_production_delay),
+        "max_block_wait_delay" => Value::Number(node.max_block_wait_delay),
+        "protocol_version" => Value::Number(node.protocol_version? as f64),
+        _ => return None,
+    };
+    Some(value)
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = s.split_whitespace();
+        let (Some(field), Some(operator), Some(value), None) =
+            (tokens.next(), tokens.next(), tokens.next(), tokens.next())
+        else {
+            return Err(format!(
+                "condition '{s}' must have the form '<field> <operator> <value>'"
+            ));
+        };
+
+        let Some((_, kind)) = FIELDS.iter().find(|(name, _)| *name == field) else {
+            return Err(format!("unknown field '{field}' in condition '{s}'"));
+        };
+        let operator = match operator {
+            "<" => Operator::Lt,
+            "<=" => Operator::Le,
+            ">" => Operator::Gt,
+            ">=" => Operator::Ge,
+            "==" => Operator::Eq,
+            "!=" => Operator::Ne,
+            _ => return Err(format!("unknown operator '{operator}' in condition '{s}'")),
+        };
+        let value = match kind {
+            FieldKind::Number => value
+                .parse()
+                .map(Value::Number)
+                .map_err(|_| format!("'{value}' is not a number in condition '{s}'"))?,
+            FieldKind::Bool => value
+                .parse()
+                .map(Value::Bool)
+                .map_err(|_| format!("'{value}' is not a boolean in condition '{s}'"))?,
+            FieldKind::Text => {
+                if !matches!(operator, Operator::Eq | Operator::Ne) {
+                    return Err(format!(
+                        "only == and != can be used on text fields in condition '{s}'"
+                    ));
+                }
+                Value::Text(value.trim_matches('"').to_string())
+            }
+        };
+        Ok(Condition {
+            field: field.to_string(),
+            operator,
+            value,
+        })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = match &self.value {
+            Value::Number(n) => n.to_string(),
+            Value::Text(s) => format!("\"{s}\""),
+            Value::Bool(b) => b.to_string(),
+        };
+        write!(f, "{} {} {value}", self.field, self.operator.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Condition {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for Condition {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+fn deserialize_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let duration = String::deserialize(deserializer)?;
+    humantime::parse_duration(&duration).map_err(de::Error::custom)
+}
+
+fn serialize_duration<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&humantime::format_duration(*duration))
+}
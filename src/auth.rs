@@ -0,0 +1,161 @@
+//! Authentication and authorization of the route groups.
+//!
+//! Every group of routes requires a scope. Clients authenticate with a static API key, in the
+//! `X-Api-Key` header or as a bearer token, or with a JWT signed with HMAC-SHA256 as a bearer
+//! token. Requests without credentials are granted the anonymous scopes, `ingest` by default, so
+//! that nodes can keep reporting without credentials. Authentication is only enforced once API keys
+//! or a token secret are configured.
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+use crate::{
+    config::{AuthConfig, Scope},
+    server::ServerState,
+};
+
+static X_API_KEY: HeaderName = HeaderName::from_static("x-api-key");
+
+#[derive(Deserialize)]
+struct TokenHeader {
+    alg: String,
+}
+
+#[derive(Deserialize)]
+struct TokenClaims {
+    #[serde(default)]
+    scope: String,
+    exp: Option<i64>,
+}
+
+/// Reasons a request is refused.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum AuthError {
+    /// Missing or invalid credentials.
+    Unauthenticated(&'static str),
+    /// Valid credentials lacking the required scope.
+    Forbidden,
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        match self {
+            AuthError::Unauthenticated(reason) => (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+                reason,
+            )
+                .into_response(),
+            AuthError::Forbidden => (StatusCode::FORBIDDEN, "insufficient scope").into_response(),
+        }
+    }
+}
+
+/// Rejects the requests that aren't granted the scope of the route group.
+pub(crate) async fn auth_middleware(
+    State((state, scope)): State<(ServerState, Scope)>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match authorize(&state.auth(), request.headers(), scope) {
+        Ok(()) => next.run(request).await,
+        Err(err) => err.into_response(),
+    }
+}
+
+/// Returns whether authentication is enforced.
+pub(crate) fn is_enabled(config: &AuthConfig) -> bool {
+    !config.api_keys.is_empty() || config.auth_token_secret.is_some()
+}
+
+fn authorize(config: &AuthConfig, headers: &HeaderMap, scope: Scope) -> Result<(), AuthError> {
+    if !is_enabled(config) {
+        return Ok(());
+    }
+    let api_key = headers.get(&X_API_KEY).and_then(|key| key.to_str().ok());
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let granted = match (api_key, bearer) {
+        (Some(key), _) => api_key_scopes(config, key)?,
+        (None, Some(token)) => match api_key_scopes(config, token) {
+            Ok(scopes) => scopes,
+            Err(_) => token_scopes(config, token)?,
+        },
+        (None, None) => {
+            if grants(&config.anonymous_scopes, scope) {
+                return Ok(());
+            }
+            return Err(AuthError::Unauthenticated("credentials required"));
+        }
+    };
+    if !grants(&granted, scope) {
+        return Err(AuthError::Forbidden);
+    }
+    Ok(())
+}
+
+fn grants(scopes: &[Scope], scope: Scope) -> bool {
+    scopes.contains(&scope) || scopes.contains(&Scope::Admin)
+}
+
+fn api_key_scopes(config: &AuthConfig, key: &str) -> Result<Vec<Scope>, AuthError> {
+    config
+        .api_keys
+        .iter()
+        .find(|api_key| bool::from(api_key.key.as_bytes().ct_eq(key.as_bytes())))
+        .map(|api_key| api_key.scopes.clone())
+        .ok_or(AuthError::Unauthenticated("invalid API key"))
+}
+
+/// Verifies a JWT signed with HMAC-SHA256 and returns the scopes it grants.
+fn token_scopes(config: &AuthConfig, token: &str) -> Result<Vec<Scope>, AuthError> {
+    let invalid = AuthError::Unauthenticated("invalid token");
+    let Some(secret) = &config.auth_token_secret else {
+        return Err(AuthError::Unauthenticated("invalid credentials"));
+    };
+    let Some((signed, signature)) = token.rsplit_once('.') else {
+        return Err(invalid);
+    };
+    let Some((header, claims)) = signed.split_once('.') else {
+        return Err(invalid);
+    };
+    let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| invalid)?;
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(signed.as_bytes());
+    mac.verify_slice(&signature)
+        .map_err(|_| AuthError::Unauthenticated("invalid token signature"))?;
+
+    let decode = |part: &str| URL_SAFE_NO_PAD.decode(part).ok();
+    let header: TokenHeader = decode(header)
+        .and_then(|header| serde_json::from_slice(&header).ok())
+        .ok_or(invalid)?;
+    if header.alg != "HS256" {
+        return Err(AuthError::Unauthenticated("unsupported token algorithm"));
+    }
+    let claims: TokenClaims = decode(claims)
+        .and_then(|claims| serde_json::from_slice(&claims).ok())
+        .ok_or(invalid)?;
+    if claims
+        .exp
+        .is_some_and(|exp| exp <= chrono::offset::Utc::now().timestamp())
+    {
+        return Err(AuthError::Unauthenticated("expired token"));
+    }
+    Ok(claims
+        .scope
+        .split_whitespace()
+        .filter_map(|scope| clap::ValueEnum::from_str(scope, true).ok())
+        .collect())
+}
@@ -1,48 +1,141 @@
-use clap::Parser;
+use sea_orm::DatabaseConnection;
 use telemetry_service::{
-    database::{connect_and_refresh_schema, MAINNET_DB_NAME, TESTNET_DB_NAME},
-    Config, Error, Server,
+    config::{Command, ConfigAction, LogFormat, LogsConfig, MigrateAction, TracingConfig},
+    database::{
+        connect, connect_and_check_schema, connect_and_refresh_schema, migrate, MAINNET_DB_NAME,
+        TESTNET_DB_NAME,
+    },
+    otlp,
+    reload::{LogFilterHandle, Reloader},
+    Config, Error, Server, Task,
 };
 use tracing::info;
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::{fmt, layer::SubscriberExt, reload, util::SubscriberInitExt};
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    setup_tracing();
-
-    let config = Config::parse();
-
-    let db_mainnet = connect_and_refresh_schema(
-        &config.database_url,
-        MAINNET_DB_NAME,
-        config.max_connections,
-        &config.sslmode,
-    )
-    .await?;
-    let db_testnet = connect_and_refresh_schema(
-        &config.database_url,
-        TESTNET_DB_NAME,
-        config.max_connections,
-        &config.sslmode,
-    )
-    .await?;
+    let args: Vec<_> = std::env::args_os().collect();
+    let (config, effective_config) = match Config::load(&args) {
+        Ok(config) => config,
+        // Print the usage, or the help and version when requested.
+        Err(Error::ArgsError(err)) => err.exit(),
+        Err(err) => return Err(err),
+    };
+    if let Some(Command::Config {
+        action: ConfigAction::Check,
+    }) = config.command
+    {
+        print!("{effective_config}");
+        return Ok(());
+    }
+    let log_filter = setup_tracing(&config.logs, &config.tracing)?;
+
+    let reloader = Reloader::new(args, effective_config).with_log_filter(log_filter);
+    let result = run(config, reloader).await;
+    otlp::shutdown();
+    result
+}
+
+async fn run(config: Config, reloader: Reloader) -> Result<(), Error> {
+    if matches!(config.command, Some(Command::Prune { .. }))
+        && config.retention.retention_days.is_none()
+    {
+        return Err(Error::ConfigError(
+            "--retention-days is required to prune nodes".to_string(),
+        ));
+    }
+
+    if let Some(Command::Migrate {
+        chain: None,
+        action: MigrateAction::Fresh { all: false },
+    }) = config.command
+    {
+        return Err(Error::ConfigError(
+            "--chain or --all is required to reset the databases".to_string(),
+        ));
+    }
+
+    if let Some(Command::Migrate { chain, action }) = &config.command {
+        for db_name in [MAINNET_DB_NAME, TESTNET_DB_NAME] {
+            if chain.as_ref().is_some_and(|chain| chain != db_name) {
+                continue;
+            }
+            let db = connect(&config.database, db_name).await?;
+            migrate(&db, db_name, *action).await?;
+        }
+        return Ok(());
+    }
+
+    let db_mainnet = connect_database(&config, MAINNET_DB_NAME).await?;
+    let db_testnet = connect_database(&config, TESTNET_DB_NAME).await?;
 
     if config.generate_schema {
         info!("generated database schema - now exiting");
         return Ok(());
     }
 
-    let http_server = Server::new(config.server_address, db_mainnet, db_testnet)?;
+    let mut retention = config.retention;
+    if let Some(Command::Prune { dry_run }) = config.command {
+        retention.prune_dry_run |= dry_run;
+        let dry_run = retention.prune_dry_run;
+        let server = Server::new(config.server_address, db_mainnet, db_testnet)?
+            .with_retention(retention)
+            .with_history(config.history);
+        server.run_task(Task::Prune).await?;
+        // The history maintenance has no dry run, and does nothing without --store-history.
+        if !dry_run {
+            server.run_task(Task::HistoryMaintenance).await?;
+        }
+        return Ok(());
+    }
+
+    let http_server = Server::new(config.server_address, db_mainnet, db_testnet)?
+        .with_tls(config.tls)
+        .with_auth(config.auth)
+        .with_admin(config.admin)
+        .with_replica_urls(config.replicas, &config.database)
+        .with_health(config.health)
+        .with_fork_detection(config.fork_detection)
+        .with_sync_status(config.sync_status)
+        .with_validators(config.validators)
+        .with_claims(config.claims)
+        .with_stream(config.stream)
+        .with_alerts(config.alerts)?
+        .with_retention(retention)
+        .with_rollups(config.rollups)
+        .with_history(config.history)
+        .with_node_metrics(config.node_metrics)
+        .with_network_stats(config.network_stats)
+        .with_logs(config.logs)
+        .with_reloader(reloader);
     http_server.run().await
 }
 
-fn setup_tracing() {
-    let fmt_layer = fmt::layer().with_target(true);
-    let filter_layer = EnvFilter::try_from_default_env()
-        .or_else(|_| EnvFilter::try_new("info,sqlx=warn"))
-        .expect("failed to create env filter for tracing");
+/// Connects to the database, applying the pending migrations unless disabled.
+async fn connect_database(config: &Config, db_name: &str) -> Result<DatabaseConnection, Error> {
+    if config.no_auto_migrate {
+        connect_and_check_schema(&config.database, db_name).await
+    } else {
+        connect_and_refresh_schema(&config.database, db_name).await
+    }
+}
+
+fn setup_tracing(logs: &LogsConfig, config: &TracingConfig) -> Result<LogFilterHandle, Error> {
+    let (text_layer, json_layer) = match logs.log_format {
+        LogFormat::Text => (Some(fmt::layer().with_target(true)), None),
+        LogFormat::Json => (
+            None,
+            Some(fmt::layer().json().with_target(true).with_span_list(true)),
+        ),
+    };
+    let (filter_layer, log_filter) = reload::Layer::new(logs.log_filter()?);
+    let otlp_layer = otlp::tracer(config)?.map(OpenTelemetryLayer::new);
     tracing_subscriber::registry()
         .with(filter_layer)
-        .with(fmt_layer)
+        .with(text_layer)
+        .with(json_layer)
+        .with(otlp_layer)
         .init();
+    Ok(log_filter)
 }
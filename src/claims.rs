@@ -0,0 +1,192 @@
+//! Tracking of the node ids and IP addresses reporting each account, to detect spoofed accounts.
+//!
+//! The account id of a report is not authenticated, so any node can claim to be any validator. When
+//! enabled, every report with an account id records the claim of its node id and of the address it
+//! was sent from, keeping the addresses used over time. An account is suspicious when it reports
+//! from a new node id after reporting from another one, or from several node ids at the same time.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::IpAddr,
+};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::NaiveDateTime;
+use sea_orm::{
+    sea_query::{OnConflict, Query},
+    ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+};
+use serde::Serialize;
+use tracing::error;
+
+use crate::{
+    entities::node_claim,
+    nodes::ChainId,
+    request_id::{error_body, RequestId},
+    server::ServerState,
+    Error,
+};
+
+/// Why the claims of an account are suspicious.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum Suspicion {
+    /// The account started reporting from a new node id.
+    NewNode,
+    /// Several node ids reported the account.
+    MultipleNodes,
+}
+
+#[derive(Serialize, Debug)]
+struct SuspiciousAccount {
+    account_id: String,
+    reasons: Vec<Suspicion>,
+    claims: Vec<Claim>,
+}
+
+#[derive(Serialize, Debug)]
+struct Claim {
+    node_id: String,
+    ip: Option<String>,
+    first_seen: NaiveDateTime,
+    last_seen: NaiveDateTime,
+}
+
+pub(crate) async fn claims_handler(
+    state: State<ServerState>,
+    request_id: Option<Extension<RequestId>>,
+    Path(chain): Path<String>,
+) -> Response {
+    let chain = ChainId::from(chain.as_str());
+    let Some(db) = state.read_database(&chain) else {
+        return (StatusCode::NOT_FOUND, format!("unknown chain: {chain}")).into_response();
+    };
+    let window = chrono::Duration::seconds(state.claims.claim_window as i64);
+    let cutoff = chrono::offset::Utc::now().naive_utc() - window;
+    let claims = match recent_claims(db, cutoff).await {
+        Ok(claims) => claims,
+        Err(err) => {
+            error!("error loading {chain} claims: {err:#?}");
+            let request_id = request_id.map(|Extension(RequestId(id))| id);
+            let message = format!("error loading claims: {err}");
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                error_body(&message, request_id.as_deref()),
+            )
+                .into_response();
+        }
+    };
+    Json(suspicious_accounts(claims, cutoff)).into_response()
+}
+
+/// Returns every claim of the accounts reported since `cutoff`, the others can't be suspicious.
+async fn recent_claims(
+    db: &DatabaseConnection,
+    cutoff: NaiveDateTime,
+) -> Result<Vec<node_claim::Model>, Error> {
+    let recent_accounts = Query::select()
+        .distinct()
+        .column(node_claim::Column::AccountId)
+        .from(node_claim::Entity)
+        .and_where(node_claim::Column::LastSeen.gt(cutoff))
+        .to_owned();
+    let claims = node_claim::Entity::find()
+        .filter(node_claim::Column::AccountId.in_subquery(recent_accounts))
+        .all(db)
+        .await?;
+    Ok(claims)
+}
+
+/// Records that `node_id` reported `account_id` from `ip`, the address being empty if unknown.
+pub(crate) async fn record_claim(
+    db: &DatabaseConnection,
+    account_id: String,
+    node_id: String,
+    ip: Option<IpAddr>,
+    now: NaiveDateTime,
+) -> Result<(), Error> {
+    let claim = node_claim::ActiveModel {
+        account_id: ActiveValue::Set(account_id),
+        node_id: ActiveValue::Set(node_id),
+        ip: ActiveValue::Set(ip.map(|ip| ip.to_string()).unwrap_or_default()),
+        first_seen: ActiveValue::Set(now),
+        last_seen: ActiveValue::Set(now),
+    };
+    let on_conflict = OnConflict::columns([
+        node_claim::Column::AccountId,
+        node_claim::Column::NodeId,
+        node_claim::Column::Ip,
+    ])
+    .update_column(node_claim::Column::LastSeen)
+    .to_owned();
+    node_claim::Entity::insert(claim)
+        .on_conflict(on_conflict)
+        .exec_without_returning(db)
+        .await?;
+    Ok(())
+}
+
+/// Returns the accounts whose claims are suspicious since `cutoff`.
+fn suspicious_accounts(
+    claims: Vec<node_claim::Model>,
+    cutoff: NaiveDateTime,
+) -> Vec<SuspiciousAccount> {
+    let mut accounts: BTreeMap<String, Vec<node_claim::Model>> = BTreeMap::new();
+    for claim in claims {
+        accounts
+            .entry(claim.account_id.clone())
+            .or_default()
+            .push(claim);
+    }
+    accounts
+        .into_iter()
+        .filter_map(|(account_id, mut claims)| {
+            claims.sort_by_key(|claim| claim.first_seen);
+            // First and last report of each node id, from any address.
+            let mut nodes: HashMap<&str, (NaiveDateTime, NaiveDateTime)> = HashMap::new();
+            for claim in &claims {
+                let (first_seen, last_seen) = nodes
+                    .entry(&claim.node_id)
+                    .or_insert((claim.first_seen, claim.last_seen));
+                *first_seen = (*first_seen).min(claim.first_seen);
+                *last_seen = (*last_seen).max(claim.last_seen);
+            }
+            let mut reasons = Vec::new();
+            let first_seen = claims.first().map(|claim| claim.first_seen);
+            if nodes.values().any(|(node_first_seen, _)| {
+                *node_first_seen > cutoff && Some(*node_first_seen) > first_seen
+            }) {
+                reasons.push(Suspicion::NewNode);
+            }
+            if nodes
+                .values()
+                .filter(|(_, last_seen)| *last_seen > cutoff)
+                .count()
+                > 1
+            {
+                reasons.push(Suspicion::MultipleNodes);
+            }
+            if reasons.is_empty() {
+                return None;
+            }
+            Some(SuspiciousAccount {
+                account_id,
+                reasons,
+                claims: claims
+                    .into_iter()
+                    .map(|claim| Claim {
+                        node_id: claim.node_id,
+                        ip: (!claim.ip.is_empty()).then_some(claim.ip),
+                        first_seen: claim.first_seen,
+                        last_seen: claim.last_seen,
+                    })
+                    .collect(),
+            })
+        })
+        .collect()
+}
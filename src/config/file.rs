@@ -0,0 +1,253 @@
+//! Configuration file, layered below the environment and the command line.
+//!
+//! The file is a TOML document, or a YAML one if its extension is `.yaml` or `.yml`, whose keys
+//! are the names of the options in snake case, such as `retention_days = 30`. Keys can be grouped
+//! in tables, whose names are ignored:
+//! ```toml
+//! database_url = "postgres://telemetry@localhost"
+//!
+//! [retention]
+//! retention_days = 30
+//! retention_mode = "archive"
+//! ```
+//! File values are used as the defaults of the options, so that the environment variables and the
+//! flags take precedence over them.
+
+use std::{
+    collections::HashSet,
+    ffi::{OsStr, OsString},
+    fmt, fs,
+    path::Path,
+};
+
+use clap::{parser::ValueSource, Arg, ArgAction, ArgMatches, CommandFactory, FromArgMatches};
+use url::Url;
+
+use super::Config;
+use crate::Error;
+
+/// Options whose value must not be printed.
+const SECRETS: [&str; 8] = [
+    "database_url",
+    "admin_database_url",
+    "mainnet_replica_url",
+    "testnet_replica_url",
+    "validator_webhook_url",
+    "alert_webhook_url",
+    "api_keys",
+    "auth_token_secret",
+];
+/// Options that can't be set in the configuration file.
+const RESERVED: [&str; 3] = ["config_file", "help", "version"];
+const MASK: &str = "****";
+
+/// The effective configuration, with the origin of every value.
+#[derive(Debug)]
+pub struct EffectiveConfig {
+    matches: ArgMatches,
+    file_keys: HashSet<String>,
+}
+
+impl Config {
+    /// Parses the configuration from the command line `args`, the environment and the
+    /// configuration file set by `--config`, in decreasing order of precedence.
+    pub fn load<I, T>(args: I) -> Result<(Self, EffectiveConfig), Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+        let mut command = Config::command();
+
+        // Only look for the configuration file, the other errors are reported below.
+        let config_file = Config::command()
+            .ignore_errors(true)
+            .try_get_matches_from(&args)
+            .ok()
+            .and_then(|matches| {
+                matches
+                    .get_one::<std::path::PathBuf>("config_file")
+                    .cloned()
+            });
+        let mut file_keys = HashSet::new();
+        if let Some(path) = config_file {
+            for (key, values) in read_file(&path)? {
+                let id = key.rsplit('.').next().unwrap_or(&key).to_string();
+                let Some(arg) = command
+                    .get_arguments()
+                    .find(|arg| arg.get_id() == id.as_str() && !RESERVED.contains(&id.as_str()))
+                else {
+                    return Err(Error::ConfigError(format!(
+                        "unknown key `{key}` in {}",
+                        path.display()
+                    )));
+                };
+                validate(arg, &values).map_err(|err| {
+                    Error::ConfigError(format!(
+                        "invalid value for `{key}` in {}: {err}",
+                        path.display()
+                    ))
+                })?;
+                command = command.mut_arg(id.as_str(), |arg| {
+                    arg.default_values(values).required(false)
+                });
+                file_keys.insert(id);
+            }
+        }
+
+        let matches = command.try_get_matches_from(&args)?;
+        let config = Config::from_arg_matches(&matches)?;
+        Ok((config, EffectiveConfig { matches, file_keys }))
+    }
+}
+
+/// Reads the values of the configuration file at `path`, by key.
+fn read_file(path: &Path) -> Result<Vec<(String, Vec<String>)>, Error> {
+    let content = fs::read_to_string(path)?;
+    let is_yaml = path
+        .extension()
+        .is_some_and(|extension| extension == "yaml" || extension == "yml");
+    let table: toml::Table = if is_yaml {
+        serde_yaml_ng::from_str(&content).map_err(|err| err.to_string())
+    } else {
+        content
+            .parse()
+            .map_err(|err: toml::de::Error| err.to_string())
+    }
+    .map_err(|err| {
+        Error::ConfigError(format!(
+            "invalid configuration file {}: {err}",
+            path.display()
+        ))
+    })?;
+    let mut values = Vec::new();
+    for (key, value) in table {
+        match value {
+            toml::Value::Table(group) => {
+                for (name, value) in group {
+                    let key = format!("{key}.{name}");
+                    let value = to_strings(&key, value, path)?;
+                    values.push((key, value));
+                }
+            }
+            value => {
+                let value = to_strings(&key, value, path)?;
+                values.push((key, value));
+            }
+        }
+    }
+    Ok(values)
+}
+
+fn to_strings(key: &str, value: toml::Value, path: &Path) -> Result<Vec<String>, Error> {
+    let to_string = |value: toml::Value| match value {
+        toml::Value::String(s) => Ok(s),
+        toml::Value::Integer(n) => Ok(n.to_string()),
+        toml::Value::Float(n) => Ok(n.to_string()),
+        toml::Value::Boolean(b) => Ok(b.to_string()),
+        _ => Err(Error::ConfigError(format!(
+            "unsupported value for `{key}` in {}",
+            path.display()
+        ))),
+    };
+    match value {
+        toml::Value::Array(values) => values.into_iter().map(to_string).collect(),
+        value => Ok(vec![to_string(value)?]),
+    }
+}
+
+/// Checks that `values` are accepted by the parser of `arg`.
+fn validate(arg: &Arg, values: &[String]) -> Result<(), String> {
+    let multiple = matches!(arg.get_action(), ArgAction::Append)
+        || arg.get_num_args().is_some_and(|n| n.max_values() > 1);
+    if values.len() > 1 && !multiple {
+        return Err("a single value is expected".to_string());
+    }
+    // Parse the values as the defaults of a command made of the single argument.
+    let arg = arg
+        .clone()
+        .env(None::<&str>)
+        .required(false)
+        .default_values(values);
+    clap::Command::new("config")
+        .no_binary_name(true)
+        .arg(arg)
+        .try_get_matches_from(Vec::<OsString>::new())
+        .map(|_| ())
+        .map_err(|err| {
+            // Keep the reason only, without the usage.
+            let message = err.to_string();
+            let reason = message.lines().next().unwrap_or_default();
+            reason.trim_start_matches("error: ").to_string()
+        })
+}
+
+impl EffectiveConfig {
+    /// Returns the options whose value differs in `other`.
+    pub(crate) fn changes(&self, other: &EffectiveConfig) -> Vec<String> {
+        let values = |matches: &ArgMatches, id: &str| -> Option<Vec<OsString>> {
+            Some(matches.get_raw(id)?.map(OsStr::to_os_string).collect())
+        };
+        Config::command()
+            .get_arguments()
+            .map(|arg| arg.get_id().to_string())
+            .filter(|id| values(&self.matches, id) != values(&other.matches, id))
+            .collect()
+    }
+}
+
+impl fmt::Display for EffectiveConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for arg in Config::command().get_arguments() {
+            let id = arg.get_id().as_str();
+            if RESERVED.contains(&id) {
+                continue;
+            }
+            let Some(values) = self.matches.get_raw(id) else {
+                continue;
+            };
+            let values: Vec<String> = values
+                .map(|value| {
+                    let value = value.to_string_lossy();
+                    if SECRETS.contains(&id) {
+                        mask(&value)
+                    } else {
+                        value.into_owned()
+                    }
+                })
+                .collect();
+            let source = match self.matches.value_source(id) {
+                Some(ValueSource::CommandLine) => "command line",
+                Some(ValueSource::EnvVariable) => "environment",
+                _ if self.file_keys.contains(id) => "file",
+                _ => "default",
+            };
+            match values.as_slice() {
+                [value] => writeln!(f, "{id} = {value:?} # {source}")?,
+                _ => writeln!(f, "{id} = {values:?} # {source}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Masks the credentials and path of a URL, or the whole value if it isn't a URL.
+fn mask(value: &str) -> String {
+    let Ok(mut url) = Url::parse(value) else {
+        return MASK.to_string();
+    };
+    // Values such as `key:scope` parse as URLs without host.
+    if !url.has_host() {
+        return MASK.to_string();
+    }
+    if url.password().is_some() {
+        let _ = url.set_password(Some(MASK));
+    }
+    if url.path() != "/" && !url.path().is_empty() {
+        url.set_path(MASK);
+    }
+    if url.query().is_some() {
+        url.set_query(Some(MASK));
+    }
+    url.to_string()
+}
@@ -0,0 +1,722 @@
+use std::{
+    fmt,
+    net::{AddrParseError, SocketAddr},
+    path::PathBuf,
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use tracing_subscriber::{filter::ParseError, EnvFilter};
+
+use crate::{
+    database::{MAINNET_DB_NAME, TESTNET_DB_NAME},
+    Error,
+};
+
+mod file;
+pub use file::EffectiveConfig;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Config {
+    /// TOML configuration file. Its values are overridden by the environment and the flags.
+    #[clap(env, long = "config", value_name = "PATH")]
+    pub config_file: Option<PathBuf>,
+    #[command(flatten)]
+    pub database: DatabaseConfig,
+    #[command(flatten)]
+    pub replicas: ReplicasConfig,
+    #[command(flatten)]
+    pub health: HealthConfig,
+    /// HTTP server address.
+    #[clap(env, short, long, default_value = "0.0.0.0:8080")]
+    #[arg(value_parser = parse_addr)]
+    pub server_address: SocketAddr,
+    /// Generate the database schema and exit.
+    #[clap(long, default_value_t = false)]
+    pub generate_schema: bool,
+    /// Don't apply the pending migrations at startup, and refuse to start if there are any.
+    #[clap(env, long, default_value_t = false, conflicts_with = "generate_schema")]
+    pub no_auto_migrate: bool,
+    #[command(flatten)]
+    pub tls: TlsConfig,
+    #[command(flatten)]
+    pub auth: AuthConfig,
+    #[command(flatten)]
+    pub admin: AdminConfig,
+    #[command(flatten)]
+    pub fork_detection: ForkDetectionConfig,
+    #[command(flatten)]
+    pub sync_status: SyncStatusConfig,
+    #[command(flatten)]
+    pub validators: ValidatorsConfig,
+    #[command(flatten)]
+    pub claims: ClaimsConfig,
+    #[command(flatten)]
+    pub stream: StreamConfig,
+    #[command(flatten)]
+    pub alerts: AlertsConfig,
+    #[command(flatten)]
+    pub retention: RetentionConfig,
+    #[command(flatten)]
+    pub rollups: RollupsConfig,
+    #[command(flatten)]
+    pub history: HistoryConfig,
+    #[command(flatten)]
+    pub node_metrics: NodeMetricsConfig,
+    #[command(flatten)]
+    pub network_stats: NetworkStatsConfig,
+    #[command(flatten)]
+    pub tracing: TracingConfig,
+    #[command(flatten)]
+    pub logs: LogsConfig,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Prune the stale nodes according to the retention policy, maintain the history partitions
+    /// and exit.
+    Prune {
+        /// Only report what would be pruned, without modifying the database.
+        #[clap(long, default_value_t = false)]
+        dry_run: bool,
+    },
+    /// Manage the database migrations and exit.
+    Migrate {
+        /// Only migrate the database of the given chain. Every database is migrated if not set.
+        #[clap(long, value_parser = [MAINNET_DB_NAME, TESTNET_DB_NAME])]
+        chain: Option<String>,
+        #[command(subcommand)]
+        action: MigrateAction,
+    },
+    /// Manage the configuration and exit.
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigAction {
+    /// Validate the configuration and print the effective one, with the secrets masked.
+    Check,
+}
+
+#[derive(Subcommand, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrateAction {
+    /// Show whether each migration is applied or pending.
+    Status,
+    /// Apply all the pending migrations.
+    Up,
+    /// Roll back the last applied migrations.
+    Down {
+        /// Number of migrations to roll back.
+        #[clap(default_value_t = 1)]
+        n: u32,
+    },
+    /// Drop every table, then apply all the migrations. Requires `--chain`, or `--all` to reset
+    /// every database.
+    Fresh {
+        /// Reset every database when no chain is set.
+        #[clap(long, default_value_t = false)]
+        all: bool,
+    },
+}
+
+const DEFAULT_MAX_CONNECTIONS: u32 = 10;
+const DEFAULT_DB_ACQUIRE_TIMEOUT: u64 = 30;
+const DEFAULT_DB_IDLE_TIMEOUT: u64 = 600;
+const DEFAULT_DB_MAX_LIFETIME: u64 = 1800;
+const DEFAULT_DB_CONNECT_RETRIES: u32 = 10;
+const DEFAULT_DB_CONNECT_BACKOFF: u64 = 1;
+const DEFAULT_DB_CONNECT_MAX_BACKOFF: u64 = 30;
+
+#[derive(Args, Debug, Clone)]
+pub struct DatabaseConfig {
+    /// Database connection URL.
+    #[clap(env)]
+    pub database_url: String,
+    /// Database connection URL used to create the databases that don't exist yet. The database URL
+    /// is used if not set.
+    #[clap(env, long)]
+    pub admin_database_url: Option<String>,
+    /// Postgres sslmode setting, overriding the one of the database URL. Defaults to `prefer`.
+    #[clap(env, long)]
+    pub sslmode: Option<String>,
+    /// Maximum number of database connections.
+    #[clap(env, short, long, default_value_t = DEFAULT_MAX_CONNECTIONS)]
+    pub max_connections: u32,
+    /// Seconds to wait for a free connection of the pool.
+    #[clap(env, long, default_value_t = DEFAULT_DB_ACQUIRE_TIMEOUT)]
+    pub db_acquire_timeout: u64,
+    /// Seconds after which idle connections of the pool are closed.
+    #[clap(env, long, default_value_t = DEFAULT_DB_IDLE_TIMEOUT)]
+    pub db_idle_timeout: u64,
+    /// Seconds after which connections of the pool are closed and replaced.
+    #[clap(env, long, default_value_t = DEFAULT_DB_MAX_LIFETIME)]
+    pub db_max_lifetime: u64,
+    /// Milliseconds after which Postgres aborts a statement. Statements never time out if not set.
+    #[clap(env, long)]
+    pub db_statement_timeout: Option<u64>,
+    /// Number of times connecting to and migrating a database is retried at startup.
+    #[clap(env, long, default_value_t = DEFAULT_DB_CONNECT_RETRIES)]
+    pub db_connect_retries: u32,
+    /// Seconds before the first startup retry, doubled at every subsequent attempt.
+    #[clap(env, long, default_value_t = DEFAULT_DB_CONNECT_BACKOFF)]
+    pub db_connect_backoff: u64,
+    /// Maximum seconds between two startup retries.
+    #[clap(env, long, default_value_t = DEFAULT_DB_CONNECT_MAX_BACKOFF)]
+    pub db_connect_max_backoff: u64,
+}
+
+const DEFAULT_REPLICA_CHECK_INTERVAL: u64 = 10;
+
+#[derive(Args, Debug, Clone)]
+pub struct ReplicasConfig {
+    /// Connection URL of the read-only replica serving the mainnet queries, like the database URL.
+    #[clap(env, long)]
+    pub mainnet_replica_url: Option<String>,
+    /// Connection URL of the read-only replica serving the testnet queries, like the database URL.
+    #[clap(env, long)]
+    pub testnet_replica_url: Option<String>,
+    /// Seconds between two health checks of the replicas.
+    #[clap(env, long, default_value_t = DEFAULT_REPLICA_CHECK_INTERVAL)]
+    pub replica_check_interval: u64,
+}
+
+impl Default for ReplicasConfig {
+    fn default() -> Self {
+        Self {
+            mainnet_replica_url: None,
+            testnet_replica_url: None,
+            replica_check_interval: DEFAULT_REPLICA_CHECK_INTERVAL,
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct HealthConfig {
+    /// Chains whose database must be reachable for `/readyz` to report the service as ready.
+    #[clap(
+        env,
+        long,
+        value_delimiter = ',',
+        value_parser = [MAINNET_DB_NAME, TESTNET_DB_NAME],
+        default_values_t = [MAINNET_DB_NAME.to_string(), TESTNET_DB_NAME.to_string()]
+    )]
+    pub ready_chains: Vec<String>,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            ready_chains: vec![MAINNET_DB_NAME.to_string(), TESTNET_DB_NAME.to_string()],
+        }
+    }
+}
+
+const DEFAULT_FORK_DETECTION_INTERVAL: u64 = 60;
+const DEFAULT_FORK_DETECTION_WINDOW: u64 = 300;
+
+#[derive(Args, Debug, Clone)]
+pub struct ForkDetectionConfig {
+    /// Seconds between two runs of the fork detector.
+    #[clap(env, long, default_value_t = DEFAULT_FORK_DETECTION_INTERVAL)]
+    pub fork_detection_interval: u64,
+    /// Only nodes seen in the last given seconds are checked for forks.
+    #[clap(env, long, default_value_t = DEFAULT_FORK_DETECTION_WINDOW)]
+    pub fork_detection_window: u64,
+}
+
+impl Default for ForkDetectionConfig {
+    fn default() -> Self {
+        Self {
+            fork_detection_interval: DEFAULT_FORK_DETECTION_INTERVAL,
+            fork_detection_window: DEFAULT_FORK_DETECTION_WINDOW,
+        }
+    }
+}
+
+const DEFAULT_SYNC_STATUS_INTERVAL: u64 = 60;
+const DEFAULT_LAG_THRESHOLD: i64 = 50;
+const DEFAULT_STALL_AFTER: u64 = 120;
+const DEFAULT_OFFLINE_AFTER: u64 = 600;
+
+#[derive(Args, Debug, Clone)]
+pub struct SyncStatusConfig {
+    /// Seconds between two classifications of the nodes sync status.
+    #[clap(env, long, default_value_t = DEFAULT_SYNC_STATUS_INTERVAL)]
+    pub sync_status_interval: u64,
+    /// Number of blocks behind the network head after which a node is lagging.
+    #[clap(env, long, default_value_t = DEFAULT_LAG_THRESHOLD)]
+    pub lag_threshold: i64,
+    /// Seconds a node can keep reporting the same height before being considered stalled.
+    #[clap(env, long, default_value_t = DEFAULT_STALL_AFTER)]
+    pub stall_after: u64,
+    /// Seconds without reports after which a node is considered offline.
+    #[clap(env, long, default_value_t = DEFAULT_OFFLINE_AFTER)]
+    pub offline_after: u64,
+}
+
+impl Default for SyncStatusConfig {
+    fn default() -> Self {
+        Self {
+            sync_status_interval: DEFAULT_SYNC_STATUS_INTERVAL,
+            lag_threshold: DEFAULT_LAG_THRESHOLD,
+            stall_after: DEFAULT_STALL_AFTER,
+            offline_after: DEFAULT_OFFLINE_AFTER,
+        }
+    }
+}
+
+const DEFAULT_VALIDATOR_CHECK_INTERVAL: u64 = 60;
+const DEFAULT_VALIDATOR_OFFLINE_AFTER: u64 = 300;
+const DEFAULT_VALIDATOR_LAG_THRESHOLD: i64 = 50;
+
+#[derive(Args, Debug, Clone)]
+pub struct ValidatorsConfig {
+    /// Seconds between two checks of the validators health.
+    #[clap(env, long, default_value_t = DEFAULT_VALIDATOR_CHECK_INTERVAL)]
+    pub validator_check_interval: u64,
+    /// Seconds without reports after which a validator is considered offline.
+    #[clap(env, long, default_value_t = DEFAULT_VALIDATOR_OFFLINE_AFTER)]
+    pub validator_offline_after: u64,
+    /// Number of blocks behind the network head after which a validator is considered behind.
+    #[clap(env, long, default_value_t = DEFAULT_VALIDATOR_LAG_THRESHOLD)]
+    pub validator_lag_threshold: i64,
+    /// URL of the webhook notified about unhealthy validators.
+    #[clap(env, long)]
+    pub validator_webhook_url: Option<String>,
+}
+
+impl Default for ValidatorsConfig {
+    fn default() -> Self {
+        Self {
+            validator_check_interval: DEFAULT_VALIDATOR_CHECK_INTERVAL,
+            validator_offline_after: DEFAULT_VALIDATOR_OFFLINE_AFTER,
+            validator_lag_threshold: DEFAULT_VALIDATOR_LAG_THRESHOLD,
+            validator_webhook_url: None,
+        }
+    }
+}
+
+const DEFAULT_CLAIM_WINDOW: u64 = 86400;
+
+#[derive(Args, Debug, Clone)]
+pub struct ClaimsConfig {
+    /// Record which node ids and IP addresses report each account, to detect spoofed accounts.
+    #[clap(env, long, default_value_t = false)]
+    pub track_claims: bool,
+    /// Seconds within which an account reporting from a new node id, or from several node ids, is
+    /// considered suspicious.
+    #[clap(env, long, default_value_t = DEFAULT_CLAIM_WINDOW)]
+    pub claim_window: u64,
+}
+
+impl Default for ClaimsConfig {
+    fn default() -> Self {
+        Self {
+            track_claims: false,
+            claim_window: DEFAULT_CLAIM_WINDOW,
+        }
+    }
+}
+
+const DEFAULT_STREAM_BUFFER: usize = 1024;
+const DEFAULT_STREAM_HEARTBEAT_INTERVAL: u64 = 15;
+
+#[derive(Args, Debug, Clone)]
+pub struct StreamConfig {
+    /// Number of node updates buffered for the stream clients, at least 1. Clients falling further
+    /// behind skip the oldest updates.
+    #[clap(env, long, default_value_t = DEFAULT_STREAM_BUFFER)]
+    pub stream_buffer: usize,
+    /// Seconds between two heartbeats sent to the stream clients. 0 disables the heartbeat.
+    #[clap(env, long, default_value_t = DEFAULT_STREAM_HEARTBEAT_INTERVAL)]
+    pub stream_heartbeat_interval: u64,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            stream_buffer: DEFAULT_STREAM_BUFFER,
+            stream_heartbeat_interval: DEFAULT_STREAM_HEARTBEAT_INTERVAL,
+        }
+    }
+}
+
+const DEFAULT_ALERT_INTERVAL: u64 = 60;
+
+#[derive(Args, Debug, Clone)]
+pub struct AlertsConfig {
+    /// Path of the TOML file declaring the alert rules.
+    #[clap(env, long)]
+    pub alert_rules: Option<PathBuf>,
+    /// Seconds between two evaluations of the alert rules.
+    #[clap(env, long, default_value_t = DEFAULT_ALERT_INTERVAL)]
+    pub alert_interval: u64,
+    /// URL of the webhook notified about alerts, unless overridden by the rule.
+    #[clap(env, long)]
+    pub alert_webhook_url: Option<String>,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            alert_rules: None,
+            alert_interval: DEFAULT_ALERT_INTERVAL,
+            alert_webhook_url: None,
+        }
+    }
+}
+
+const DEFAULT_PRUNE_INTERVAL: u64 = 3600;
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetentionMode {
+    /// Delete the stale nodes.
+    Delete,
+    /// Move the stale nodes to the `node_archive` table.
+    Archive,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct RetentionConfig {
+    /// Days without reports after which a node is pruned. Nodes are never pruned if not set.
+    #[clap(env, long)]
+    pub retention_days: Option<u64>,
+    /// What to do with the pruned nodes.
+    #[clap(env, long, value_enum, default_value_t = RetentionMode::Delete)]
+    pub retention_mode: RetentionMode,
+    /// Days after which archived nodes are deleted. Archived nodes are kept forever if not set.
+    #[clap(env, long)]
+    pub archive_retention_days: Option<u64>,
+    /// Seconds between two pruning runs.
+    #[clap(env, long, default_value_t = DEFAULT_PRUNE_INTERVAL)]
+    pub prune_interval: u64,
+    /// Only report what would be pruned, without modifying the database.
+    #[clap(env, long, default_value_t = false)]
+    pub prune_dry_run: bool,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            retention_days: None,
+            retention_mode: RetentionMode::Delete,
+            archive_retention_days: None,
+            prune_interval: DEFAULT_PRUNE_INTERVAL,
+            prune_dry_run: false,
+        }
+    }
+}
+
+const DEFAULT_ROLLUP_INTERVAL: u64 = 300;
+
+#[derive(Args, Debug, Clone)]
+pub struct RollupsConfig {
+    /// Seconds between two updates of the hourly and daily rollups.
+    #[clap(env, long, default_value_t = DEFAULT_ROLLUP_INTERVAL)]
+    pub rollup_interval: u64,
+}
+
+impl Default for RollupsConfig {
+    fn default() -> Self {
+        Self {
+            rollup_interval: DEFAULT_ROLLUP_INTERVAL,
+        }
+    }
+}
+
+const DEFAULT_HISTORY_RETENTION_DAYS: u64 = 30;
+const DEFAULT_HISTORY_PARTITIONS_AHEAD: u64 = 3;
+const DEFAULT_HISTORY_MAINTENANCE_INTERVAL: u64 = 3600;
+
+#[derive(Args, Debug, Clone)]
+pub struct HistoryConfig {
+    /// Store every report in the `node_history` table, partitioned by day.
+    #[clap(env, long, default_value_t = false)]
+    pub store_history: bool,
+    /// Days after which the history partitions are dropped.
+    #[clap(env, long, default_value_t = DEFAULT_HISTORY_RETENTION_DAYS)]
+    pub history_retention_days: u64,
+    /// Number of daily history partitions created in advance, including today.
+    #[clap(env, long, default_value_t = DEFAULT_HISTORY_PARTITIONS_AHEAD)]
+    pub history_partitions_ahead: u64,
+    /// Seconds between two maintenance runs of the history partitions.
+    #[clap(env, long, default_value_t = DEFAULT_HISTORY_MAINTENANCE_INTERVAL)]
+    pub history_maintenance_interval: u64,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            store_history: false,
+            history_retention_days: DEFAULT_HISTORY_RETENTION_DAYS,
+            history_partitions_ahead: DEFAULT_HISTORY_PARTITIONS_AHEAD,
+            history_maintenance_interval: DEFAULT_HISTORY_MAINTENANCE_INTERVAL,
+        }
+    }
+}
+
+const DEFAULT_NODE_METRICS_MAX_NODES: u64 = 1000;
+const DEFAULT_NODE_METRICS_WINDOW: u64 = 3600;
+
+#[derive(Args, Debug, Clone)]
+pub struct NodeMetricsConfig {
+    /// Export the metrics of every node at `/metrics/nodes`.
+    #[clap(env, long, default_value_t = false)]
+    pub node_metrics: bool,
+    /// Maximum number of nodes exported per chain, the most recently seen first.
+    #[clap(env, long, default_value_t = DEFAULT_NODE_METRICS_MAX_NODES)]
+    pub node_metrics_max_nodes: u64,
+    /// Only export the nodes that reported within this number of seconds.
+    #[clap(env, long, default_value_t = DEFAULT_NODE_METRICS_WINDOW)]
+    pub node_metrics_window: u64,
+    /// Only export the nodes whose id or account is listed. Every node is exported if empty.
+    #[clap(env, long, value_delimiter = ',')]
+    pub node_metrics_allowlist: Vec<String>,
+}
+
+impl Default for NodeMetricsConfig {
+    fn default() -> Self {
+        Self {
+            node_metrics: false,
+            node_metrics_max_nodes: DEFAULT_NODE_METRICS_MAX_NODES,
+            node_metrics_window: DEFAULT_NODE_METRICS_WINDOW,
+            node_metrics_allowlist: Vec::new(),
+        }
+    }
+}
+
+const DEFAULT_NETWORK_STATS_INTERVAL: u64 = 60;
+const DEFAULT_STALE_AFTER: u64 = 600;
+
+#[derive(Args, Debug, Clone)]
+pub struct NetworkStatsConfig {
+    /// Seconds between two updates of the network gauges.
+    #[clap(env, long, default_value_t = DEFAULT_NETWORK_STATS_INTERVAL)]
+    pub network_stats_interval: u64,
+    /// Seconds without report after which a node is stale rather than active.
+    #[clap(env, long, default_value_t = DEFAULT_STALE_AFTER)]
+    pub stale_after: u64,
+}
+
+impl Default for NetworkStatsConfig {
+    fn default() -> Self {
+        Self {
+            network_stats_interval: DEFAULT_NETWORK_STATS_INTERVAL,
+            stale_after: DEFAULT_STALE_AFTER,
+        }
+    }
+}
+
+const DEFAULT_TLS_RELOAD_INTERVAL: u64 = 60;
+
+#[derive(Args, Debug, Clone)]
+pub struct TlsConfig {
+    /// PEM file of the server certificate chain. The server is served over TLS if set, together
+    /// with `--tls-key`.
+    #[clap(env, long, value_name = "PATH")]
+    pub tls_cert: Option<PathBuf>,
+    /// PEM file of the server private key.
+    #[clap(env, long, value_name = "PATH")]
+    pub tls_key: Option<PathBuf>,
+    /// PEM file of the CAs of the client certificates. If set, the read APIs are only served to
+    /// clients presenting a certificate signed by one of them, while ingestion and the health
+    /// probes stay open to every client.
+    #[clap(env, long, value_name = "PATH")]
+    pub tls_client_ca: Option<PathBuf>,
+    /// Seconds between two checks for changes of the certificate files, which are then reloaded.
+    /// 0 disables the reload.
+    #[clap(env, long, default_value_t = DEFAULT_TLS_RELOAD_INTERVAL)]
+    pub tls_reload_interval: u64,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            tls_cert: None,
+            tls_key: None,
+            tls_client_ca: None,
+            tls_reload_interval: DEFAULT_TLS_RELOAD_INTERVAL,
+        }
+    }
+}
+
+const DEFAULT_BAN_REFRESH_INTERVAL: u64 = 60;
+const DEFAULT_TRUSTED_PROXIES: usize = 1;
+
+#[derive(Args, Debug, Clone)]
+pub struct AdminConfig {
+    /// Seconds between two reloads of the bans from the databases, to pick up the bans made
+    /// through other instances. 0 disables the reload.
+    #[clap(env, long, default_value_t = DEFAULT_BAN_REFRESH_INTERVAL)]
+    pub ban_refresh_interval: u64,
+    /// Header holding the client IP address, such as `X-Forwarded-For` behind a proxy. The address
+    /// of the peer is used if not set.
+    #[clap(env, long)]
+    pub client_ip_header: Option<String>,
+    /// Number of trusted proxies appending to `client_ip_header`. The client address is the one
+    /// appended by the outermost of them, counting from the right, since the addresses on its left
+    /// are sent by the client. The peer address is used if the header holds fewer addresses.
+    #[clap(env, long, default_value_t = DEFAULT_TRUSTED_PROXIES)]
+    pub trusted_proxies: usize,
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            ban_refresh_interval: DEFAULT_BAN_REFRESH_INTERVAL,
+            client_ip_header: None,
+            trusted_proxies: DEFAULT_TRUSTED_PROXIES,
+        }
+    }
+}
+
+/// Permission granted to the clients of a group of routes.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scope {
+    /// Report telemetry.
+    Ingest,
+    /// Query the stored telemetry and the metrics.
+    Read,
+    /// Administer the service, implying every other scope.
+    Admin,
+}
+
+/// Static API key and the scopes it grants.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ApiKey {
+    pub key: String,
+    pub scopes: Vec<Scope>,
+}
+
+impl fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ApiKey")
+            .field("key", &"****")
+            .field("scopes", &self.scopes)
+            .finish()
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct AuthConfig {
+    /// Static API keys, as `<key>:<scopes>` with the scopes separated by `+`, such as
+    /// `s3cr3t:read+admin`. Authentication is enforced once API keys or a token secret are set.
+    #[clap(env, long, value_delimiter = ',', value_parser = parse_api_key)]
+    pub api_keys: Vec<ApiKey>,
+    /// Secret of the bearer tokens, JWTs signed with HMAC-SHA256 whose `scope` claim lists the
+    /// granted scopes separated by spaces.
+    #[clap(env, long)]
+    pub auth_token_secret: Option<String>,
+    /// Scopes granted to the requests without credentials when authentication is enforced.
+    #[clap(env, long, value_enum, value_delimiter = ',', default_value = "ingest")]
+    pub anonymous_scopes: Vec<Scope>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            api_keys: Vec::new(),
+            auth_token_secret: None,
+            anonymous_scopes: vec![Scope::Ingest],
+        }
+    }
+}
+
+const DEFAULT_OTLP_SERVICE_NAME: &str = "telemetry-service";
+
+#[derive(Args, Debug, Clone)]
+pub struct TracingConfig {
+    /// OTLP gRPC endpoint of the collector the traces are exported to, such as
+    /// `http://localhost:4317`. Traces are not exported if not set.
+    #[clap(env, long)]
+    pub otlp_endpoint: Option<String>,
+    /// Service name of the exported traces.
+    #[clap(env, long, default_value = DEFAULT_OTLP_SERVICE_NAME)]
+    pub otlp_service_name: String,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            otlp_service_name: DEFAULT_OTLP_SERVICE_NAME.to_string(),
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human readable lines.
+    Text,
+    /// One JSON object per line, including the fields of the enclosing spans.
+    Json,
+}
+
+const DEFAULT_LOG_LEVEL: &str = "info,sqlx=warn";
+
+#[derive(Args, Debug, Clone)]
+pub struct LogsConfig {
+    /// Log filter, such as `info,telemetry_service=debug`. Defaults to the `RUST_LOG` environment
+    /// variable, or `info,sqlx=warn`.
+    #[clap(env, long)]
+    #[arg(value_parser = parse_log_level)]
+    pub log_level: Option<String>,
+    /// Format of the logs.
+    #[clap(env, long, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+    /// Log the body of the ingestion requests at trace level, with the account ids redacted.
+    #[clap(env, long, default_value_t = false)]
+    pub log_request_bodies: bool,
+}
+
+impl Default for LogsConfig {
+    fn default() -> Self {
+        Self {
+            log_level: None,
+            log_format: LogFormat::Text,
+            log_request_bodies: false,
+        }
+    }
+}
+
+impl LogsConfig {
+    /// Returns the filter of the logs.
+    pub fn log_filter(&self) -> Result<EnvFilter, Error> {
+        match &self.log_level {
+            Some(level) => EnvFilter::try_new(level),
+            None => {
+                EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new(DEFAULT_LOG_LEVEL))
+            }
+        }
+        .map_err(|err| Error::ConfigError(format!("invalid log level: {err}")))
+    }
+}
+
+fn parse_addr(arg: &str) -> Result<SocketAddr, AddrParseError> {
+    arg.parse()
+}
+
+fn parse_api_key(arg: &str) -> Result<ApiKey, String> {
+    let (key, scopes) = arg.rsplit_once(':').ok_or("expected `<key>:<scopes>`")?;
+    if key.is_empty() {
+        return Err("empty key".to_string());
+    }
+    let scopes = scopes
+        .split('+')
+        .map(|scope| Scope::from_str(scope, true))
+        .collect::<Result<_, _>>()?;
+    Ok(ApiKey {
+        key: key.to_string(),
+        scopes,
+    })
+}
+
+fn parse_log_level(arg: &str) -> Result<String, ParseError> {
+    EnvFilter::try_new(arg)?;
+    Ok(arg.to_string())
+}
@@ -1,50 +1,179 @@
 use sea_orm::{
     ConnectOptions, ConnectionTrait, Database, DatabaseConnection, DbBackend, Statement,
 };
-use sea_orm_migration::MigratorTrait;
-use tracing::info;
+use std::{collections::HashSet, future::Future, time::Duration};
 
-use crate::{migrator::Migrator, Error};
+use sea_orm_migration::{MigrationStatus, MigratorTrait};
+use serde::Serialize;
+use tokio::time;
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::{
+    config::{DatabaseConfig, MigrateAction},
+    migrator::Migrator,
+    Error,
+};
 
 pub const MAINNET_DB_NAME: &str = "mainnet";
 pub const TESTNET_DB_NAME: &str = "testnet";
 
-async fn connect(
+/// sslmode used when neither the URL nor the configuration set one.
+const DEFAULT_SSLMODE: &str = "prefer";
+
+/// Returns `db_url` pointing to the database `db_name`, if any, with the given sslmode.
+///
+/// `sslmode` overrides the one set in the URL, if any. `statement_timeout` is in milliseconds.
+pub fn database_url(
     db_url: &str,
-    db_name: &str,
+    db_name: Option<&str>,
+    sslmode: Option<&str>,
+    statement_timeout: Option<u64>,
+) -> Result<String, Error> {
+    let mut url = Url::parse(db_url)
+        .map_err(|err| Error::ConfigError(format!("invalid database URL: {err}")))?;
+    if let Some(db_name) = db_name {
+        url.path_segments_mut()
+            .map_err(|_| Error::ConfigError("invalid database URL: no host".to_string()))?
+            .clear()
+            .push(db_name);
+    }
+    let mut params: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    match sslmode {
+        Some(sslmode) => {
+            params.retain(|(key, _)| key != "sslmode");
+            params.push(("sslmode".to_string(), sslmode.to_string()));
+        }
+        None if !params.iter().any(|(key, _)| key == "sslmode") => {
+            params.push(("sslmode".to_string(), DEFAULT_SSLMODE.to_string()));
+        }
+        None => {}
+    }
+    if let Some(statement_timeout) = statement_timeout {
+        // Runtime parameters are passed to Postgres through the `options` parameter.
+        let option = format!("-c statement_timeout={statement_timeout}");
+        match params.iter_mut().find(|(key, _)| key == "options") {
+            Some((_, options)) => *options = format!("{options} {option}"),
+            None => params.push(("options".to_string(), option)),
+        }
+    }
+    url.query_pairs_mut().clear().extend_pairs(params);
+    Ok(url.into())
+}
+
+/// Utilisation of a database connection pool.
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct PoolStats {
+    /// Number of open connections, idle or in use.
+    pub size: u32,
+    pub idle: u32,
+    pub max: u32,
+}
+
+/// Returns the utilisation of the pool of `db`, if it has one.
+pub fn pool_stats(db: &DatabaseConnection) -> Option<PoolStats> {
+    // Mock connections used in tests don't have a pool.
+    let DatabaseConnection::SqlxPostgresPoolConnection(_) = db else {
+        return None;
+    };
+    let pool = db.get_postgres_connection_pool();
+    Some(PoolStats {
+        size: pool.size(),
+        idle: pool.num_idle() as u32,
+        max: pool.options().get_max_connections(),
+    })
+}
+
+/// Returns the names of the applied migrations, without modifying the database.
+///
+/// The migration table is only created when applying the first migration, so a database without
+/// it has none applied.
+async fn applied_migrations(db: &DatabaseConnection) -> Result<HashSet<String>, Error> {
+    let backend = db.get_database_backend();
+    let table = Migrator::migration_table_name().to_string();
+    let exists: bool = db
+        .query_one(Statement::from_sql_and_values(
+            backend,
+            "SELECT EXISTS (SELECT 1 FROM information_schema.tables \
+             WHERE table_schema = current_schema() AND table_name = $1) AS \"exists\"",
+            [table.clone().into()],
+        ))
+        .await?
+        .map(|row| row.try_get("", "exists"))
+        .transpose()?
+        .unwrap_or_default();
+    if !exists {
+        return Ok(HashSet::new());
+    }
+    let applied = db
+        .query_all(Statement::from_string(
+            backend,
+            format!("SELECT version FROM {}", quote_identifier(&table)),
+        ))
+        .await?
+        .iter()
+        .map(|row| row.try_get("", "version"))
+        .collect::<Result<_, _>>()?;
+    Ok(applied)
+}
+
+/// Returns the names of the migrations not applied yet, without modifying the database.
+pub async fn pending_migrations(db: &DatabaseConnection) -> Result<Vec<String>, Error> {
+    let applied = applied_migrations(db).await?;
+    Ok(Migrator::migrations()
+        .iter()
+        .map(|migration| migration.name().to_string())
+        .filter(|name| !applied.contains(name))
+        .collect())
+}
+
+/// Quotes an SQL identifier, such as a database name.
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+async fn connect_pool(
+    url: String,
     max_connections: u32,
-    sslmode: &str,
+    config: &DatabaseConfig,
 ) -> Result<DatabaseConnection, Error> {
-    let mut opt = ConnectOptions::new(format!("{db_url}?sslmode={sslmode}"));
+    let mut opt = ConnectOptions::new(url);
     opt.max_connections(max_connections)
-        .min_connections(5.min(max_connections));
-    let db = Database::connect(opt).await?;
+        .min_connections(5.min(max_connections))
+        .acquire_timeout(Duration::from_secs(config.db_acquire_timeout))
+        .idle_timeout(Duration::from_secs(config.db_idle_timeout))
+        .max_lifetime(Duration::from_secs(config.db_max_lifetime));
+    Ok(Database::connect(opt).await?)
+}
 
-    let db = match db.get_database_backend() {
-        DbBackend::MySql => {
-            unimplemented!()
-        }
-        DbBackend::Postgres => {
-            let result = db
-                .execute(Statement::from_string(
-                    db.get_database_backend(),
-                    format!("SELECT 1 FROM pg_database WHERE datname = '{}'", db_name),
-                ))
-                .await?;
-            if result.rows_affected() == 0 {
-                db.execute(Statement::from_string(
-                    db.get_database_backend(),
-                    format!("CREATE DATABASE {}", db_name),
-                ))
-                .await?;
-            }
-            let url = format!("{db_url}/{db_name}?sslmode={sslmode}");
-            let mut opt = ConnectOptions::new(url);
-            opt.max_connections(max_connections)
-                .min_connections(5.min(max_connections));
-            Database::connect(opt).await?
+/// Connects to the database `db_name`, creating it if it doesn't exist.
+///
+/// The database is created through the admin URL if set, so that the database URL only needs the
+/// privileges required at runtime. Existing databases are used as they are, without any extra
+/// privilege.
+pub async fn connect(config: &DatabaseConfig, db_name: &str) -> Result<DatabaseConnection, Error> {
+    let sslmode = config.sslmode.as_deref();
+    let url = database_url(
+        &config.database_url,
+        Some(db_name),
+        sslmode,
+        config.db_statement_timeout,
+    )?;
+    let db = match connect_pool(url.clone(), config.max_connections, config).await {
+        Ok(db) => db,
+        Err(err) => {
+            debug!("failed to connect to database {db_name}, trying to create it: {err:?}");
+            let admin_url = config
+                .admin_database_url
+                .as_deref()
+                .unwrap_or(&config.database_url);
+            let admin_url = database_url(admin_url, None, sslmode, None)?;
+            let admin_db = connect_pool(admin_url, 1, config).await?;
+            let result = create_database(&admin_db, db_name).await;
+            admin_db.close().await?;
+            result?;
+            connect_pool(url, config.max_connections, config).await?
         }
-        DbBackend::Sqlite => unimplemented!(),
     };
 
     info!("connected to database: {db_name}");
@@ -52,19 +181,141 @@ async fn connect(
     Ok(db)
 }
 
+/// Connects to the database `db_name` of the read-only replica at `replica_url`.
+pub async fn connect_replica(
+    config: &DatabaseConfig,
+    replica_url: &str,
+    db_name: &str,
+) -> Result<DatabaseConnection, Error> {
+    let url = database_url(
+        replica_url,
+        Some(db_name),
+        config.sslmode.as_deref(),
+        config.db_statement_timeout,
+    )?;
+    let db = connect_pool(url, config.max_connections, config).await?;
+    info!("connected to database replica: {db_name}");
+    Ok(db)
+}
+
+/// Creates the database `db_name` unless it already exists.
+pub async fn create_database(admin_db: &DatabaseConnection, db_name: &str) -> Result<(), Error> {
+    let backend = admin_db.get_database_backend();
+    if backend != DbBackend::Postgres {
+        return Err(Error::ConfigError(
+            "only Postgres databases are supported".to_string(),
+        ));
+    }
+    let exists = admin_db
+        .query_one(Statement::from_sql_and_values(
+            backend,
+            "SELECT 1 FROM pg_database WHERE datname = $1",
+            [db_name.into()],
+        ))
+        .await?
+        .is_some();
+    if !exists {
+        info!("creating database: {db_name}");
+        admin_db
+            .execute_unprepared(&format!("CREATE DATABASE {}", quote_identifier(db_name)))
+            .await?;
+    }
+    Ok(())
+}
+
+/// Runs `f` until it succeeds, retrying database errors with exponential backoff.
+async fn with_retries<T, F, Fut>(config: &DatabaseConfig, db_name: &str, f: F) -> Result<T, Error>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    let max_backoff = Duration::from_secs(config.db_connect_max_backoff);
+    let mut backoff = Duration::from_secs(config.db_connect_backoff).min(max_backoff);
+    for _ in 0..config.db_connect_retries {
+        match f().await {
+            Err(Error::DBError(err)) => {
+                warn!("database {db_name} is not ready, retrying in {backoff:?}: {err}");
+                time::sleep(backoff).await;
+                backoff = (backoff * 2).min(max_backoff);
+            }
+            result => return result,
+        }
+    }
+    f().await
+}
+
+/// Connects to the database and applies the pending migrations, retrying on database errors.
 pub async fn connect_and_refresh_schema(
-    db_url: &str,
+    config: &DatabaseConfig,
+    db_name: &str,
+) -> Result<DatabaseConnection, Error> {
+    with_retries(config, db_name, || async {
+        let db = connect(config, db_name).await?;
+        let pending = Migrator::get_pending_migrations(&db).await?.len();
+        if pending == 0 {
+            info!("no database migrations to apply");
+        } else {
+            info!("applying {pending} database migrations");
+            Migrator::up(&db, None).await?;
+        }
+        Ok(db)
+    })
+    .await
+}
+
+/// Connects to the database and fails if it has pending migrations, without applying them.
+pub async fn connect_and_check_schema(
+    config: &DatabaseConfig,
     db_name: &str,
-    max_connections: u32,
-    sslmode: &str,
 ) -> Result<DatabaseConnection, Error> {
-    let db = connect(db_url, db_name, max_connections, sslmode).await?;
-    let pending = Migrator::get_pending_migrations(&db).await?.len();
-    if pending == 0 {
-        info!("no database migrations to apply");
-    } else {
-        info!("applying {pending} database migrations");
-        Migrator::up(&db, None).await?;
+    with_retries(config, db_name, || async {
+        let db = connect(config, db_name).await?;
+        check_schema(&db, db_name).await?;
+        Ok(db)
+    })
+    .await
+}
+
+/// Returns an error listing the pending migrations, if any.
+pub async fn check_schema(db: &DatabaseConnection, db_name: &str) -> Result<(), Error> {
+    let pending = pending_migrations(db).await?;
+    if !pending.is_empty() {
+        return Err(Error::PendingMigrations(db_name.to_string(), pending));
     }
-    Ok(db)
+    Ok(())
+}
+
+/// Executes a migration management action.
+pub async fn migrate(
+    db: &DatabaseConnection,
+    db_name: &str,
+    action: MigrateAction,
+) -> Result<(), Error> {
+    match action {
+        MigrateAction::Status => {
+            // Printed rather than logged, to be shown whatever the log level.
+            let applied = applied_migrations(db).await?;
+            for migration in Migrator::migrations() {
+                let status = if applied.contains(migration.name()) {
+                    MigrationStatus::Applied
+                } else {
+                    MigrationStatus::Pending
+                };
+                println!("{db_name} migration {}: {status}", migration.name());
+            }
+        }
+        MigrateAction::Up => {
+            info!("applying {db_name} pending migrations");
+            Migrator::up(db, None).await?;
+        }
+        MigrateAction::Down { n } => {
+            info!("rolling back {n} {db_name} migrations");
+            Migrator::down(db, Some(n)).await?;
+        }
+        MigrateAction::Fresh { .. } => {
+            info!("dropping every {db_name} table and applying all migrations");
+            Migrator::fresh(db).await?;
+        }
+    }
+    Ok(())
 }
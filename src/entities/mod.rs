@@ -2,4 +2,11 @@
 
 pub mod prelude;
 
+pub mod ban;
 pub mod node;
+pub mod node_annotation;
+pub mod node_archive;
+pub mod node_claim;
+pub mod node_history;
+pub mod rollup;
+pub mod rollup_version;
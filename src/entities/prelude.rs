@@ -1,3 +1,10 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.15
 
+pub use super::ban::Entity as Ban;
 pub use super::node::Entity as Node;
+pub use super::node_annotation::Entity as NodeAnnotation;
+pub use super::node_archive::Entity as NodeArchive;
+pub use super::node_claim::Entity as NodeClaim;
+pub use super::node_history::Entity as NodeHistory;
+pub use super::rollup::Entity as Rollup;
+pub use super::rollup_version::Entity as RollupVersion;
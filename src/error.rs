@@ -10,6 +10,20 @@ pub enum Error {
     DBError(#[from] sea_orm::DbErr),
     #[error("input error ({0})/n{1}")]
     InputError(String, String),
+    #[error("validation error ({0})/n{1}")]
+    ValidationError(String, String),
+    #[error("webhook error")]
+    WebhookError(#[from] reqwest::Error),
+    #[error("tracing error ({0})")]
+    TracingError(String),
+    #[error("TLS error ({0})")]
+    TlsError(String),
+    #[error("configuration error ({0})")]
+    ConfigError(String),
+    #[error("arguments error ({0})")]
+    ArgsError(#[from] clap::Error),
+    #[error("pending migrations error ({0} database: {1:?})")]
+    PendingMigrations(String, Vec<String>),
     #[error("database not found error")]
     DatabaseNotFound,
     #[error("unknown error")]
@@ -0,0 +1,146 @@
+//! Detection of forks and nodes stuck on a different chain.
+//!
+//! Recently seen nodes are grouped by their latest block height: whenever nodes at the same height
+//! report different block hashes, the height is flagged as a divergence.
+
+use std::collections::{BTreeMap, HashMap};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use tracing::{debug, warn};
+
+use crate::{
+    entities::node,
+    metrics::Labels,
+    nodes::{recent_nodes, ChainId},
+    server::ServerState,
+    Error,
+};
+
+/// Result of the latest fork detection run for a chain.
+#[derive(Serialize, Clone, Debug, Default)]
+pub(crate) struct ForkReport {
+    pub(crate) updated_at: Option<NaiveDateTime>,
+    pub(crate) divergences: Vec<Divergence>,
+}
+
+/// A block height at which nodes disagree on the block hash.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub(crate) struct Divergence {
+    pub(crate) height: i64,
+    /// Branches sorted by number of nodes, the most popular one first.
+    pub(crate) branches: Vec<Branch>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub(crate) struct Branch {
+    pub(crate) hash: String,
+    pub(crate) nodes: Vec<String>,
+}
+
+impl Divergence {
+    /// Number of nodes that are not on the most popular branch.
+    fn minority_nodes(&self) -> usize {
+        self.branches.iter().skip(1).map(|b| b.nodes.len()).sum()
+    }
+}
+
+pub(crate) async fn forks_handler(
+    state: State<ServerState>,
+    Path(chain): Path<String>,
+) -> Response {
+    let chain = ChainId::from(chain.as_str());
+    if state.database(&chain).is_none() {
+        return (StatusCode::NOT_FOUND, format!("unknown chain: {chain}")).into_response();
+    }
+    let report = state
+        .forks
+        .read()
+        .expect("forks lock poisoned")
+        .get(&chain)
+        .cloned()
+        .unwrap_or_default();
+    Json(report).into_response()
+}
+
+/// Runs fork detection over the recently seen nodes of every chain.
+pub(crate) async fn detect_forks(state: &ServerState) -> Result<(), Error> {
+    let window = chrono::Duration::seconds(state.fork_detection.fork_detection_window as i64);
+    for (chain, db) in state.read_databases() {
+        let nodes = recent_nodes(db, window).await?;
+        let divergences = find_divergences(&nodes);
+        if divergences.is_empty() {
+            debug!("no divergences found for {chain}");
+        }
+        for divergence in &divergences {
+            warn!(
+                "{chain} nodes diverge at height {}: {} branches",
+                divergence.height,
+                divergence.branches.len()
+            );
+        }
+
+        let labels = Labels::new(chain.to_string());
+        state
+            .metrics
+            .divergent_heights
+            .get_or_create(&labels)
+            .set(divergences.len() as i64);
+        state.metrics.diverging_nodes.get_or_create(&labels).set(
+            divergences
+                .iter()
+                .map(Divergence::minority_nodes)
+                .sum::<usize>() as i64,
+        );
+
+        let report = ForkReport {
+            updated_at: Some(chrono::offset::Utc::now().naive_utc()),
+            divergences,
+        };
+        state
+            .forks
+            .write()
+            .expect("forks lock poisoned")
+            .insert(chain, report);
+    }
+    Ok(())
+}
+
+/// Returns the heights at which nodes report more than one block hash, highest first.
+fn find_divergences(nodes: &[node::Model]) -> Vec<Divergence> {
+    let mut heights: BTreeMap<i64, HashMap<&str, Vec<String>>> = BTreeMap::new();
+    for node in nodes {
+        heights
+            .entry(node.last_height)
+            .or_default()
+            .entry(node.last_hash.as_str())
+            .or_default()
+            .push(node.id.clone());
+    }
+
+    heights
+        .into_iter()
+        .rev()
+        .filter(|(_, hashes)| hashes.len() > 1)
+        .map(|(height, hashes)| {
+            let mut branches: Vec<Branch> = hashes
+                .into_iter()
+                .map(|(hash, mut nodes)| {
+                    nodes.sort();
+                    Branch {
+                        hash: hash.to_string(),
+                        nodes,
+                    }
+                })
+                .collect();
+            branches.sort_by(|a, b| b.nodes.len().cmp(&a.nodes.len()).then(a.hash.cmp(&b.hash)));
+            Divergence { height, branches }
+        })
+        .collect()
+}
@@ -1,27 +1,178 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use std::sync::{atomic::Ordering, Arc};
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
 use sea_orm::{ConnectionTrait, DatabaseConnection, DbErr, ExecResult, Statement};
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
 use tracing::{debug, error};
 
-use crate::server::ServerState;
+use crate::{
+    database::{pending_migrations, pool_stats, PoolStats},
+    nodes::ChainId,
+    server::ServerState,
+};
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct HealthQuery {
+    /// Present, with any value other than `false`, to get the detailed JSON report.
+    verbose: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct HealthReport {
+    healthy: bool,
+    databases: Vec<DatabaseHealth>,
+    /// Number of ingestion requests currently being processed.
+    ingestion_in_flight: i64,
+}
+
+#[derive(Serialize, Debug)]
+struct DatabaseHealth {
+    chain: String,
+    role: &'static str,
+    healthy: bool,
+    error: Option<String>,
+    latency_seconds: f64,
+    pool: Option<PoolStats>,
+    /// Migrations not applied yet, only reported for the primary databases.
+    pending_migrations: Option<Vec<String>>,
+}
+
+pub(crate) async fn health_handler(
+    state: State<ServerState>,
+    Query(query): Query<HealthQuery>,
+) -> Response {
+    if query.verbose.is_some_and(|verbose| verbose != "false") {
+        return verbose_health(&state).await;
+    }
 
-pub(crate) async fn health_handler(state: State<ServerState>) -> impl IntoResponse {
     let mainnet_result = check(&state.db_mainnet).await;
     let testnet_result = check(&state.db_testnet).await;
 
+    // Replicas are reported without affecting the status, as queries fall back to the primary.
+    // Their health is the one last recorded by the replica check, which switches the queries.
+    let replicas: String = state
+        .replicas()
+        .iter()
+        .map(|(chain, replica)| {
+            let health = if replica.is_healthy() {
+                "healthy"
+            } else {
+                "unhealthy"
+            };
+            format!("{chain} replica: {health}\n")
+        })
+        .collect();
+
     if let Err(err) = mainnet_result {
         error!("mainnet database error: {err}");
-        return StatusCode::INTERNAL_SERVER_ERROR;
+        return (StatusCode::INTERNAL_SERVER_ERROR, replicas).into_response();
     }
     if let Err(err) = testnet_result {
         error!("testnet database error: {err}");
-        return StatusCode::INTERNAL_SERVER_ERROR;
+        return (StatusCode::INTERNAL_SERVER_ERROR, replicas).into_response();
     }
 
     debug!("health check: success");
+    (StatusCode::OK, replicas).into_response()
+}
+
+async fn verbose_health(state: &ServerState) -> Response {
+    let mut databases = Vec::new();
+    for (chain, db) in state.databases() {
+        databases.push(database_health(&chain, "primary", db).await);
+    }
+    for (chain, replica) in state.replicas() {
+        databases.push(match replica.db() {
+            Some(db) => database_health(&chain, "replica", db).await,
+            None => DatabaseHealth {
+                chain: chain.to_string(),
+                role: "replica",
+                healthy: false,
+                error: Some("not connected".to_string()),
+                latency_seconds: 0.0,
+                pool: None,
+                pending_migrations: None,
+            },
+        });
+    }
+    // Like the plain check, only the primary databases affect the health of the service.
+    let healthy = databases
+        .iter()
+        .all(|db| db.role != "primary" || db.healthy);
+    let report = HealthReport {
+        healthy,
+        databases,
+        ingestion_in_flight: state.ingestion_in_flight.load(Ordering::Relaxed),
+    };
+    let status = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    (status, Json(report)).into_response()
+}
+
+async fn database_health(
+    chain: &ChainId,
+    role: &'static str,
+    db: &Arc<DatabaseConnection>,
+) -> DatabaseHealth {
+    let start = Instant::now();
+    let result = check(db).await;
+    let latency_seconds = start.elapsed().as_secs_f64();
+    let pending_migrations = match (role, &result) {
+        ("primary", Ok(_)) => match pending_migrations(db).await {
+            Ok(pending) => Some(pending),
+            Err(err) => {
+                error!("failed to list the {chain} pending migrations: {err:?}");
+                None
+            }
+        },
+        _ => None,
+    };
+    DatabaseHealth {
+        chain: chain.to_string(),
+        role,
+        healthy: result.is_ok(),
+        error: result.err().map(|err| err.to_string()),
+        latency_seconds,
+        pool: pool_stats(db),
+        pending_migrations,
+    }
+}
+
+/// Reports that the process is alive, without checking its dependencies.
+pub(crate) async fn liveness_handler() -> impl IntoResponse {
     StatusCode::OK
 }
 
-async fn check(db: &DatabaseConnection) -> Result<ExecResult, DbErr> {
+/// Reports whether the service can handle requests: the databases of the required chains must be
+/// reachable.
+pub(crate) async fn readiness_handler(state: State<ServerState>) -> impl IntoResponse {
+    let mut unavailable = Vec::new();
+    for (chain, db) in state.databases() {
+        if !state.health.ready_chains.contains(&chain.to_string()) {
+            continue;
+        }
+        if let Err(err) = check(db).await {
+            error!("{chain} database error: {err}");
+            unavailable.push(format!("{chain} database unavailable\n"));
+        }
+    }
+    if unavailable.is_empty() {
+        (StatusCode::OK, String::new())
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, unavailable.concat())
+    }
+}
+
+pub(crate) async fn check(db: &DatabaseConnection) -> Result<ExecResult, DbErr> {
     db.execute(Statement::from_string(
         db.get_database_backend(),
         "SELECT 1",
@@ -0,0 +1,41 @@
+//! Optional storage of every report in the `node_history` table.
+//!
+//! The table is range partitioned by day. A maintenance task creates the partitions of the upcoming
+//! days ahead of time and drops the ones older than the history retention.
+
+use tracing::{debug, info};
+
+use crate::{
+    metrics::Labels,
+    migrator::partitions::{create_partitions, drop_partitions, list_partitions},
+    server::ServerState,
+    Error,
+};
+
+/// Creates the upcoming history partitions and drops the expired ones for every chain.
+pub(crate) async fn maintain_partitions(state: &ServerState) -> Result<(), Error> {
+    let config = &state.history;
+    if !config.store_history {
+        debug!("history storage is disabled");
+        return Ok(());
+    }
+    let today = chrono::offset::Utc::now().date_naive();
+    let expired_before = today - chrono::Duration::days(config.history_retention_days as i64);
+
+    for (chain, db) in state.databases() {
+        let db = db.as_ref();
+        let created = create_partitions(db, today, config.history_partitions_ahead).await?;
+        debug!("{chain} history partitions up to date: {created:?}");
+        let dropped = drop_partitions(db, expired_before).await?;
+        if !dropped.is_empty() {
+            info!("dropped expired {chain} history partitions: {dropped:?}");
+        }
+        let partitions = list_partitions(db).await?;
+        state
+            .metrics
+            .history_partitions
+            .get_or_create(&Labels::new(chain.to_string()))
+            .set(partitions.len() as i64);
+    }
+    Ok(())
+}
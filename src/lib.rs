@@ -1,5 +1,13 @@
 #![forbid(unsafe_code)]
 
+mod admin;
+
+mod alerts;
+
+mod auth;
+
+mod claims;
+
 pub mod config;
 pub use config::Config;
 
@@ -10,15 +18,50 @@ pub mod entities;
 pub mod error;
 pub use error::Error;
 
+mod forks;
+
 mod health;
 
+mod history;
+
 mod metrics;
 
-mod migrator;
+pub mod migrator;
+
+mod network_stats;
+
+mod node_metrics;
 
 pub mod nodes;
 
+pub mod otlp;
+
+pub mod reload;
+
+mod replicas;
+
+mod request_id;
+
+mod retention;
+
+mod rollups;
+
 pub mod server;
 pub use server::Server;
 
+mod stream;
+
+mod substrate_feed;
+
+mod sync_status;
+
+pub mod tasks;
+pub use tasks::Task;
+
+mod tls;
+
+mod validators;
+
+mod webhook;
+
 mod telemetry;
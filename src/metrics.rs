@@ -1,32 +1,102 @@
 use axum::extract::State;
 use axum::http::{header, StatusCode};
-use axum::response::IntoResponse;
+use axum::response::{IntoResponse, Response};
 use derive_more::Constructor;
 use prometheus_client::encoding::EncodeLabelSet;
 use prometheus_client::metrics::counter::Counter;
 use prometheus_client::metrics::family::Family;
-use prometheus_client::metrics::histogram::Histogram;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
 use prometheus_client::registry::Unit;
 use prometheus_client::{encoding::text::encode, registry::Registry};
 use std::sync::Arc;
 
-use crate::server::ServerState;
+use crate::{database::pool_stats, server::ServerState};
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet, Constructor)]
 pub struct Labels {
     network: String,
 }
 
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet, Constructor)]
+pub struct StatusLabels {
+    network: String,
+    status: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet, Constructor)]
+pub struct ReasonLabels {
+    network: String,
+    reason: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet, Constructor)]
+pub struct IngestionLabels {
+    route: String,
+    telemetry_version: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet, Constructor)]
+pub struct RuleLabels {
+    rule: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet, Constructor)]
+pub struct ActionLabels {
+    network: String,
+    action: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet, Constructor)]
+pub struct ReloadLabels {
+    status: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet, Constructor)]
+pub struct VersionLabels {
+    network: String,
+    agent_version: String,
+    protocol_version: String,
+    status: String,
+}
+
 pub struct Metrics {
     pub total_requests: Family<Labels, Counter>,
     pub successful_requests: Family<Labels, Counter>,
-    pub failed_requests: Family<Labels, Counter>,
+    pub failed_requests: Family<ReasonLabels, Counter>,
     pub request_latency: Family<Labels, Histogram>,
+    pub ingestion_requests: Family<IngestionLabels, Counter>,
+    pub payload_size: Family<Labels, Histogram>,
+    pub db_upsert_latency: Family<Labels, Histogram>,
+    pub upserted_nodes: Family<ActionLabels, Counter>,
+    pub divergent_heights: Family<Labels, Gauge>,
+    pub diverging_nodes: Family<Labels, Gauge>,
+    pub nodes_by_sync_status: Family<StatusLabels, Gauge>,
+    pub alerts_firing: Family<RuleLabels, Gauge>,
+    pub pruned_nodes: Family<ActionLabels, Counter>,
+    pub history_partitions: Family<Labels, Gauge>,
+    pub db_connections: Family<StatusLabels, Gauge>,
+    pub db_max_connections: Family<Labels, Gauge>,
+    pub replica_healthy: Family<Labels, Gauge>,
+    pub active_nodes: Family<VersionLabels, Gauge>,
+    pub active_validators: Family<Labels, Gauge>,
+    pub max_height: Family<Labels, Gauge>,
+    pub median_height: Family<Labels, Gauge>,
+    pub stale_nodes: Family<Labels, Gauge>,
+    pub config_reloads: Family<ReloadLabels, Counter>,
+    pub stream_clients: Family<Labels, Gauge>,
+    pub stream_skipped_updates: Family<Labels, Counter>,
 }
 
 pub(crate) async fn metric_handler(state: State<ServerState>) -> impl IntoResponse {
+    update_pool_metrics(&state);
+    encode_registry(&state.metrics_registry)
+}
+
+/// Encodes `registry` in the Prometheus text format.
+pub(crate) fn encode_registry(registry: &Registry) -> Response {
     let mut buf = String::new();
-    match encode(&mut buf, &state.metrics_registry) {
+    match encode(&mut buf, registry) {
         Ok(()) => (
             [(
                 header::CONTENT_TYPE,
@@ -39,6 +109,34 @@ pub(crate) async fn metric_handler(state: State<ServerState>) -> impl IntoRespon
     }
 }
 
+/// Updates the utilisation gauges of the database pools.
+fn update_pool_metrics(state: &ServerState) {
+    for (chain, db) in state.databases() {
+        let Some(pool) = pool_stats(db) else {
+            continue;
+        };
+        for (status, count) in [("idle", pool.idle), ("in_use", pool.size - pool.idle)] {
+            state
+                .metrics
+                .db_connections
+                .get_or_create(&StatusLabels::new(chain.to_string(), status.to_string()))
+                .set(count as i64);
+        }
+        state
+            .metrics
+            .db_max_connections
+            .get_or_create(&Labels::new(chain.to_string()))
+            .set(pool.max as i64);
+    }
+}
+
+fn latency_histogram() -> Histogram {
+    let buckets = [
+        0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+    ];
+    Histogram::new(buckets.into_iter())
+}
+
 pub(crate) fn create_registry_and_metrics() -> (Arc<Registry>, Arc<Metrics>) {
     let mut registry = <Registry>::with_prefix("telemetry_service");
 
@@ -54,30 +152,177 @@ pub(crate) fn create_registry_and_metrics() -> (Arc<Registry>, Arc<Metrics>) {
         "Number of successful requests",
         successful_requests.clone(),
     );
-    let failed_requests = Family::<Labels, Counter>::default();
+    let failed_requests = Family::<ReasonLabels, Counter>::default();
     registry.register(
         "failed_requests",
-        "Number of failed requests",
+        "Number of failed requests, by failure reason",
         failed_requests.clone(),
     );
-    let request_latency = Family::<Labels, Histogram>::new_with_constructor(|| {
-        let buckets = [
-            0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
-        ];
-        Histogram::new(buckets.into_iter())
-    });
+    let request_latency = Family::<Labels, Histogram>::new_with_constructor(latency_histogram);
     registry.register_with_unit(
         "request_latency",
         "Request latency",
         Unit::Seconds,
         request_latency.clone(),
     );
+    let ingestion_requests = Family::<IngestionLabels, Counter>::default();
+    registry.register(
+        "ingestion_requests",
+        "Number of ingestion requests by route and telemetry version",
+        ingestion_requests.clone(),
+    );
+    let payload_size = Family::<Labels, Histogram>::new_with_constructor(|| {
+        Histogram::new(exponential_buckets(256.0, 2.0, 12))
+    });
+    registry.register_with_unit(
+        "payload_size",
+        "Size of the telemetry payloads",
+        Unit::Bytes,
+        payload_size.clone(),
+    );
+    let db_upsert_latency = Family::<Labels, Histogram>::new_with_constructor(latency_histogram);
+    registry.register_with_unit(
+        "db_upsert_latency",
+        "Latency of the node upserts in the database",
+        Unit::Seconds,
+        db_upsert_latency.clone(),
+    );
+    let upserted_nodes = Family::<ActionLabels, Counter>::default();
+    registry.register(
+        "upserted_nodes",
+        "Number of nodes inserted for the first time or updated",
+        upserted_nodes.clone(),
+    );
+
+    let divergent_heights = Family::<Labels, Gauge>::default();
+    registry.register(
+        "divergent_heights",
+        "Number of block heights at which nodes report different hashes",
+        divergent_heights.clone(),
+    );
+    let diverging_nodes = Family::<Labels, Gauge>::default();
+    registry.register(
+        "diverging_nodes",
+        "Number of nodes not on the most popular hash at a divergent height",
+        diverging_nodes.clone(),
+    );
+    let nodes_by_sync_status = Family::<StatusLabels, Gauge>::default();
+    registry.register(
+        "nodes_by_sync_status",
+        "Number of nodes per sync status",
+        nodes_by_sync_status.clone(),
+    );
+    let alerts_firing = Family::<RuleLabels, Gauge>::default();
+    registry.register(
+        "alerts_firing",
+        "Number of firing alerts per rule",
+        alerts_firing.clone(),
+    );
+    let pruned_nodes = Family::<ActionLabels, Counter>::default();
+    registry.register(
+        "pruned_nodes",
+        "Number of nodes pruned by the retention policy",
+        pruned_nodes.clone(),
+    );
+    let history_partitions = Family::<Labels, Gauge>::default();
+    registry.register(
+        "history_partitions",
+        "Number of daily partitions of the node history table",
+        history_partitions.clone(),
+    );
+    let db_connections = Family::<StatusLabels, Gauge>::default();
+    registry.register(
+        "db_connections",
+        "Number of connections of the database pool, idle or in use",
+        db_connections.clone(),
+    );
+    let db_max_connections = Family::<Labels, Gauge>::default();
+    registry.register(
+        "db_max_connections",
+        "Maximum number of connections of the database pool",
+        db_max_connections.clone(),
+    );
+    let replica_healthy = Family::<Labels, Gauge>::default();
+    registry.register(
+        "replica_healthy",
+        "Whether the read-only replica is healthy and serves the queries",
+        replica_healthy.clone(),
+    );
+    let active_nodes = Family::<VersionLabels, Gauge>::default();
+    registry.register(
+        "active_nodes",
+        "Number of active nodes per agent version, protocol version and status",
+        active_nodes.clone(),
+    );
+    let active_validators = Family::<Labels, Gauge>::default();
+    registry.register(
+        "active_validators",
+        "Number of active validator nodes",
+        active_validators.clone(),
+    );
+    let max_height = Family::<Labels, Gauge>::default();
+    registry.register(
+        "max_height",
+        "Highest block height reported by the active nodes",
+        max_height.clone(),
+    );
+    let median_height = Family::<Labels, Gauge>::default();
+    registry.register(
+        "median_height",
+        "Median block height reported by the active nodes",
+        median_height.clone(),
+    );
+    let stale_nodes = Family::<Labels, Gauge>::default();
+    registry.register(
+        "stale_nodes",
+        "Number of stored nodes that stopped reporting",
+        stale_nodes.clone(),
+    );
+    let config_reloads = Family::<ReloadLabels, Counter>::default();
+    registry.register(
+        "config_reloads",
+        "Number of configuration reloads, successful or failed",
+        config_reloads.clone(),
+    );
+    let stream_clients = Family::<Labels, Gauge>::default();
+    registry.register(
+        "stream_clients",
+        "Number of clients connected to the live update streams",
+        stream_clients.clone(),
+    );
+    let stream_skipped_updates = Family::<Labels, Counter>::default();
+    registry.register(
+        "stream_skipped_updates",
+        "Number of updates skipped by stream clients too slow to keep up",
+        stream_skipped_updates.clone(),
+    );
 
     let metrics = Metrics {
         total_requests,
         successful_requests,
         failed_requests,
         request_latency,
+        ingestion_requests,
+        payload_size,
+        db_upsert_latency,
+        upserted_nodes,
+        divergent_heights,
+        diverging_nodes,
+        nodes_by_sync_status,
+        alerts_firing,
+        pruned_nodes,
+        history_partitions,
+        db_connections,
+        db_max_connections,
+        replica_healthy,
+        active_nodes,
+        active_validators,
+        max_height,
+        median_height,
+        stale_nodes,
+        config_reloads,
+        stream_clients,
+        stream_skipped_updates,
     };
     (Arc::new(registry), Arc::new(metrics))
 }
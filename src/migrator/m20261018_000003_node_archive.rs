@@ -0,0 +1,140 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20261018_000003_node_archive"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(NodeArchive::Table)
+                    .col(
+                        ColumnDef::new(NodeArchive::Id)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(NodeArchive::AccountId).string().null())
+                    .col(ColumnDef::new(NodeArchive::LastSeen).timestamp().not_null())
+                    .col(
+                        ColumnDef::new(NodeArchive::LastHeight)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(NodeArchive::LastHash).string().not_null())
+                    .col(ColumnDef::new(NodeArchive::AgentName).string().not_null())
+                    .col(
+                        ColumnDef::new(NodeArchive::AgentVersion)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(NodeArchive::AgentBuild).string().not_null())
+                    .col(
+                        ColumnDef::new(NodeArchive::PeerCount)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(NodeArchive::IsValidator)
+                            .boolean()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(NodeArchive::Status).string().not_null())
+                    .col(
+                        ColumnDef::new(NodeArchive::BandwidthDownload)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(NodeArchive::BandwidthUpload)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(NodeArchive::CpuUsage).float().not_null())
+                    .col(
+                        ColumnDef::new(NodeArchive::MemoryUsage)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(NodeArchive::BootTimeSeconds)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(NodeArchive::BlockProductionTrackingDelay)
+                            .double()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(NodeArchive::MinBlockProductionDelay)
+                            .double()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(NodeArchive::MaxBlockProductionDelay)
+                            .double()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(NodeArchive::MaxBlockWaitDelay)
+                            .double()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(NodeArchive::ChainId).string().null())
+                    .col(
+                        ColumnDef::new(NodeArchive::ProtocolVersion)
+                            .integer()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(NodeArchive::ArchivedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(NodeArchive::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum NodeArchive {
+    Table,
+    Id,
+    AccountId,
+    LastSeen,
+    LastHeight,
+    LastHash,
+    AgentName,
+    AgentVersion,
+    AgentBuild,
+    PeerCount,
+    IsValidator,
+    Status,
+    BandwidthDownload,
+    BandwidthUpload,
+    CpuUsage,
+    MemoryUsage,
+    BootTimeSeconds,
+    BlockProductionTrackingDelay,
+    MinBlockProductionDelay,
+    MaxBlockProductionDelay,
+    MaxBlockWaitDelay,
+    ChainId,
+    ProtocolVersion,
+    ArchivedAt,
+}
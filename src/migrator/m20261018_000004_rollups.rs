@@ -0,0 +1,136 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20261018_000004_rollups"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let mut rollup = Table::create();
+        rollup
+            .table(Rollup::Table)
+            .col(ColumnDef::new(Rollup::Granularity).string().not_null())
+            .col(ColumnDef::new(Rollup::BucketStart).timestamp().not_null())
+            .col(ColumnDef::new(Rollup::NodeCount).big_integer().not_null())
+            .col(
+                ColumnDef::new(Rollup::ValidatorCount)
+                    .big_integer()
+                    .not_null(),
+            )
+            .primary_key(
+                Index::create()
+                    .col(Rollup::Granularity)
+                    .col(Rollup::BucketStart),
+            );
+        for col in [
+            Rollup::PeerCountMin,
+            Rollup::PeerCountAvg,
+            Rollup::PeerCountMax,
+            Rollup::CpuUsageMin,
+            Rollup::CpuUsageAvg,
+            Rollup::CpuUsageMax,
+            Rollup::MemoryUsageMin,
+            Rollup::MemoryUsageAvg,
+            Rollup::MemoryUsageMax,
+            Rollup::BandwidthDownloadMin,
+            Rollup::BandwidthDownloadAvg,
+            Rollup::BandwidthDownloadMax,
+            Rollup::BandwidthUploadMin,
+            Rollup::BandwidthUploadAvg,
+            Rollup::BandwidthUploadMax,
+        ] {
+            rollup.col(ColumnDef::new(col).double().null());
+        }
+        manager.create_table(rollup.to_owned()).await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(RollupVersion::Table)
+                    .col(
+                        ColumnDef::new(RollupVersion::Granularity)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RollupVersion::BucketStart)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RollupVersion::AgentVersion)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RollupVersion::ProtocolVersion)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RollupVersion::NodeCount)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(RollupVersion::Granularity)
+                            .col(RollupVersion::BucketStart)
+                            .col(RollupVersion::AgentVersion)
+                            .col(RollupVersion::ProtocolVersion),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RollupVersion::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Rollup::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum Rollup {
+    Table,
+    Granularity,
+    BucketStart,
+    NodeCount,
+    ValidatorCount,
+    PeerCountMin,
+    PeerCountAvg,
+    PeerCountMax,
+    CpuUsageMin,
+    CpuUsageAvg,
+    CpuUsageMax,
+    MemoryUsageMin,
+    MemoryUsageAvg,
+    MemoryUsageMax,
+    BandwidthDownloadMin,
+    BandwidthDownloadAvg,
+    BandwidthDownloadMax,
+    BandwidthUploadMin,
+    BandwidthUploadAvg,
+    BandwidthUploadMax,
+}
+
+#[derive(Iden)]
+pub enum RollupVersion {
+    Table,
+    Granularity,
+    BucketStart,
+    AgentVersion,
+    ProtocolVersion,
+    NodeCount,
+}
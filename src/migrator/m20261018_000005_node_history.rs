@@ -0,0 +1,59 @@
+use sea_orm_migration::prelude::*;
+
+use super::partitions::{create_partitions, DEFAULT_PARTITION, HISTORY_TABLE};
+
+/// Number of daily partitions created along with the table, starting today.
+const INITIAL_PARTITIONS: u64 = 3;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20261018_000005_node_history"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // The query builder doesn't support declarative partitioning.
+        let db = manager.get_connection();
+        db.execute_unprepared(&format!(
+            r#"CREATE TABLE "{HISTORY_TABLE}" (
+                "node_id" varchar NOT NULL,
+                "reported_at" timestamp NOT NULL,
+                "account_id" varchar NULL,
+                "height" bigint NOT NULL,
+                "hash" varchar NOT NULL,
+                "agent_version" varchar NOT NULL,
+                "protocol_version" integer NULL,
+                "peer_count" bigint NOT NULL,
+                "is_validator" bool NOT NULL,
+                "status" varchar NOT NULL,
+                "bandwidth_download" bigint NOT NULL,
+                "bandwidth_upload" bigint NOT NULL,
+                "cpu_usage" real NOT NULL,
+                "memory_usage" bigint NOT NULL,
+                PRIMARY KEY ("node_id", "reported_at")
+            ) PARTITION BY RANGE ("reported_at")"#
+        ))
+        .await?;
+        let today = chrono::offset::Utc::now().date_naive();
+        create_partitions(db, today, INITIAL_PARTITIONS).await?;
+        // Keeps the reports of the days whose partition is missing.
+        db.execute_unprepared(&format!(
+            "CREATE TABLE \"{DEFAULT_PARTITION}\" PARTITION OF \"{HISTORY_TABLE}\" DEFAULT"
+        ))
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Dropping the partitioned table drops its partitions too.
+        manager
+            .get_connection()
+            .execute_unprepared(&format!("DROP TABLE \"{HISTORY_TABLE}\""))
+            .await?;
+        Ok(())
+    }
+}
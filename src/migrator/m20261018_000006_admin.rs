@@ -0,0 +1,81 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20261018_000006_admin"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Ban::Table)
+                    .col(ColumnDef::new(Ban::Kind).string().not_null())
+                    .col(ColumnDef::new(Ban::Value).string().not_null())
+                    .col(ColumnDef::new(Ban::Reason).string().null())
+                    .col(ColumnDef::new(Ban::CreatedAt).timestamp().not_null())
+                    .primary_key(Index::create().col(Ban::Kind).col(Ban::Value))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(NodeAnnotation::Table)
+                    .col(
+                        ColumnDef::new(NodeAnnotation::NodeId)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(NodeAnnotation::Note).string().null())
+                    .col(
+                        ColumnDef::new(NodeAnnotation::Tags)
+                            .json_binary()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(NodeAnnotation::UpdatedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(NodeAnnotation::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Ban::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum Ban {
+    Table,
+    Kind,
+    Value,
+    Reason,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+pub enum NodeAnnotation {
+    Table,
+    NodeId,
+    Note,
+    Tags,
+    UpdatedAt,
+}
@@ -0,0 +1,53 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20261018_000007_node_claims"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Claims are recorded per address, the empty one standing for an unknown address, so that
+        // the addresses used by a node id are kept.
+        manager
+            .create_table(
+                Table::create()
+                    .table(NodeClaim::Table)
+                    .col(ColumnDef::new(NodeClaim::AccountId).string().not_null())
+                    .col(ColumnDef::new(NodeClaim::NodeId).string().not_null())
+                    .col(ColumnDef::new(NodeClaim::Ip).string().not_null())
+                    .col(ColumnDef::new(NodeClaim::FirstSeen).timestamp().not_null())
+                    .col(ColumnDef::new(NodeClaim::LastSeen).timestamp().not_null())
+                    .primary_key(
+                        Index::create()
+                            .col(NodeClaim::AccountId)
+                            .col(NodeClaim::NodeId)
+                            .col(NodeClaim::Ip),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(NodeClaim::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum NodeClaim {
+    Table,
+    AccountId,
+    NodeId,
+    Ip,
+    FirstSeen,
+    LastSeen,
+}
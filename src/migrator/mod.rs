@@ -2,6 +2,12 @@ use sea_orm_migration::prelude::*;
 
 mod m20240508_000001_create_tables;
 mod m20240603_000002_node_v2;
+mod m20261018_000003_node_archive;
+mod m20261018_000004_rollups;
+mod m20261018_000005_node_history;
+mod m20261018_000006_admin;
+mod m20261018_000007_node_claims;
+pub(crate) mod partitions;
 
 pub struct Migrator;
 
@@ -11,6 +17,11 @@ impl MigratorTrait for Migrator {
         vec![
             Box::new(m20240508_000001_create_tables::Migration),
             Box::new(m20240603_000002_node_v2::Migration),
+            Box::new(m20261018_000003_node_archive::Migration),
+            Box::new(m20261018_000004_rollups::Migration),
+            Box::new(m20261018_000005_node_history::Migration),
+            Box::new(m20261018_000006_admin::Migration),
+            Box::new(m20261018_000007_node_claims::Migration),
         ]
     }
 }
@@ -0,0 +1,103 @@
+//! Management of the daily range partitions of the `node_history` table.
+//!
+//! Partitions are named `node_history_YYYYMMDD` and hold the reports received during that day. The
+//! reports received on a day without partition go to the default partition, and are moved to the
+//! daily partition once created.
+
+use chrono::{NaiveDate, NaiveDateTime};
+use sea_orm::{ConnectionTrait, DbErr, Statement};
+
+/// Partitioned table holding every report received from the nodes.
+pub(crate) const HISTORY_TABLE: &str = "node_history";
+
+/// Partition holding the reports of the days without partition.
+pub(crate) const DEFAULT_PARTITION: &str = "node_history_default";
+
+const PARTITION_DATE_FORMAT: &str = "%Y%m%d";
+
+/// Returns the name of the partition holding the reports of `day`.
+pub(crate) fn partition_name(day: NaiveDate) -> String {
+    format!("{HISTORY_TABLE}_{}", day.format(PARTITION_DATE_FORMAT))
+}
+
+/// Returns the day whose reports are held by the partition `name`, if it is a daily partition.
+pub(crate) fn partition_day(name: &str) -> Option<NaiveDate> {
+    let suffix = name.strip_prefix(HISTORY_TABLE)?.strip_prefix('_')?;
+    NaiveDate::parse_from_str(suffix, PARTITION_DATE_FORMAT).ok()
+}
+
+/// Creates the partitions for `days` days starting at `from`, skipping the existing ones.
+pub(crate) async fn create_partitions<C: ConnectionTrait>(
+    db: &C,
+    from: NaiveDate,
+    days: u64,
+) -> Result<Vec<String>, DbErr> {
+    let existing = list_partitions(db).await?;
+    let has_default = existing.iter().any(|name| name == DEFAULT_PARTITION);
+    let mut created = Vec::new();
+    for day in from.iter_days().take(days as usize) {
+        let name = partition_name(day);
+        let start = day.and_hms_opt(0, 0, 0).expect("midnight is valid");
+        let end = format_timestamp(start + chrono::Duration::days(1));
+        let start = format_timestamp(start);
+        let create = format!(
+            "CREATE TABLE IF NOT EXISTS \"{name}\" PARTITION OF \"{HISTORY_TABLE}\" \
+             FOR VALUES FROM ('{start}') TO ('{end}')"
+        );
+        if has_default && !existing.contains(&name) {
+            // A partition can't be created while the default one holds reports of its day. The
+            // statements run in a single transaction.
+            let range = format!("\"reported_at\" >= '{start}' AND \"reported_at\" < '{end}'");
+            db.execute_unprepared(&format!(
+                "CREATE TEMPORARY TABLE \"moved_history\" AS \
+                 SELECT * FROM \"{DEFAULT_PARTITION}\" WHERE {range}; \
+                 DELETE FROM \"{DEFAULT_PARTITION}\" WHERE {range}; \
+                 {create}; \
+                 INSERT INTO \"{HISTORY_TABLE}\" SELECT * FROM \"moved_history\"; \
+                 DROP TABLE \"moved_history\""
+            ))
+            .await?;
+        } else {
+            db.execute_unprepared(&create).await?;
+        }
+        created.push(name);
+    }
+    Ok(created)
+}
+
+/// Returns the names of the existing partitions of the history table.
+pub(crate) async fn list_partitions<C: ConnectionTrait>(db: &C) -> Result<Vec<String>, DbErr> {
+    let rows = db
+        .query_all(Statement::from_sql_and_values(
+            db.get_database_backend(),
+            "SELECT child.relname FROM pg_inherits \
+             JOIN pg_class parent ON pg_inherits.inhparent = parent.oid \
+             JOIN pg_class child ON pg_inherits.inhrelid = child.oid \
+             WHERE parent.relname = $1 ORDER BY child.relname",
+            [HISTORY_TABLE.into()],
+        ))
+        .await?;
+    rows.iter()
+        .map(|row| row.try_get::<String>("", "relname"))
+        .collect()
+}
+
+/// Drops the daily partitions holding only reports older than `before`.
+pub(crate) async fn drop_partitions<C: ConnectionTrait>(
+    db: &C,
+    before: NaiveDate,
+) -> Result<Vec<String>, DbErr> {
+    let mut dropped = Vec::new();
+    for name in list_partitions(db).await? {
+        if partition_day(&name).is_some_and(|day| day < before) {
+            db.execute_unprepared(&format!("DROP TABLE IF EXISTS \"{name}\""))
+                .await?;
+            dropped.push(name);
+        }
+    }
+    Ok(dropped)
+}
+
+fn format_timestamp(timestamp: NaiveDateTime) -> String {
+    timestamp.format("%Y-%m-%d %H:%M:%S").to_string()
+}
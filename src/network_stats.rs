@@ -0,0 +1,119 @@
+//! Network gauges computed from the stored nodes, for the network dashboards.
+//!
+//! A node is active when it reported within the `stale_after` window, and stale otherwise.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    entities::node,
+    metrics::{Labels, VersionLabels},
+    nodes::{all_nodes, ChainId},
+    server::ServerState,
+    Error,
+};
+
+/// Protocol version label of the nodes that don't report it.
+const UNKNOWN_PROTOCOL_VERSION: &str = "unknown";
+
+#[derive(Debug, Default)]
+struct NetworkStats {
+    /// Number of active nodes by agent version, protocol version and status.
+    active: BTreeMap<(String, String, String), i64>,
+    validators: i64,
+    max_height: Option<i64>,
+    median_height: Option<i64>,
+    stale: i64,
+}
+
+/// Refreshes the network gauges of every chain.
+pub(crate) async fn update_network_stats(state: &ServerState) -> Result<(), Error> {
+    let now = chrono::offset::Utc::now().naive_utc();
+    let stale_after = chrono::Duration::seconds(state.network_stats.stale_after as i64);
+
+    let mut stats: Vec<(ChainId, NetworkStats)> = Vec::new();
+    for (chain, db) in state.read_databases() {
+        let nodes = all_nodes(db).await?;
+        let (active, stale): (Vec<node::Model>, Vec<node::Model>) = nodes
+            .into_iter()
+            .partition(|node| now - node.last_seen <= stale_after);
+        stats.push((chain, compute_stats(&active, stale.len())));
+    }
+
+    let metrics = &state.metrics;
+    // Versions and statuses that disappeared must not be reported anymore.
+    metrics.active_nodes.clear();
+    for (chain, stats) in stats {
+        for ((agent_version, protocol_version, status), count) in stats.active {
+            metrics
+                .active_nodes
+                .get_or_create(&VersionLabels::new(
+                    chain.to_string(),
+                    agent_version,
+                    protocol_version,
+                    status,
+                ))
+                .set(count);
+        }
+        let labels = Labels::new(chain.to_string());
+        metrics
+            .active_validators
+            .get_or_create(&labels)
+            .set(stats.validators);
+        metrics.stale_nodes.get_or_create(&labels).set(stats.stale);
+        match (stats.max_height, stats.median_height) {
+            (Some(max_height), Some(median_height)) => {
+                metrics.max_height.get_or_create(&labels).set(max_height);
+                metrics
+                    .median_height
+                    .get_or_create(&labels)
+                    .set(median_height);
+            }
+            _ => {
+                metrics.max_height.remove(&labels);
+                metrics.median_height.remove(&labels);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn compute_stats(active: &[node::Model], stale: usize) -> NetworkStats {
+    let mut stats = NetworkStats {
+        stale: stale as i64,
+        ..Default::default()
+    };
+    for node in active {
+        let protocol_version = node
+            .protocol_version
+            .map_or_else(|| UNKNOWN_PROTOCOL_VERSION.to_string(), |v| v.to_string());
+        *stats
+            .active
+            .entry((
+                node.agent_version.clone(),
+                protocol_version,
+                node.status.clone(),
+            ))
+            .or_default() += 1;
+        if node.is_validator {
+            stats.validators += 1;
+        }
+    }
+    let mut heights: Vec<i64> = active.iter().map(|node| node.last_height).collect();
+    heights.sort_unstable();
+    stats.max_height = heights.last().copied();
+    stats.median_height = median(&heights);
+    stats
+}
+
+/// Returns the median of the sorted `values`.
+fn median(values: &[i64]) -> Option<i64> {
+    if values.is_empty() {
+        return None;
+    }
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        Some((values[middle - 1] + values[middle]) / 2)
+    } else {
+        Some(values[middle])
+    }
+}
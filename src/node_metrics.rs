@@ -0,0 +1,182 @@
+//! Prometheus exporter of the metrics reported by every node.
+//!
+//! The gauges are read from the `node` table on each scrape of `/metrics/nodes`. To bound the
+//! number of series, only the nodes that reported recently are exported, up to a maximum per chain,
+//! and the export can be restricted to an allowlist of node ids or accounts.
+
+use std::sync::atomic::AtomicU64;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use derive_more::Constructor;
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{family::Family, gauge::Gauge},
+    registry::Registry,
+};
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+};
+use tracing::{debug, error};
+
+use crate::{
+    config::NodeMetricsConfig,
+    entities::node,
+    metrics::encode_registry,
+    request_id::{error_body, RequestId},
+    server::ServerState,
+    Error,
+};
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet, Constructor)]
+struct NodeLabels {
+    network: String,
+    node_id: String,
+    account_id: String,
+    agent_version: String,
+}
+
+/// Name, help and value of a gauge exported for every node.
+type NodeGauge<T> = (&'static str, &'static str, fn(&node::Model) -> T);
+
+/// Gauges holding integer node fields.
+const INT_GAUGES: [NodeGauge<i64>; 7] = [
+    ("height", "Last block height reported by the node", |n| {
+        n.last_height
+    }),
+    ("peer_count", "Number of peers of the node", |n| {
+        n.peer_count
+    }),
+    ("memory_usage", "Memory usage of the node", |n| {
+        n.memory_usage
+    }),
+    (
+        "bandwidth_download",
+        "Download bandwidth of the node",
+        |n| n.bandwidth_download,
+    ),
+    ("bandwidth_upload", "Upload bandwidth of the node", |n| {
+        n.bandwidth_upload
+    }),
+    ("is_validator", "Whether the node is a validator", |n| {
+        n.is_validator as i64
+    }),
+    (
+        "last_seen_timestamp",
+        "Unix timestamp of the last report of the node",
+        |n| n.last_seen.and_utc().timestamp(),
+    ),
+];
+
+/// Gauges holding floating point node fields.
+const FLOAT_GAUGES: [NodeGauge<f64>; 5] = [
+    ("cpu_usage", "CPU usage of the node", |n| n.cpu_usage as f64),
+    (
+        "block_production_tracking_delay",
+        "Block production tracking delay of the node",
+        |n| n.block_production_tracking_delay,
+    ),
+    (
+        "min_block_production_delay",
+        "Minimum block production delay of the node",
+        |n| n.min_block_production_delay,
+    ),
+    (
+        "max_block_production_delay",
+        "Maximum block production delay of the node",
+        |n| n.max_block_production_delay,
+    ),
+    (
+        "max_block_wait_delay",
+        "Maximum block wait delay of the node",
+        |n| n.max_block_wait_delay,
+    ),
+];
+
+pub(crate) async fn node_metrics_handler(
+    state: State<ServerState>,
+    request_id: Option<Extension<RequestId>>,
+) -> Response {
+    let config = state.node_metrics();
+    if !config.node_metrics {
+        return (StatusCode::NOT_FOUND, "node metrics are disabled").into_response();
+    }
+
+    let mut nodes = Vec::new();
+    for (chain, db) in state.read_databases() {
+        match exported_nodes(db, &config).await {
+            Ok(chain_nodes) => {
+                if chain_nodes.len() as u64 >= config.node_metrics_max_nodes {
+                    debug!(
+                        "{chain} node metrics capped to {} nodes",
+                        config.node_metrics_max_nodes
+                    );
+                }
+                nodes.extend(chain_nodes.into_iter().map(|node| {
+                    let labels = NodeLabels::new(
+                        chain.to_string(),
+                        node.id.clone(),
+                        node.account_id.clone().unwrap_or_default(),
+                        node.agent_version.clone(),
+                    );
+                    (labels, node)
+                }));
+            }
+            Err(err) => {
+                error!("error loading {chain} nodes: {err:#?}");
+                let request_id = request_id.map(|Extension(RequestId(id))| id);
+                let message = format!("error loading nodes: {err}");
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    error_body(&message, request_id.as_deref()),
+                )
+                    .into_response();
+            }
+        }
+    }
+
+    // The registry is built on each scrape, so that the nodes no longer exported disappear.
+    let mut registry = <Registry>::with_prefix("telemetry_service_node");
+    for (name, help, value) in INT_GAUGES {
+        let family = Family::<NodeLabels, Gauge>::default();
+        registry.register(name, help, family.clone());
+        for (labels, node) in &nodes {
+            family.get_or_create(labels).set(value(node));
+        }
+    }
+    for (name, help, value) in FLOAT_GAUGES {
+        let family = Family::<NodeLabels, Gauge<f64, AtomicU64>>::default();
+        registry.register(name, help, family.clone());
+        for (labels, node) in &nodes {
+            family.get_or_create(labels).set(value(node));
+        }
+    }
+    encode_registry(&registry)
+}
+
+/// Returns the nodes of a chain to export, the most recently seen first.
+async fn exported_nodes(
+    db: &DatabaseConnection,
+    config: &NodeMetricsConfig,
+) -> Result<Vec<node::Model>, Error> {
+    let cutoff = chrono::offset::Utc::now().naive_utc()
+        - chrono::Duration::seconds(config.node_metrics_window as i64);
+    let mut query = node::Entity::find().filter(node::Column::LastSeen.gt(cutoff));
+    let allowlist = &config.node_metrics_allowlist;
+    if !allowlist.is_empty() {
+        query = query.filter(
+            Condition::any()
+                .add(node::Column::Id.is_in(allowlist))
+                .add(node::Column::AccountId.is_in(allowlist)),
+        );
+    }
+    Ok(query
+        .order_by_desc(node::Column::LastSeen)
+        .limit(config.node_metrics_max_nodes)
+        .all(db)
+        .await?)
+}
@@ -1,19 +1,43 @@
-use std::{fmt, sync::Arc};
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use axum::{extract::State, http::StatusCode, response::IntoResponse};
-use sea_orm::{sea_query::OnConflict, ActiveValue, DatabaseConnection, EntityTrait, Iterable};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Extension,
+};
+use sea_orm::{
+    sea_query::{Expr, OnConflict, Query},
+    ActiveValue, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait, Iterable,
+    QueryFilter, QueryTrait, TryIntoModel,
+};
+use serde_json::error::Category;
 use tokio::time::Instant;
-use tracing::{debug, error, trace};
+use tracing::{debug, error, field, info_span, trace, Instrument, Span};
 
 use crate::{
-    entities::node::{self},
-    metrics::Labels,
+    admin::{ban_reason, client_ip},
+    claims::record_claim,
+    entities::{node, node_history},
+    metrics::{ActionLabels, IngestionLabels, Labels, ReasonLabels},
+    request_id::{error_body, RequestId},
     server::ServerState,
     telemetry::TelemetryInfo,
     Error,
 };
 
-#[derive(Debug, Clone)]
+/// Replacement of the account ids in the logged request bodies.
+const REDACTED: &str = "<redacted>";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ChainId {
     Mainnet,
     Testnet,
@@ -30,46 +54,97 @@ impl fmt::Display for ChainId {
     }
 }
 
+impl From<&str> for ChainId {
+    fn from(chain: &str) -> Self {
+        match chain {
+            "mainnet" => ChainId::Mainnet,
+            "testnet" => ChainId::Testnet,
+            _ => ChainId::Other(chain.to_string()),
+        }
+    }
+}
+
 pub(crate) async fn nodes_handler_mainnet(
     state: State<ServerState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    request_id: Option<Extension<RequestId>>,
+    headers: HeaderMap,
     body: String,
 ) -> impl IntoResponse {
-    nodes_handler_impl(state, body, Some(ChainId::Mainnet)).await
+    let ip = client_ip(&state, &headers, connect_info);
+    let request_id = request_id.map(|Extension(RequestId(id))| id);
+    nodes_handler_impl(state, ip, request_id, body, Some(ChainId::Mainnet)).await
 }
 
 pub(crate) async fn nodes_handler_testnet(
     state: State<ServerState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    request_id: Option<Extension<RequestId>>,
+    headers: HeaderMap,
     body: String,
 ) -> impl IntoResponse {
-    nodes_handler_impl(state, body, Some(ChainId::Testnet)).await
+    let ip = client_ip(&state, &headers, connect_info);
+    let request_id = request_id.map(|Extension(RequestId(id))| id);
+    nodes_handler_impl(state, ip, request_id, body, Some(ChainId::Testnet)).await
 }
 
-pub(crate) async fn nodes_handler(state: State<ServerState>, body: String) -> impl IntoResponse {
-    nodes_handler_impl(state, body, None).await
+pub(crate) async fn nodes_handler(
+    state: State<ServerState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    request_id: Option<Extension<RequestId>>,
+    headers: HeaderMap,
+    body: String,
+) -> impl IntoResponse {
+    let ip = client_ip(&state, &headers, connect_info);
+    let request_id = request_id.map(|Extension(RequestId(id))| id);
+    nodes_handler_impl(state, ip, request_id, body, None).await
 }
 
 async fn nodes_handler_impl(
     state: State<ServerState>,
+    ip: Option<IpAddr>,
+    request_id: Option<String>,
     body: String,
     chain_from_path: Option<ChainId>,
-) -> impl IntoResponse {
+) -> (StatusCode, String) {
+    let route = match chain_from_path {
+        Some(ChainId::Mainnet) => "/nodes/mainnet",
+        Some(ChainId::Testnet) => "/nodes/testnet",
+        _ => "/nodes",
+    };
+    let span = info_span!(
+        "ingest",
+        route,
+        chain = field::Empty,
+        node_id = field::Empty
+    );
+    ingest(state, ip, request_id, body, route, chain_from_path)
+        .instrument(span)
+        .await
+}
+
+async fn ingest(
+    state: State<ServerState>,
+    ip: Option<IpAddr>,
+    request_id: Option<String>,
+    body: String,
+    route: &str,
+    chain_from_path: Option<ChainId>,
+) -> (StatusCode, String) {
     let now = Instant::now();
 
-    trace!("chain_from_path: {chain_from_path:?}, request body: {body}");
+    trace!("chain_from_path: {chain_from_path:?}");
+    if state.logs.log_request_bodies {
+        trace!("request body: {}", redact_account_ids(&body));
+    }
 
-    let telemetry: Result<TelemetryInfo, Error> =
-        serde_json::from_str(&body).map_err(|err| Error::InputError(err.to_string(), body));
+    let payload_size = body.len();
+    let telemetry = info_span!("decode").in_scope(|| parse_telemetry(body));
 
-    let chain_from_telemetry = telemetry.as_ref().ok().and_then(|info| {
-        info.chain
-            .chain_id
-            .as_ref()
-            .map(|chain| match chain.as_str() {
-                "mainnet" => ChainId::Mainnet,
-                "testnet" => ChainId::Testnet,
-                _ => ChainId::Other(chain.clone()),
-            })
-    });
+    let chain_from_telemetry = telemetry
+        .as_ref()
+        .ok()
+        .and_then(|info| info.chain.chain_id.as_deref().map(ChainId::from));
     // Determine the chain-id. In order of priority:
     // 1. chain-id sent inside the json
     // 2. HTTP path
@@ -79,51 +154,211 @@ async fn nodes_handler_impl(
         _ => ChainId::Other("unknown".to_string()),
     };
 
+    let span = Span::current();
+    span.record("chain", chain.to_string());
+    if let Ok(info) = &telemetry {
+        span.record("node_id", info.chain.node_id.as_str());
+    }
     debug!("received node telemetry for {chain}");
 
     let labels = Labels::new(chain.to_string());
-    state.metrics.total_requests.get_or_create(&labels).inc();
+    let metrics = &state.metrics;
+    metrics.total_requests.get_or_create(&labels).inc();
+    metrics
+        .ingestion_requests
+        .get_or_create(&IngestionLabels::new(
+            route.to_string(),
+            telemetry_version(&telemetry).to_string(),
+        ))
+        .inc();
+    metrics
+        .payload_size
+        .get_or_create(&labels)
+        .observe(payload_size as f64);
+
+    let node_id = telemetry
+        .as_ref()
+        .ok()
+        .map(|info| info.chain.node_id.as_str());
+    if let Some(reason) = ban_reason(&state, &chain, node_id, ip) {
+        metrics
+            .failed_requests
+            .get_or_create(&ReasonLabels::new(chain.to_string(), reason.to_string()))
+            .inc();
+        debug!("rejected {chain} request ({reason})");
+        return (StatusCode::FORBIDDEN, "banned".to_string());
+    }
 
-    let result = store_telemetry(state.database(&chain), &chain, telemetry).await;
+    let in_flight = InFlight::new(&state.ingestion_in_flight);
+    let result = store_telemetry(
+        state.database(&chain),
+        &chain,
+        telemetry,
+        state.history.store_history,
+        state.claims.track_claims,
+        ip,
+    )
+    .await;
+    drop(in_flight);
 
     let elapsed = now.elapsed();
-    state
-        .metrics
+    metrics
         .request_latency
         .get_or_create(&labels)
         .observe(elapsed.as_secs_f64());
 
     match result {
-        Ok(_) => {
-            state
-                .metrics
-                .successful_requests
+        // The telemetry of chains without database is accepted, but not stored.
+        Ok(None) => {
+            metrics
+                .failed_requests
+                .get_or_create(&ReasonLabels::new(
+                    chain.to_string(),
+                    "unknown_chain".to_string(),
+                ))
+                .inc();
+            debug!("telemetry request for {chain} not stored");
+            (StatusCode::NO_CONTENT, String::new())
+        }
+        Ok(Some(upsert)) => {
+            metrics
+                .db_upsert_latency
                 .get_or_create(&labels)
+                .observe(upsert.latency.as_secs_f64());
+            let action = if upsert.new_node {
+                "inserted"
+            } else {
+                "updated"
+            };
+            metrics
+                .upserted_nodes
+                .get_or_create(&ActionLabels::new(chain.to_string(), action.to_string()))
                 .inc();
+            state.updates.publish(&chain, upsert.node);
+            metrics.successful_requests.get_or_create(&labels).inc();
             debug!("telemetry request for {chain} handled correctly");
             (StatusCode::NO_CONTENT, String::new())
         }
         Err(err) => {
-            state.metrics.failed_requests.get_or_create(&labels).inc();
-            error!("error processing {chain} request: {err:#?}");
-            match err {
-                Error::InputError(_, _) => (StatusCode::BAD_REQUEST, format!("{err:#?}")),
-                _ => (StatusCode::INTERNAL_SERVER_ERROR, format!("{err:#?}")),
+            let reason = match err {
+                Error::InputError(_, _) => "parse",
+                Error::ValidationError(_, _) => "validation",
+                Error::DBError(_) => "db",
+                Error::DatabaseNotFound => "unknown_chain",
+                _ => "other",
+            };
+            metrics
+                .failed_requests
+                .get_or_create(&ReasonLabels::new(chain.to_string(), reason.to_string()))
+                .inc();
+            // Neither log nor return the body of invalid requests.
+            let (status, message) = match &err {
+                Error::InputError(message, _) | Error::ValidationError(message, _) => {
+                    error!("invalid {chain} request ({reason}): {message}");
+                    (
+                        StatusCode::BAD_REQUEST,
+                        format!("invalid request ({reason}): {message}"),
+                    )
+                }
+                _ => {
+                    error!("error processing {chain} request: {err:#?}");
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        format!("error processing request: {err}"),
+                    )
+                }
+            };
+            (status, error_body(&message, request_id.as_deref()))
+        }
+    }
+}
+
+/// Counts an ingestion request in flight until dropped, including when the request is cancelled
+/// by a timeout or a disconnection of the client.
+struct InFlight<'a>(&'a AtomicI64);
+
+impl<'a> InFlight<'a> {
+    fn new(counter: &'a AtomicI64) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter)
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Returns `body` with the account ids replaced, or only its size if it isn't valid JSON.
+fn redact_account_ids(body: &str) -> String {
+    fn redact(value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(object) => {
+                for (key, value) in object.iter_mut() {
+                    if key == "account_id" && !value.is_null() {
+                        *value = serde_json::Value::from(REDACTED);
+                    } else {
+                        redact(value);
+                    }
+                }
             }
+            serde_json::Value::Array(values) => values.iter_mut().for_each(redact),
+            _ => {}
+        }
+    }
+
+    match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(mut value) => {
+            redact(&mut value);
+            value.to_string()
         }
+        Err(_) => format!("<invalid JSON of {} bytes>", body.len()),
+    }
+}
+
+/// Parses the telemetry sent by a node, telling malformed JSON from an unexpected structure.
+fn parse_telemetry(body: String) -> Result<TelemetryInfo, Error> {
+    serde_json::from_str(&body).map_err(|err| match err.classify() {
+        Category::Data => Error::ValidationError(err.to_string(), body),
+        _ => Error::InputError(err.to_string(), body),
+    })
+}
+
+/// Returns the version of the telemetry format, see the README.
+fn telemetry_version(telemetry: &Result<TelemetryInfo, Error>) -> &'static str {
+    match telemetry {
+        Ok(info) if info.chain.chain_id.is_some() || info.agent.protocol_version.is_some() => "v2",
+        Ok(_) => "v1",
+        Err(_) => "unknown",
     }
 }
 
+/// Outcome of the upsert of a node.
+struct Upsert {
+    node: node::Model,
+    /// Whether the node was seen for the first time.
+    new_node: bool,
+    latency: Duration,
+}
+
+/// Stores the telemetry of a node. Returns `None` if the telemetry of `chain` is not persisted.
+///
+/// When `track_claims` is set, the claim of the account by the node is recorded with the address
+/// it was sent from.
 async fn store_telemetry(
     db: Option<&Arc<DatabaseConnection>>,
     chain: &ChainId,
     telemetry: Result<TelemetryInfo, Error>,
-) -> Result<(), Error> {
+    store_history: bool,
+    track_claims: bool,
+    ip: Option<IpAddr>,
+) -> Result<Option<Upsert>, Error> {
     let telemetry = telemetry?;
 
     if matches!(chain, ChainId::Other(_)) {
         debug!("persisting telemetry for chains other than mainnet and testnet is disabled");
-        return Ok(());
+        return Ok(None);
     }
 
     let db: &DatabaseConnection = match db {
@@ -158,14 +393,88 @@ async fn store_telemetry(
         protocol_version: ActiveValue::Set(telemetry.agent.protocol_version.map(|n| n as i32)),
     };
 
+    let history = store_history.then(|| node_history::ActiveModel {
+        node_id: node.id.clone(),
+        reported_at: node.last_seen.clone(),
+        account_id: node.account_id.clone(),
+        height: node.last_height.clone(),
+        hash: node.last_hash.clone(),
+        agent_version: node.agent_version.clone(),
+        protocol_version: node.protocol_version.clone(),
+        peer_count: node.peer_count.clone(),
+        is_validator: node.is_validator.clone(),
+        status: node.status.clone(),
+        bandwidth_download: node.bandwidth_download.clone(),
+        bandwidth_upload: node.bandwidth_upload.clone(),
+        cpu_usage: node.cpu_usage.clone(),
+        memory_usage: node.memory_usage.clone(),
+    });
+
+    let claim = match (track_claims, &node.account_id) {
+        (true, ActiveValue::Set(Some(account_id))) => Some((
+            account_id.clone(),
+            node.id.clone().unwrap(),
+            node.last_seen.clone().unwrap(),
+        )),
+        _ => None,
+    };
+
+    let model = node.clone().try_into_model()?;
+
     let on_conflict = OnConflict::column(node::Column::Id)
         .update_columns(node::Column::iter().filter(|col| !matches!(*col, node::Column::Id)))
         .to_owned();
 
-    node::Entity::insert(node)
+    // `xmax` is only set on the rows updated on conflict.
+    let mut insert = node::Entity::insert(node)
         .on_conflict(on_conflict)
-        .exec(db)
-        .await?;
+        .into_query();
+    insert.returning(Query::returning().expr(Expr::cust("(xmax = 0) AS inserted")));
+    let start = Instant::now();
+    let row = db
+        .query_one(db.get_database_backend().build(&insert))
+        .instrument(info_span!("upsert"))
+        .await?
+        .ok_or_else(|| DbErr::RecordNotInserted)?;
+    let latency = start.elapsed();
+    let new_node = row.try_get("", "inserted")?;
+
+    // The node is stored already: failing to append its history must not reject the report.
+    if let Some(history) = history {
+        if let Err(err) = node_history::Entity::insert(history)
+            .exec_without_returning(db)
+            .await
+        {
+            error!("failed to store the {chain} history of the node: {err}");
+        }
+    }
+    if let Some((account_id, node_id, now)) = claim {
+        if let Err(err) = record_claim(db, account_id, node_id, ip, now).await {
+            error!("failed to record the {chain} claim of the node: {err}");
+        }
+    }
+
+    Ok(Some(Upsert {
+        node: model,
+        new_node,
+        latency,
+    }))
+}
+
+/// Returns every node stored in the database.
+pub(crate) async fn all_nodes(db: &DatabaseConnection) -> Result<Vec<node::Model>, Error> {
+    Ok(node::Entity::find().all(db).await?)
+}
 
-    Ok(())
+/// Returns the nodes that reported within the given time window.
+pub(crate) async fn recent_nodes(
+    db: &DatabaseConnection,
+    window: chrono::Duration,
+) -> Result<Vec<node::Model>, Error> {
+    let cutoff = chrono::offset::Utc::now().naive_utc() - window;
+    let nodes = node::Entity::find()
+        .filter(node::Column::LastSeen.gt(cutoff))
+        .all(db)
+        .await?;
+    Ok(nodes)
 }
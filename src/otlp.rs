@@ -0,0 +1,56 @@
+//! Export of the traces to an OpenTelemetry collector, over OTLP.
+//!
+//! Spans are recorded through `tracing` and exported by the `tracing-opentelemetry` layer. The
+//! trace context of incoming requests is taken from their W3C `traceparent` header.
+
+use axum::http::HeaderMap;
+use opentelemetry::{global, propagation::Extractor, trace::TraceError, Context, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{propagation::TraceContextPropagator, runtime, trace::Tracer, Resource};
+
+use crate::{config::TracingConfig, Error};
+
+/// Returns the tracer exporting the spans to the configured collector, if any.
+pub fn tracer(config: &TracingConfig) -> Result<Option<Tracer>, Error> {
+    let Some(endpoint) = &config.otlp_endpoint else {
+        return Ok(None);
+    };
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let resource = Resource::new([KeyValue::new(
+        "service.name",
+        config.otlp_service_name.clone(),
+    )]);
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(opentelemetry_sdk::trace::config().with_resource(resource))
+        .install_batch(runtime::Tokio)
+        .map_err(|err: TraceError| Error::TracingError(err.to_string()))?;
+    Ok(Some(tracer))
+}
+
+/// Flushes the spans not exported yet.
+pub fn shutdown() {
+    global::shutdown_tracer_provider();
+}
+
+/// Returns the trace context propagated by the headers of a request.
+pub(crate) fn parent_context(headers: &HeaderMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
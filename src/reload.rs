@@ -0,0 +1,120 @@
+//! Reload of the configuration on SIGHUP.
+//!
+//! The configuration is parsed again from the original arguments, the environment and the
+//! configuration file, and the alert rules are read again from their file. The options that can be
+//! changed while serving are applied at once, without interrupting the requests in flight: the log
+//! level, the credentials, the alert rules and the node metrics settings, including the allowlist.
+//! A change of any other option is logged as requiring a restart. Nothing is applied if the new
+//! configuration is invalid. There are no rate limits nor chain registry to reload: the service
+//! doesn't rate limit requests and serves the fixed mainnet and testnet chains.
+
+use std::{
+    ffi::OsString,
+    sync::{Arc, Mutex},
+};
+
+use tracing::{error, info, warn};
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+use crate::{
+    alerts::load_rules, config::EffectiveConfig, metrics::ReloadLabels, server::ServerState,
+    Config, Error,
+};
+
+/// Handle changing the filter of the logs.
+pub type LogFilterHandle = reload::Handle<EnvFilter, Registry>;
+
+/// Options applied on reload.
+const RELOADABLE: [&str; 9] = [
+    "log_level",
+    "api_keys",
+    "auth_token_secret",
+    "anonymous_scopes",
+    "alert_rules",
+    "node_metrics",
+    "node_metrics_max_nodes",
+    "node_metrics_window",
+    "node_metrics_allowlist",
+];
+
+pub struct Reloader {
+    args: Vec<OsString>,
+    effective_config: Mutex<EffectiveConfig>,
+    log_filter: Option<LogFilterHandle>,
+}
+
+impl Reloader {
+    /// Creates a reloader parsing the configuration from the command line `args`, whose current
+    /// configuration is `effective_config`.
+    pub fn new<I, T>(args: I, effective_config: EffectiveConfig) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+    {
+        Self {
+            args: args.into_iter().map(Into::into).collect(),
+            effective_config: Mutex::new(effective_config),
+            log_filter: None,
+        }
+    }
+
+    /// Changes the filter of the logs through `handle` on reload.
+    pub fn with_log_filter(mut self, handle: LogFilterHandle) -> Self {
+        self.log_filter = Some(handle);
+        self
+    }
+
+    fn reload(&self, state: &ServerState) -> Result<(), Error> {
+        let (config, effective_config) = Config::load(&self.args)?;
+        let log_filter = config.logs.log_filter()?;
+        let alert_rules = match &config.alerts.alert_rules {
+            Some(path) => load_rules(path)?,
+            None => Vec::new(),
+        };
+
+        let mut current = self.effective_config.lock().expect("config lock poisoned");
+        for id in current.changes(&effective_config) {
+            if !RELOADABLE.contains(&id.as_str()) {
+                warn!("{id} changed, restart to apply it");
+            }
+        }
+        if let Some(handle) = &self.log_filter {
+            handle
+                .reload(log_filter)
+                .map_err(|err| Error::ConfigError(format!("failed to set log level: {err}")))?;
+        }
+        *state
+            .alert_rules
+            .write()
+            .expect("alert rules lock poisoned") = Arc::new(alert_rules);
+        *state
+            .node_metrics
+            .write()
+            .expect("node metrics lock poisoned") = Arc::new(config.node_metrics);
+        *state.auth.write().expect("auth lock poisoned") = Arc::new(config.auth);
+        *current = effective_config;
+        Ok(())
+    }
+}
+
+/// Reloads the configuration, if reloads are enabled.
+pub(crate) fn reload_config(state: &ServerState) {
+    let Some(reloader) = &state.reloader else {
+        return;
+    };
+    let status = match reloader.reload(state) {
+        Ok(()) => {
+            info!("configuration reloaded");
+            "success"
+        }
+        Err(err) => {
+            error!("failed to reload the configuration: {err}");
+            "failure"
+        }
+    };
+    state
+        .metrics
+        .config_reloads
+        .get_or_create(&ReloadLabels::new(status.to_string()))
+        .inc();
+}
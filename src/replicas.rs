@@ -0,0 +1,108 @@
+//! Read-only replicas serving the queries of the read endpoints and background tasks.
+//!
+//! Replicas are periodically pinged, the first checks connecting to the replicas that couldn't be
+//! reached so far. Replicas start unhealthy, and queries fall back to the primary database while the
+//! replica of their chain is unhealthy.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use sea_orm::DatabaseConnection;
+use tokio::sync::OnceCell;
+use tracing::{info, warn};
+
+use crate::{
+    config::DatabaseConfig, database::connect_replica, health::check, metrics::Labels,
+    server::ServerState, Error,
+};
+
+/// Database of a replica and how to connect to it.
+struct ReplicaTarget {
+    config: DatabaseConfig,
+    url: String,
+    db_name: &'static str,
+}
+
+#[derive(Clone)]
+pub(crate) struct Replica {
+    db: Arc<OnceCell<Arc<DatabaseConnection>>>,
+    /// Set for the replicas connected on their first check.
+    target: Option<Arc<ReplicaTarget>>,
+    healthy: Arc<AtomicBool>,
+}
+
+impl Replica {
+    pub(crate) fn new(db: DatabaseConnection) -> Self {
+        Self {
+            db: Arc::new(OnceCell::new_with(Some(Arc::new(db)))),
+            target: None,
+            healthy: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Returns a replica of the database `db_name` at `url`, connected on its first check.
+    pub(crate) fn lazy(config: DatabaseConfig, url: String, db_name: &'static str) -> Self {
+        Self {
+            db: Arc::new(OnceCell::new()),
+            target: Some(Arc::new(ReplicaTarget {
+                config,
+                url,
+                db_name,
+            })),
+            healthy: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Returns the database of the replica, unless it couldn't be connected yet.
+    pub(crate) fn db(&self) -> Option<&Arc<DatabaseConnection>> {
+        self.db.get()
+    }
+
+    pub(crate) fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    async fn connect(&self) -> Result<&Arc<DatabaseConnection>, Error> {
+        self.db
+            .get_or_try_init(|| async {
+                let target = self
+                    .target
+                    .as_ref()
+                    .expect("replicas without connection have a target");
+                let db = connect_replica(&target.config, &target.url, target.db_name).await?;
+                Ok(Arc::new(db))
+            })
+            .await
+    }
+}
+
+/// Pings every replica, connecting it if needed, and updates its health.
+pub(crate) async fn check_replicas(state: &ServerState) -> Result<(), Error> {
+    for (chain, replica) in state.replicas() {
+        let result = match replica.connect().await {
+            Ok(db) => check(db).await.map_err(Error::from),
+            Err(err) => Err(err),
+        };
+        let healthy = match result {
+            Ok(_) => true,
+            Err(err) => {
+                warn!("{chain} replica is unhealthy: {err:?}");
+                false
+            }
+        };
+        if replica.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+            info!(
+                "{chain} queries are now served by the {}",
+                if healthy { "replica" } else { "primary" }
+            );
+        }
+        state
+            .metrics
+            .replica_healthy
+            .get_or_create(&Labels::new(chain.to_string()))
+            .set(healthy as i64);
+    }
+    Ok(())
+}
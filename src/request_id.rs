@@ -0,0 +1,70 @@
+//! Request ids, to correlate the logs and traces of a request.
+//!
+//! Every request is handled within a `request` span holding its id, taken from the `X-Request-Id`
+//! header when valid or generated otherwise. The id is returned in the same header of the response,
+//! and available to the handlers as a [`RequestId`] extension.
+
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tracing::{info_span, Instrument};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use uuid::Uuid;
+
+use crate::otlp::parent_context;
+
+pub(crate) static X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Maximum length of the request ids accepted from clients.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+/// Id of the request being handled.
+#[derive(Clone, Debug)]
+pub(crate) struct RequestId(pub(crate) String);
+
+pub(crate) async fn request_id_middleware(mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(&X_REQUEST_ID)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_valid(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let span = info_span!(
+        "request",
+        request_id,
+        method = %request.method(),
+        path = request.uri().path()
+    );
+    span.set_parent(parent_context(request.headers()));
+    request
+        .extensions_mut()
+        .insert(RequestId(request_id.clone()));
+
+    let mut response = next.run(request).instrument(span).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(X_REQUEST_ID.clone(), value);
+    }
+    response
+}
+
+/// Returns the body of an error response, ending with the request id to find the details of the
+/// error in the logs.
+pub(crate) fn error_body(message: &str, request_id: Option<&str>) -> String {
+    match request_id {
+        Some(request_id) => format!("{message}\nrequest id: {request_id}\n"),
+        None => format!("{message}\n"),
+    }
+}
+
+fn is_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
@@ -0,0 +1,195 @@
+//! Retention policy: pruning of the nodes that stopped reporting.
+//!
+//! Stale nodes are either deleted or moved to the `node_archive` table. Archived nodes can in turn
+//! be deleted once they exceed their own retention window. The account claims not seen within the
+//! retention window are deleted along with the stale nodes.
+
+use chrono::NaiveDateTime;
+use sea_orm::{
+    sea_query::OnConflict, ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, Iterable,
+    PaginatorTrait, QueryFilter, QuerySelect, TransactionTrait,
+};
+use tracing::{debug, info};
+
+use crate::{
+    config::RetentionMode,
+    entities::{node, node_archive, node_claim},
+    metrics::ActionLabels,
+    server::ServerState,
+    Error,
+};
+
+/// Number of nodes archived per statement, keeping the bind parameters of the inserts within the
+/// limit of Postgres.
+const ARCHIVE_BATCH_SIZE: usize = 1000;
+
+/// Number of stale node ids logged by a dry run.
+const DRY_RUN_SAMPLE_SIZE: u64 = 20;
+
+/// Prunes the stale nodes of every chain according to the retention policy.
+pub(crate) async fn prune(state: &ServerState) -> Result<(), Error> {
+    let config = &state.retention;
+    let Some(retention_days) = config.retention_days else {
+        debug!("node retention is disabled");
+        return Ok(());
+    };
+    let now = chrono::offset::Utc::now().naive_utc();
+    let cutoff = now - chrono::Duration::days(retention_days as i64);
+
+    let action = match config.retention_mode {
+        RetentionMode::Delete => "deleted",
+        RetentionMode::Archive => "archived",
+    };
+
+    for (chain, db) in state.databases() {
+        let db = db.as_ref();
+        if config.prune_dry_run {
+            let stale = node::Entity::find().filter(node::Column::LastSeen.lt(cutoff));
+            let count = stale.clone().count(db).await?;
+            let sample: Vec<String> = stale
+                .select_only()
+                .column(node::Column::Id)
+                .limit(DRY_RUN_SAMPLE_SIZE)
+                .into_tuple()
+                .all(db)
+                .await?;
+            info!(
+                "dry run: {count} {chain} nodes not seen since {cutoff} would be {action}, \
+                 including {sample:?}"
+            );
+        } else {
+            let pruned = match config.retention_mode {
+                RetentionMode::Delete => delete_nodes(db, cutoff).await?,
+                RetentionMode::Archive => archive_nodes(db, cutoff, now).await?,
+            };
+            info!("{pruned} {chain} nodes not seen since {cutoff} {action}");
+            state
+                .metrics
+                .pruned_nodes
+                .get_or_create(&ActionLabels::new(chain.to_string(), action.to_string()))
+                .inc_by(pruned);
+        }
+
+        if config.prune_dry_run {
+            let count = node_claim::Entity::find()
+                .filter(node_claim::Column::LastSeen.lt(cutoff))
+                .count(db)
+                .await?;
+            info!("dry run: {count} {chain} claims not seen since {cutoff} would be deleted");
+        } else {
+            let result = node_claim::Entity::delete_many()
+                .filter(node_claim::Column::LastSeen.lt(cutoff))
+                .exec(db)
+                .await?;
+            info!(
+                "{} {chain} claims not seen since {cutoff} deleted",
+                result.rows_affected
+            );
+        }
+
+        if let Some(archive_retention_days) = config.archive_retention_days {
+            let archive_cutoff = now - chrono::Duration::days(archive_retention_days as i64);
+            if config.prune_dry_run {
+                let count = node_archive::Entity::find()
+                    .filter(node_archive::Column::ArchivedAt.lt(archive_cutoff))
+                    .count(db)
+                    .await?;
+                info!("dry run: {count} {chain} archived nodes would be deleted");
+            } else {
+                let result = node_archive::Entity::delete_many()
+                    .filter(node_archive::Column::ArchivedAt.lt(archive_cutoff))
+                    .exec(db)
+                    .await?;
+                info!("{} {chain} archived nodes deleted", result.rows_affected);
+                state
+                    .metrics
+                    .pruned_nodes
+                    .get_or_create(&ActionLabels::new(
+                        chain.to_string(),
+                        "archive_deleted".to_string(),
+                    ))
+                    .inc_by(result.rows_affected);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Deletes the nodes not seen since `cutoff`.
+async fn delete_nodes(db: &DatabaseConnection, cutoff: NaiveDateTime) -> Result<u64, Error> {
+    let result = node::Entity::delete_many()
+        .filter(node::Column::LastSeen.lt(cutoff))
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected)
+}
+
+/// Moves the nodes not seen since `cutoff` to the archive, in batches within a single transaction.
+async fn archive_nodes(
+    db: &DatabaseConnection,
+    cutoff: NaiveDateTime,
+    now: NaiveDateTime,
+) -> Result<u64, Error> {
+    let txn = db.begin().await?;
+
+    // Lock the stale rows so that nodes reporting in the meantime are not archived.
+    let nodes = node::Entity::find()
+        .filter(node::Column::LastSeen.lt(cutoff))
+        .lock_exclusive()
+        .all(&txn)
+        .await?;
+
+    // A node archived in the past may have come back and gone stale again.
+    let on_conflict = OnConflict::column(node_archive::Column::Id)
+        .update_columns(
+            node_archive::Column::iter().filter(|col| !matches!(*col, node_archive::Column::Id)),
+        )
+        .to_owned();
+    let mut archived = 0;
+    let mut nodes = nodes.into_iter().peekable();
+    while nodes.peek().is_some() {
+        let batch: Vec<node::Model> = nodes.by_ref().take(ARCHIVE_BATCH_SIZE).collect();
+        let ids: Vec<String> = batch.iter().map(|node| node.id.clone()).collect();
+        node_archive::Entity::insert_many(batch.into_iter().map(|node| archived_node(node, now)))
+            .on_conflict(on_conflict.clone())
+            .exec_without_returning(&txn)
+            .await?;
+        let result = node::Entity::delete_many()
+            .filter(node::Column::Id.is_in(ids))
+            .exec(&txn)
+            .await?;
+        archived += result.rows_affected;
+    }
+
+    txn.commit().await?;
+    Ok(archived)
+}
+
+/// Returns the archived copy of `node`.
+fn archived_node(node: node::Model, archived_at: NaiveDateTime) -> node_archive::ActiveModel {
+    node_archive::ActiveModel {
+        id: ActiveValue::Set(node.id),
+        account_id: ActiveValue::Set(node.account_id),
+        last_seen: ActiveValue::Set(node.last_seen),
+        last_height: ActiveValue::Set(node.last_height),
+        last_hash: ActiveValue::Set(node.last_hash),
+        agent_name: ActiveValue::Set(node.agent_name),
+        agent_version: ActiveValue::Set(node.agent_version),
+        agent_build: ActiveValue::Set(node.agent_build),
+        peer_count: ActiveValue::Set(node.peer_count),
+        is_validator: ActiveValue::Set(node.is_validator),
+        status: ActiveValue::Set(node.status),
+        bandwidth_download: ActiveValue::Set(node.bandwidth_download),
+        bandwidth_upload: ActiveValue::Set(node.bandwidth_upload),
+        cpu_usage: ActiveValue::Set(node.cpu_usage),
+        memory_usage: ActiveValue::Set(node.memory_usage),
+        boot_time_seconds: ActiveValue::Set(node.boot_time_seconds),
+        block_production_tracking_delay: ActiveValue::Set(node.block_production_tracking_delay),
+        min_block_production_delay: ActiveValue::Set(node.min_block_production_delay),
+        max_block_production_delay: ActiveValue::Set(node.max_block_production_delay),
+        max_block_wait_delay: ActiveValue::Set(node.max_block_wait_delay),
+        chain_id: ActiveValue::Set(node.chain_id),
+        protocol_version: ActiveValue::Set(node.protocol_version),
+        archived_at: ActiveValue::Set(archived_at),
+    }
+}
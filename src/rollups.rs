@@ -0,0 +1,322 @@
+//! Time-bucketed rollups of the node metrics, for long-term trends.
+//!
+//! The aggregator periodically summarises the nodes seen during the current hour and day, and
+//! upserts the result in the `rollup` and `rollup_version` tables. Once a bucket is over, its
+//! rollup keeps the values computed by the last run within the bucket.
+
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{DurationRound, NaiveDateTime};
+use sea_orm::{
+    sea_query::OnConflict, ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, Iterable,
+    QueryFilter, QueryOrder, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+
+use crate::{
+    entities::{node, rollup, rollup_version},
+    nodes::{recent_nodes, ChainId},
+    request_id::{error_body, RequestId},
+    server::ServerState,
+    Error,
+};
+
+/// Protocol version stored for nodes that don't report it.
+const UNKNOWN_PROTOCOL_VERSION: i32 = 0;
+/// Number of buckets returned when the query doesn't specify a start.
+const DEFAULT_QUERY_BUCKETS: i32 = 48;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Granularity {
+    #[default]
+    Hour,
+    Day,
+}
+
+impl Granularity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Granularity::Hour => "hour",
+            Granularity::Day => "day",
+        }
+    }
+
+    fn duration(&self) -> chrono::Duration {
+        match self {
+            Granularity::Hour => chrono::Duration::hours(1),
+            Granularity::Day => chrono::Duration::days(1),
+        }
+    }
+
+    /// Returns the start of the bucket containing `time`.
+    fn bucket_start(&self, time: NaiveDateTime) -> NaiveDateTime {
+        time.duration_trunc(self.duration())
+            .expect("bucket duration is valid")
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct RollupQuery {
+    #[serde(default)]
+    granularity: Granularity,
+    from: Option<NaiveDateTime>,
+    to: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Debug)]
+struct Stats {
+    min: Option<f64>,
+    avg: Option<f64>,
+    max: Option<f64>,
+}
+
+#[derive(Serialize, Debug)]
+struct VersionCount {
+    agent_version: String,
+    protocol_version: Option<i32>,
+    node_count: i64,
+}
+
+#[derive(Serialize, Debug)]
+struct RollupView {
+    bucket_start: NaiveDateTime,
+    node_count: i64,
+    validator_count: i64,
+    peer_count: Stats,
+    cpu_usage: Stats,
+    memory_usage: Stats,
+    bandwidth_download: Stats,
+    bandwidth_upload: Stats,
+    versions: Vec<VersionCount>,
+}
+
+pub(crate) async fn rollups_handler(
+    state: State<ServerState>,
+    request_id: Option<Extension<RequestId>>,
+    Path(chain): Path<String>,
+    Query(query): Query<RollupQuery>,
+) -> Response {
+    let chain = ChainId::from(chain.as_str());
+    let Some(db) = state.read_database(&chain) else {
+        return (StatusCode::NOT_FOUND, format!("unknown chain: {chain}")).into_response();
+    };
+    match load_rollups(db, &query).await {
+        Ok(rollups) => Json(rollups).into_response(),
+        Err(err) => {
+            error!("error loading {chain} rollups: {err:#?}");
+            let request_id = request_id.map(|Extension(RequestId(id))| id);
+            let message = format!("error loading rollups: {err}");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                error_body(&message, request_id.as_deref()),
+            )
+                .into_response()
+        }
+    }
+}
+
+async fn load_rollups(
+    db: &DatabaseConnection,
+    query: &RollupQuery,
+) -> Result<Vec<RollupView>, Error> {
+    let granularity = query.granularity;
+    let to = query
+        .to
+        .unwrap_or_else(|| chrono::offset::Utc::now().naive_utc());
+    let from = query
+        .from
+        .unwrap_or_else(|| to - granularity.duration() * DEFAULT_QUERY_BUCKETS);
+
+    let rollups = rollup::Entity::find()
+        .filter(rollup::Column::Granularity.eq(granularity.as_str()))
+        .filter(rollup::Column::BucketStart.between(from, to))
+        .order_by_asc(rollup::Column::BucketStart)
+        .all(db)
+        .await?;
+    let mut versions: BTreeMap<NaiveDateTime, Vec<VersionCount>> = BTreeMap::new();
+    for version in rollup_version::Entity::find()
+        .filter(rollup_version::Column::Granularity.eq(granularity.as_str()))
+        .filter(rollup_version::Column::BucketStart.between(from, to))
+        .order_by_asc(rollup_version::Column::AgentVersion)
+        .order_by_asc(rollup_version::Column::ProtocolVersion)
+        .all(db)
+        .await?
+    {
+        versions
+            .entry(version.bucket_start)
+            .or_default()
+            .push(VersionCount {
+                agent_version: version.agent_version,
+                protocol_version: Some(version.protocol_version)
+                    .filter(|v| *v != UNKNOWN_PROTOCOL_VERSION),
+                node_count: version.node_count,
+            });
+    }
+
+    Ok(rollups
+        .into_iter()
+        .map(|rollup| RollupView {
+            bucket_start: rollup.bucket_start,
+            node_count: rollup.node_count,
+            validator_count: rollup.validator_count,
+            peer_count: Stats {
+                min: rollup.peer_count_min,
+                avg: rollup.peer_count_avg,
+                max: rollup.peer_count_max,
+            },
+            cpu_usage: Stats {
+                min: rollup.cpu_usage_min,
+                avg: rollup.cpu_usage_avg,
+                max: rollup.cpu_usage_max,
+            },
+            memory_usage: Stats {
+                min: rollup.memory_usage_min,
+                avg: rollup.memory_usage_avg,
+                max: rollup.memory_usage_max,
+            },
+            bandwidth_download: Stats {
+                min: rollup.bandwidth_download_min,
+                avg: rollup.bandwidth_download_avg,
+                max: rollup.bandwidth_download_max,
+            },
+            bandwidth_upload: Stats {
+                min: rollup.bandwidth_upload_min,
+                avg: rollup.bandwidth_upload_avg,
+                max: rollup.bandwidth_upload_max,
+            },
+            versions: versions.remove(&rollup.bucket_start).unwrap_or_default(),
+        })
+        .collect())
+}
+
+/// Updates the rollups of the current hour and day for every chain.
+pub(crate) async fn update_rollups(state: &ServerState) -> Result<(), Error> {
+    let now = chrono::offset::Utc::now().naive_utc();
+    for (chain, db) in state.databases() {
+        // The day bucket includes the hour bucket.
+        let day_start = Granularity::Day.bucket_start(now);
+        let read_db = state.read_database(&chain).unwrap_or(db);
+        let nodes = recent_nodes(read_db, now - day_start).await?;
+        for granularity in [Granularity::Hour, Granularity::Day] {
+            let bucket_start = granularity.bucket_start(now);
+            let nodes: Vec<&node::Model> = nodes
+                .iter()
+                .filter(|node| node.last_seen >= bucket_start)
+                .collect();
+            debug!(
+                "{chain} {} rollup at {bucket_start}: {} nodes",
+                granularity.as_str(),
+                nodes.len()
+            );
+            store_rollup(db, granularity, bucket_start, &nodes).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn store_rollup(
+    db: &DatabaseConnection,
+    granularity: Granularity,
+    bucket_start: NaiveDateTime,
+    nodes: &[&node::Model],
+) -> Result<(), Error> {
+    let stats = |value: fn(&node::Model) -> f64| {
+        let values = nodes.iter().map(|node| value(node));
+        let min = values.clone().reduce(f64::min);
+        let max = values.clone().reduce(f64::max);
+        let avg = (!nodes.is_empty()).then(|| values.sum::<f64>() / nodes.len() as f64);
+        (
+            ActiveValue::Set(min),
+            ActiveValue::Set(avg),
+            ActiveValue::Set(max),
+        )
+    };
+    let (peer_count_min, peer_count_avg, peer_count_max) = stats(|n| n.peer_count as f64);
+    let (cpu_usage_min, cpu_usage_avg, cpu_usage_max) = stats(|n| n.cpu_usage as f64);
+    let (memory_usage_min, memory_usage_avg, memory_usage_max) = stats(|n| n.memory_usage as f64);
+    let (bandwidth_download_min, bandwidth_download_avg, bandwidth_download_max) =
+        stats(|n| n.bandwidth_download as f64);
+    let (bandwidth_upload_min, bandwidth_upload_avg, bandwidth_upload_max) =
+        stats(|n| n.bandwidth_upload as f64);
+
+    let rollup = rollup::ActiveModel {
+        granularity: ActiveValue::Set(granularity.as_str().to_string()),
+        bucket_start: ActiveValue::Set(bucket_start),
+        node_count: ActiveValue::Set(nodes.len() as i64),
+        validator_count: ActiveValue::Set(nodes.iter().filter(|n| n.is_validator).count() as i64),
+        peer_count_min,
+        peer_count_avg,
+        peer_count_max,
+        cpu_usage_min,
+        cpu_usage_avg,
+        cpu_usage_max,
+        memory_usage_min,
+        memory_usage_avg,
+        memory_usage_max,
+        bandwidth_download_min,
+        bandwidth_download_avg,
+        bandwidth_download_max,
+        bandwidth_upload_min,
+        bandwidth_upload_avg,
+        bandwidth_upload_max,
+    };
+
+    let mut versions: BTreeMap<(&str, i32), i64> = BTreeMap::new();
+    for node in nodes {
+        let protocol_version = node.protocol_version.unwrap_or(UNKNOWN_PROTOCOL_VERSION);
+        *versions
+            .entry((node.agent_version.as_str(), protocol_version))
+            .or_default() += 1;
+    }
+    let versions: Vec<rollup_version::ActiveModel> = versions
+        .into_iter()
+        .map(
+            |((agent_version, protocol_version), node_count)| rollup_version::ActiveModel {
+                granularity: ActiveValue::Set(granularity.as_str().to_string()),
+                bucket_start: ActiveValue::Set(bucket_start),
+                agent_version: ActiveValue::Set(agent_version.to_string()),
+                protocol_version: ActiveValue::Set(protocol_version),
+                node_count: ActiveValue::Set(node_count),
+            },
+        )
+        .collect();
+
+    let txn = db.begin().await?;
+
+    let on_conflict =
+        OnConflict::columns([rollup::Column::Granularity, rollup::Column::BucketStart])
+            .update_columns(rollup::Column::iter().filter(|col| {
+                !matches!(
+                    *col,
+                    rollup::Column::Granularity | rollup::Column::BucketStart
+                )
+            }))
+            .to_owned();
+    rollup::Entity::insert(rollup)
+        .on_conflict(on_conflict)
+        .exec_without_returning(&txn)
+        .await?;
+
+    // Versions that disappeared from the bucket must not be kept.
+    rollup_version::Entity::delete_many()
+        .filter(rollup_version::Column::Granularity.eq(granularity.as_str()))
+        .filter(rollup_version::Column::BucketStart.eq(bucket_start))
+        .exec(&txn)
+        .await?;
+    if !versions.is_empty() {
+        rollup_version::Entity::insert_many(versions)
+            .exec_without_returning(&txn)
+            .await?;
+    }
+
+    txn.commit().await?;
+    Ok(())
+}
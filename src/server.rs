@@ -1,19 +1,51 @@
 use axum::http::StatusCode;
+use axum::middleware;
 use axum::response::IntoResponse;
-use axum::routing::post;
+use axum::routing::{delete, post, put};
 use axum::{routing::get, Router};
-use derive_more::Constructor;
 use prometheus_client::registry::Registry;
 use sea_orm::DatabaseConnection;
-use std::{net::SocketAddr, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{atomic::AtomicI64, Arc, RwLock},
+    time::Duration,
+};
 use tokio::net::TcpListener;
 use tokio::signal;
 use tower_http::timeout::TimeoutLayer;
-use tracing::info;
+use tracing::{info, warn};
 
-use crate::health::health_handler;
+use crate::admin::{
+    annotate_node_handler, annotations_handler, bans_handler, create_ban_handler,
+    delete_annotation_handler, delete_ban_handler, delete_node_handler, Bans,
+};
+use crate::alerts::{alerts_handler, load_rules, AlertRule, AlertsState};
+use crate::auth::{self, auth_middleware};
+use crate::claims::claims_handler;
+use crate::config::{
+    AdminConfig, AlertsConfig, AuthConfig, ClaimsConfig, DatabaseConfig, ForkDetectionConfig,
+    HealthConfig, HistoryConfig, LogsConfig, NetworkStatsConfig, NodeMetricsConfig, ReplicasConfig,
+    RetentionConfig, RollupsConfig, Scope, StreamConfig, SyncStatusConfig, TlsConfig,
+    ValidatorsConfig,
+};
+use crate::database::{MAINNET_DB_NAME, TESTNET_DB_NAME};
+use crate::forks::{forks_handler, ForkReport};
+use crate::health::{health_handler, liveness_handler, readiness_handler};
 use crate::metrics::{create_registry_and_metrics, metric_handler, Metrics};
+use crate::node_metrics::node_metrics_handler;
 use crate::nodes::{nodes_handler, nodes_handler_mainnet, nodes_handler_testnet, ChainId};
+use crate::reload::{reload_config, Reloader};
+use crate::replicas::Replica;
+use crate::request_id::request_id_middleware;
+use crate::rollups::rollups_handler;
+use crate::stream::{sse_handler, ws_handler, Updates};
+use crate::substrate_feed::feed_handler;
+use crate::sync_status::{sync_status_handler, SyncReport};
+use crate::tasks::{run_task, spawn_tasks, Task};
+use crate::tls::{self, client_certificate_middleware, serve_tls};
+use crate::validators::{validators_handler, ValidatorAlert};
+use crate::webhook::Webhook;
 use crate::Error;
 
 pub struct Server {
@@ -21,15 +53,113 @@ pub struct Server {
     state: ServerState,
 }
 
-#[derive(Constructor, Clone)]
+#[derive(Clone)]
 pub(crate) struct ServerState {
     pub(crate) metrics_registry: Arc<Registry>,
     pub(crate) metrics: Arc<Metrics>,
     pub(crate) db_mainnet: Arc<DatabaseConnection>,
     pub(crate) db_testnet: Arc<DatabaseConnection>,
+    pub(crate) replicas_config: Arc<ReplicasConfig>,
+    pub(crate) replica_mainnet: Option<Replica>,
+    pub(crate) replica_testnet: Option<Replica>,
+    pub(crate) health: Arc<HealthConfig>,
+    /// Number of ingestion requests currently being processed.
+    pub(crate) ingestion_in_flight: Arc<AtomicI64>,
+    pub(crate) fork_detection: Arc<ForkDetectionConfig>,
+    pub(crate) forks: Arc<RwLock<HashMap<ChainId, ForkReport>>>,
+    pub(crate) sync_status_config: Arc<SyncStatusConfig>,
+    pub(crate) sync_status: Arc<RwLock<HashMap<ChainId, SyncReport>>>,
+    pub(crate) validators_config: Arc<ValidatorsConfig>,
+    pub(crate) validators_webhook: Option<Webhook>,
+    /// Alerts currently raised for validators, by chain and account.
+    pub(crate) validator_alerts: Arc<RwLock<HashMap<ChainId, HashMap<String, ValidatorAlert>>>>,
+    pub(crate) claims: Arc<ClaimsConfig>,
+    pub(crate) alerts_config: Arc<AlertsConfig>,
+    pub(crate) alert_rules: Arc<RwLock<Arc<Vec<AlertRule>>>>,
+    pub(crate) alerts_webhook: Option<Webhook>,
+    pub(crate) alerts: Arc<RwLock<AlertsState>>,
+    pub(crate) retention: Arc<RetentionConfig>,
+    pub(crate) rollups: Arc<RollupsConfig>,
+    pub(crate) history: Arc<HistoryConfig>,
+    pub(crate) node_metrics: Arc<RwLock<Arc<NodeMetricsConfig>>>,
+    pub(crate) network_stats: Arc<NetworkStatsConfig>,
+    pub(crate) logs: Arc<LogsConfig>,
+    pub(crate) reloader: Option<Arc<Reloader>>,
+    pub(crate) tls: Arc<TlsConfig>,
+    pub(crate) auth: Arc<RwLock<Arc<AuthConfig>>>,
+    pub(crate) admin: Arc<AdminConfig>,
+    /// Banned node ids and IP addresses, by chain.
+    pub(crate) bans: Arc<RwLock<HashMap<ChainId, Bans>>>,
+    pub(crate) stream: Arc<StreamConfig>,
+    pub(crate) updates: Arc<Updates>,
 }
 
 impl ServerState {
+    fn new(
+        metrics_registry: Arc<Registry>,
+        metrics: Arc<Metrics>,
+        db_mainnet: Arc<DatabaseConnection>,
+        db_testnet: Arc<DatabaseConnection>,
+    ) -> Self {
+        Self {
+            metrics_registry,
+            metrics,
+            db_mainnet,
+            db_testnet,
+            replicas_config: Arc::default(),
+            replica_mainnet: None,
+            replica_testnet: None,
+            health: Arc::default(),
+            ingestion_in_flight: Arc::default(),
+            fork_detection: Arc::default(),
+            forks: Arc::default(),
+            sync_status_config: Arc::default(),
+            sync_status: Arc::default(),
+            validators_config: Arc::default(),
+            validators_webhook: None,
+            validator_alerts: Arc::default(),
+            claims: Arc::default(),
+            alerts_config: Arc::default(),
+            alert_rules: Arc::default(),
+            alerts_webhook: None,
+            alerts: Arc::default(),
+            retention: Arc::default(),
+            rollups: Arc::default(),
+            history: Arc::default(),
+            node_metrics: Arc::default(),
+            network_stats: Arc::default(),
+            logs: Arc::default(),
+            reloader: None,
+            tls: Arc::default(),
+            auth: Arc::default(),
+            admin: Arc::default(),
+            bans: Arc::default(),
+            stream: Arc::default(),
+            updates: Arc::default(),
+        }
+    }
+
+    /// Returns the current authentication configuration.
+    pub(crate) fn auth(&self) -> Arc<AuthConfig> {
+        self.auth.read().expect("auth lock poisoned").clone()
+    }
+
+    /// Returns the current alert rules.
+    pub(crate) fn alert_rules(&self) -> Arc<Vec<AlertRule>> {
+        self.alert_rules
+            .read()
+            .expect("alert rules lock poisoned")
+            .clone()
+    }
+
+    /// Returns the current node metrics configuration.
+    pub(crate) fn node_metrics(&self) -> Arc<NodeMetricsConfig> {
+        self.node_metrics
+            .read()
+            .expect("node metrics lock poisoned")
+            .clone()
+    }
+
     pub(crate) fn database(&self, chain: &ChainId) -> Option<&Arc<DatabaseConnection>> {
         match chain {
             ChainId::Mainnet => Some(&self.db_mainnet),
@@ -37,6 +167,47 @@ impl ServerState {
             ChainId::Other(_) => None,
         }
     }
+
+    /// Returns the databases of all the chains whose telemetry is persisted.
+    pub(crate) fn databases(&self) -> [(ChainId, &Arc<DatabaseConnection>); 2] {
+        [
+            (ChainId::Mainnet, &self.db_mainnet),
+            (ChainId::Testnet, &self.db_testnet),
+        ]
+    }
+
+    /// Returns the replicas of the chains that have one.
+    pub(crate) fn replicas(&self) -> Vec<(ChainId, &Replica)> {
+        [
+            (ChainId::Mainnet, &self.replica_mainnet),
+            (ChainId::Testnet, &self.replica_testnet),
+        ]
+        .into_iter()
+        .filter_map(|(chain, replica)| Some((chain, replica.as_ref()?)))
+        .collect()
+    }
+
+    /// Returns the database serving the read queries of `chain`: its replica while healthy,
+    /// otherwise the primary database.
+    pub(crate) fn read_database(&self, chain: &ChainId) -> Option<&Arc<DatabaseConnection>> {
+        let replica = match chain {
+            ChainId::Mainnet => &self.replica_mainnet,
+            ChainId::Testnet => &self.replica_testnet,
+            ChainId::Other(_) => return None,
+        };
+        match replica.as_ref().filter(|replica| replica.is_healthy()) {
+            Some(replica) => replica.db(),
+            None => self.database(chain),
+        }
+    }
+
+    /// Returns the databases serving the read queries of all the chains.
+    pub(crate) fn read_databases(&self) -> [(ChainId, &Arc<DatabaseConnection>); 2] {
+        self.databases().map(|(chain, db)| {
+            let db = self.read_database(&chain).unwrap_or(db);
+            (chain, db)
+        })
+    }
 }
 
 impl Server {
@@ -57,24 +228,240 @@ impl Server {
         })
     }
 
+    /// Serves the read queries from the given replicas, when set.
+    pub fn with_replicas(
+        mut self,
+        config: ReplicasConfig,
+        replica_mainnet: Option<DatabaseConnection>,
+        replica_testnet: Option<DatabaseConnection>,
+    ) -> Self {
+        self.state.replica_mainnet = replica_mainnet.map(Replica::new);
+        self.state.replica_testnet = replica_testnet.map(Replica::new);
+        self.state.replicas_config = Arc::new(config);
+        self
+    }
+
+    /// Serves the read queries from the replicas at the URLs of `config`, when set. Replicas are
+    /// connected by their first successful check, so that they can be unreachable at startup.
+    pub fn with_replica_urls(mut self, config: ReplicasConfig, database: &DatabaseConfig) -> Self {
+        let replica = |url: &Option<String>, db_name| {
+            url.clone()
+                .map(|url| Replica::lazy(database.clone(), url, db_name))
+        };
+        self.state.replica_mainnet = replica(&config.mainnet_replica_url, MAINNET_DB_NAME);
+        self.state.replica_testnet = replica(&config.testnet_replica_url, TESTNET_DB_NAME);
+        self.state.replicas_config = Arc::new(config);
+        self
+    }
+
+    pub fn with_health(mut self, config: HealthConfig) -> Self {
+        self.state.health = Arc::new(config);
+        self
+    }
+
+    pub fn with_fork_detection(mut self, config: ForkDetectionConfig) -> Self {
+        self.state.fork_detection = Arc::new(config);
+        self
+    }
+
+    pub fn with_sync_status(mut self, config: SyncStatusConfig) -> Self {
+        self.state.sync_status_config = Arc::new(config);
+        self
+    }
+
+    pub fn with_validators(mut self, config: ValidatorsConfig) -> Self {
+        self.state.validators_webhook = config.validator_webhook_url.clone().map(Webhook::new);
+        self.state.validators_config = Arc::new(config);
+        self
+    }
+
+    pub fn with_claims(mut self, config: ClaimsConfig) -> Self {
+        self.state.claims = Arc::new(config);
+        self
+    }
+
+    /// Enables alerting, loading the rules from the configured file.
+    pub fn with_alerts(mut self, config: AlertsConfig) -> Result<Self, Error> {
+        if let Some(path) = &config.alert_rules {
+            let rules = load_rules(path)?;
+            info!("loaded {} alert rules from {}", rules.len(), path.display());
+            self.state.alert_rules = Arc::new(RwLock::new(Arc::new(rules)));
+        }
+        self.state.alerts_webhook = config.alert_webhook_url.clone().map(Webhook::new);
+        self.state.alerts_config = Arc::new(config);
+        Ok(self)
+    }
+
+    pub fn with_retention(mut self, config: RetentionConfig) -> Self {
+        self.state.retention = Arc::new(config);
+        self
+    }
+
+    pub fn with_rollups(mut self, config: RollupsConfig) -> Self {
+        self.state.rollups = Arc::new(config);
+        self
+    }
+
+    pub fn with_history(mut self, config: HistoryConfig) -> Self {
+        self.state.history = Arc::new(config);
+        self
+    }
+
+    pub fn with_node_metrics(mut self, config: NodeMetricsConfig) -> Self {
+        self.state.node_metrics = Arc::new(RwLock::new(Arc::new(config)));
+        self
+    }
+
+    pub fn with_network_stats(mut self, config: NetworkStatsConfig) -> Self {
+        self.state.network_stats = Arc::new(config);
+        self
+    }
+
+    pub fn with_logs(mut self, config: LogsConfig) -> Self {
+        self.state.logs = Arc::new(config);
+        self
+    }
+
+    pub fn with_stream(mut self, config: StreamConfig) -> Self {
+        self.state.updates = Arc::new(Updates::new(config.stream_buffer));
+        self.state.stream = Arc::new(config);
+        self
+    }
+
+    /// Serves over TLS, if a certificate is configured.
+    pub fn with_tls(mut self, config: TlsConfig) -> Self {
+        self.state.tls = Arc::new(config);
+        self
+    }
+
+    /// Restricts the route groups to the clients granted their scope.
+    pub fn with_auth(mut self, config: AuthConfig) -> Self {
+        self.state.auth = Arc::new(RwLock::new(Arc::new(config)));
+        self
+    }
+
+    pub fn with_admin(mut self, config: AdminConfig) -> Self {
+        self.state.admin = Arc::new(config);
+        self
+    }
+
+    /// Reloads the configuration on SIGHUP.
+    pub fn with_reloader(mut self, reloader: Reloader) -> Self {
+        self.state.reloader = Some(Arc::new(reloader));
+        self
+    }
+
     pub async fn run(&self) -> Result<(), Error> {
-        info!("starting HTTP server on {}", self.address);
+        let tls = tls::is_enabled(&self.state.tls)?;
+        let scheme = if tls { "HTTPS" } else { "HTTP" };
+        info!("starting {scheme} server on {}", self.address);
+        if !auth::is_enabled(&self.state.auth()) {
+            warn!("authentication is disabled, every API is public");
+        }
 
-        let listener = TcpListener::bind(self.address).await?;
+        let listener = if tls {
+            None
+        } else {
+            Some(TcpListener::bind(self.address).await?)
+        };
         let app = self.app();
-        axum::serve(listener, app)
-            .with_graceful_shutdown(shutdown_signal())
-            .await?;
-        Ok(())
+        let mut tasks = spawn_tasks(&self.state);
+        if self.state.reloader.is_some() {
+            tasks.push(tokio::spawn(reload_signal(self.state.clone())));
+        }
+        // Close the live update streams, which would otherwise hold the graceful shutdown.
+        let updates = self.state.updates.clone();
+        let shutdown = async move {
+            shutdown_signal().await;
+            updates.close();
+        };
+        let result = match listener {
+            Some(listener) => axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown)
+            .await
+            .map_err(Error::from),
+            None => serve_tls(self.address, app, &self.state.tls, shutdown).await,
+        };
+        for task in tasks {
+            task.abort();
+        }
+        result
+    }
+
+    /// Executes a single run of a background task.
+    pub async fn run_task(&self, task: Task) -> Result<(), Error> {
+        run_task(&self.state, task).await
+    }
+
+    /// Reloads the configuration, as on SIGHUP.
+    pub fn reload_config(&self) {
+        reload_config(&self.state)
     }
 
     pub fn app(&self) -> Router {
-        Router::new()
-            .route("/metrics", get(metric_handler))
-            .route("/healthz", get(health_handler))
+        let ingest_routes = Router::new()
             .route("/nodes/mainnet", post(nodes_handler_mainnet))
             .route("/nodes/testnet", post(nodes_handler_testnet))
             .route("/nodes", post(nodes_handler))
+            .route_layer(middleware::from_fn_with_state(
+                (self.state.clone(), Scope::Ingest),
+                auth_middleware,
+            ));
+        // Read APIs, restricted to verified clients when client certificates are required.
+        let read_routes = Router::new()
+            .route("/metrics", get(metric_handler))
+            .route("/metrics/nodes", get(node_metrics_handler))
+            .route("/forks/:chain", get(forks_handler))
+            .route("/sync/:chain", get(sync_status_handler))
+            .route("/validators/:chain", get(validators_handler))
+            .route("/claims/:chain", get(claims_handler))
+            .route("/nodes/:chain/stream", get(sse_handler))
+            .route("/nodes/:chain/ws", get(ws_handler))
+            .route("/feed", get(feed_handler))
+            .route("/alerts", get(alerts_handler))
+            .route("/rollups/:chain", get(rollups_handler))
+            .route_layer(middleware::from_fn_with_state(
+                (self.state.clone(), Scope::Read),
+                auth_middleware,
+            ))
+            .route_layer(middleware::from_fn_with_state(
+                self.state.clone(),
+                client_certificate_middleware,
+            ));
+        // Admin APIs, restricted like the read APIs.
+        let admin_routes = Router::new()
+            .route("/admin/nodes/:chain/:id", delete(delete_node_handler))
+            .route(
+                "/admin/bans/:chain",
+                get(bans_handler).post(create_ban_handler),
+            )
+            .route(
+                "/admin/bans/:chain/:kind/:value",
+                delete(delete_ban_handler),
+            )
+            .route("/admin/annotations/:chain", get(annotations_handler))
+            .route(
+                "/admin/annotations/:chain/:id",
+                put(annotate_node_handler).delete(delete_annotation_handler),
+            )
+            .route_layer(middleware::from_fn_with_state(
+                (self.state.clone(), Scope::Admin),
+                auth_middleware,
+            ))
+            .route_layer(middleware::from_fn_with_state(
+                self.state.clone(),
+                client_certificate_middleware,
+            ));
+        Router::new()
+            .route("/healthz", get(health_handler))
+            .route("/livez", get(liveness_handler))
+            .route("/readyz", get(readiness_handler))
+            .merge(ingest_routes)
+            .merge(read_routes)
+            .merge(admin_routes)
             .layer((
                 // Graceful shutdown will wait for outstanding requests to complete. Add a timeout so
                 // requests don't hang forever.
@@ -82,6 +469,7 @@ impl Server {
             ))
             .with_state(self.state.clone())
             .fallback(handler_404)
+            .layer(middleware::from_fn(request_id_middleware))
     }
 
     pub fn into_db_connections(self) -> (Option<DatabaseConnection>, Option<DatabaseConnection>) {
@@ -116,6 +504,22 @@ async fn shutdown_signal() {
     }
 }
 
+/// Reloads the configuration on every SIGHUP.
+async fn reload_signal(state: ServerState) {
+    #[cfg(unix)]
+    {
+        let mut hangup = signal::unix::signal(signal::unix::SignalKind::hangup())
+            .expect("failed to install signal handler");
+        while hangup.recv().await.is_some() {
+            info!("received SIGHUP, reloading the configuration");
+            reload_config(&state);
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = state;
+}
+
 async fn handler_404() -> impl IntoResponse {
     (StatusCode::NOT_FOUND, "nothing to see here")
 }
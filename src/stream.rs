@@ -0,0 +1,324 @@
+//! Live updates of the nodes, pushed to the clients over Server-Sent Events or WebSocket.
+//!
+//! Every upsert accepted at ingestion is broadcast to the subscribed clients through a bounded
+//! buffer, so that slow clients never hold back the ingestion: those falling behind skip the oldest
+//! updates and are told how many they missed. A heartbeat keeps idle connections open through
+//! proxies, and the streams are closed on shutdown.
+
+use std::{collections::HashSet, future, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+};
+use chrono::NaiveDateTime;
+use futures::stream;
+use prometheus_client::metrics::gauge::Gauge;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{broadcast, watch},
+    time::{self, Instant, Interval},
+};
+use tracing::debug;
+
+use crate::{
+    config::StreamConfig,
+    entities::node,
+    metrics::{Labels, Metrics},
+    nodes::ChainId,
+    server::ServerState,
+};
+
+/// Update of a node, as accepted at ingestion.
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct NodeUpdate {
+    pub(crate) chain: String,
+    pub(crate) id: String,
+    pub(crate) account_id: Option<String>,
+    pub(crate) is_validator: bool,
+    pub(crate) agent_name: String,
+    pub(crate) agent_version: String,
+    pub(crate) agent_build: String,
+    pub(crate) protocol_version: Option<i32>,
+    pub(crate) height: i64,
+    pub(crate) hash: String,
+    pub(crate) status: String,
+    pub(crate) peer_count: i64,
+    pub(crate) bandwidth_download: i64,
+    pub(crate) bandwidth_upload: i64,
+    pub(crate) last_seen: NaiveDateTime,
+}
+
+impl NodeUpdate {
+    pub(crate) fn new(chain: &ChainId, node: node::Model) -> Self {
+        Self {
+            chain: chain.to_string(),
+            id: node.id,
+            account_id: node.account_id,
+            is_validator: node.is_validator,
+            agent_name: node.agent_name,
+            agent_version: node.agent_version,
+            agent_build: node.agent_build,
+            protocol_version: node.protocol_version,
+            height: node.last_height,
+            hash: node.last_hash,
+            status: node.status,
+            peer_count: node.peer_count,
+            bandwidth_download: node.bandwidth_download,
+            bandwidth_upload: node.bandwidth_upload,
+            last_seen: node.last_seen,
+        }
+    }
+}
+
+/// Event sent to a stream client.
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub(crate) enum StreamEvent {
+    Node(Box<NodeUpdate>),
+    /// The client fell behind and skipped updates.
+    Lagged {
+        skipped: u64,
+    },
+}
+
+impl StreamEvent {
+    fn into_sse(self) -> Result<Event, axum::Error> {
+        match self {
+            StreamEvent::Node(update) => Event::default().event("node").json_data(update),
+            StreamEvent::Lagged { skipped } => Event::default()
+                .event("lagged")
+                .json_data(serde_json::json!({ "skipped": skipped })),
+        }
+    }
+}
+
+/// Filters of the updates sent to a client, from the query string.
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct StreamFilter {
+    /// Only send the updates of validator nodes.
+    #[serde(default)]
+    validators: bool,
+    /// Comma-separated node ids whose updates are sent.
+    node_id: Option<String>,
+    /// Only send the updates of nodes running this agent version.
+    version: Option<String>,
+}
+
+/// Broadcaster of the node updates to the stream clients.
+pub(crate) struct Updates {
+    sender: broadcast::Sender<NodeUpdate>,
+    closing: watch::Sender<bool>,
+}
+
+impl Default for Updates {
+    fn default() -> Self {
+        Self::new(StreamConfig::default().stream_buffer)
+    }
+}
+
+impl Updates {
+    /// Buffers up to `capacity` updates for the slowest client.
+    pub(crate) fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        let (closing, _) = watch::channel(false);
+        Self { sender, closing }
+    }
+
+    /// Sends the update of `node` to the clients streaming `chain`.
+    pub(crate) fn publish(&self, chain: &ChainId, node: node::Model) {
+        if self.sender.receiver_count() > 0 {
+            // Fails only if every client disconnected in the meantime.
+            let _ = self.sender.send(NodeUpdate::new(chain, node));
+        }
+    }
+
+    /// Returns a receiver notified when the streams are closed.
+    pub(crate) fn closing(&self) -> watch::Receiver<bool> {
+        self.closing.subscribe()
+    }
+
+    /// Ends every stream.
+    pub(crate) fn close(&self) {
+        self.closing.send_replace(true);
+    }
+}
+
+/// Updates of a chain matching the filters of a client.
+pub(crate) struct Subscription {
+    chain: String,
+    validators: bool,
+    node_ids: Option<HashSet<String>>,
+    version: Option<String>,
+    receiver: broadcast::Receiver<NodeUpdate>,
+    closing: watch::Receiver<bool>,
+    metrics: Arc<Metrics>,
+}
+
+impl Subscription {
+    pub(crate) fn new(state: &ServerState, chain: &ChainId, filter: StreamFilter) -> Self {
+        let subscription = Self {
+            chain: chain.to_string(),
+            validators: filter.validators,
+            node_ids: filter
+                .node_id
+                .map(|ids| ids.split(',').map(str::to_string).collect()),
+            version: filter.version,
+            receiver: state.updates.sender.subscribe(),
+            closing: state.updates.closing(),
+            metrics: state.metrics.clone(),
+        };
+        subscription.clients().inc();
+        subscription
+    }
+
+    fn clients(&self) -> Gauge {
+        self.metrics
+            .stream_clients
+            .get_or_create(&Labels::new(self.chain.clone()))
+            .clone()
+    }
+
+    fn matches(&self, update: &NodeUpdate) -> bool {
+        update.chain == self.chain
+            && (!self.validators || update.is_validator)
+            && self
+                .node_ids
+                .as_ref()
+                .is_none_or(|ids| ids.contains(&update.id))
+            && self
+                .version
+                .as_ref()
+                .is_none_or(|version| *version == update.agent_version)
+    }
+
+    /// Returns the next event, or `None` once the server shuts down.
+    pub(crate) async fn next(&mut self) -> Option<StreamEvent> {
+        loop {
+            let received = tokio::select! {
+                biased;
+                _ = self.closing.wait_for(|closing| *closing) => return None,
+                received = self.receiver.recv() => received,
+            };
+            match received {
+                Ok(update) if self.matches(&update) => {
+                    return Some(StreamEvent::Node(Box::new(update)))
+                }
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    self.metrics
+                        .stream_skipped_updates
+                        .get_or_create(&Labels::new(self.chain.clone()))
+                        .inc_by(skipped);
+                    return Some(StreamEvent::Lagged { skipped });
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.clients().dec();
+    }
+}
+
+/// Subscribes to the updates of `chain`, or returns the response to send if unknown.
+fn subscribe(
+    state: &ServerState,
+    chain: &str,
+    filter: StreamFilter,
+) -> Result<Subscription, (StatusCode, String)> {
+    let chain = ChainId::from(chain);
+    if state.database(&chain).is_none() {
+        return Err((StatusCode::NOT_FOUND, format!("unknown chain: {chain}")));
+    }
+    debug!("new {chain} stream client: {filter:?}");
+    Ok(Subscription::new(state, &chain, filter))
+}
+
+pub(crate) fn heartbeat_interval(state: &ServerState) -> Duration {
+    Duration::from_secs(state.stream.stream_heartbeat_interval)
+}
+
+pub(crate) async fn sse_handler(
+    state: State<ServerState>,
+    Path(chain): Path<String>,
+    Query(filter): Query<StreamFilter>,
+) -> Response {
+    let subscription = match subscribe(&state, &chain, filter) {
+        Ok(subscription) => subscription,
+        Err(response) => return response.into_response(),
+    };
+    let events = stream::unfold(subscription, |mut subscription| async move {
+        let event = subscription.next().await?;
+        Some((event.into_sse(), subscription))
+    });
+    let heartbeat = heartbeat_interval(&state);
+    let sse = Sse::new(events);
+    if heartbeat.is_zero() {
+        sse.into_response()
+    } else {
+        sse.keep_alive(KeepAlive::new().interval(heartbeat))
+            .into_response()
+    }
+}
+
+pub(crate) async fn ws_handler(
+    state: State<ServerState>,
+    Path(chain): Path<String>,
+    Query(filter): Query<StreamFilter>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let subscription = match subscribe(&state, &chain, filter) {
+        Ok(subscription) => subscription,
+        Err(response) => return response.into_response(),
+    };
+    let heartbeat = heartbeat_interval(&state);
+    ws.on_upgrade(move |socket| ws_session(socket, subscription, heartbeat))
+}
+
+/// Sends the events as JSON text messages, and pings as heartbeat, until either side closes.
+async fn ws_session(mut socket: WebSocket, mut subscription: Subscription, heartbeat: Duration) {
+    let mut heartbeat =
+        (!heartbeat.is_zero()).then(|| time::interval_at(Instant::now() + heartbeat, heartbeat));
+    loop {
+        let message = tokio::select! {
+            event = subscription.next() => match event {
+                Some(event) => match serde_json::to_string(&event) {
+                    Ok(json) => Message::Text(json),
+                    Err(_) => continue,
+                },
+                None => {
+                    let _ = socket.send(Message::Close(None)).await;
+                    break;
+                }
+            },
+            _ = tick(&mut heartbeat) => Message::Ping(Vec::new()),
+            received = socket.recv() => match received {
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+        };
+        if socket.send(message).await.is_err() {
+            break;
+        }
+    }
+}
+
+pub(crate) async fn tick(interval: &mut Option<Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => future::pending().await,
+    }
+}
@@ -0,0 +1,342 @@
+//! Feed of the nodes in the WebSocket protocol of the Substrate telemetry, so that its frontend can
+//! display the NEAR networks.
+//!
+//! The frontend connects to `/feed`, which lists the chains, then subscribes to one by sending
+//! `subscribe:<genesis hash>`, the chain name standing in for the genesis hash. The active nodes of
+//! the chain are sent, followed by their updates as they are accepted at ingestion, and their
+//! removal once stale. A client falling behind is sent the nodes updated in the meantime, reloaded
+//! from the database. Messages are JSON arrays alternating action codes and payloads, as in version
+//! 32 of the feed protocol. The fields that NEAR nodes don't report are left empty.
+
+use std::{collections::HashMap, future, time::Duration};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::Response,
+};
+use chrono::NaiveDateTime;
+use serde_json::{json, Value};
+use tokio::time::{self, Instant};
+use tracing::{debug, error};
+
+use crate::{
+    nodes::{recent_nodes, ChainId},
+    server::ServerState,
+    stream::{heartbeat_interval, tick, NodeUpdate, StreamEvent, StreamFilter, Subscription},
+    Error,
+};
+
+/// Version of the feed protocol.
+const FEED_VERSION: u32 = 32;
+
+/// Time between two checks of the nodes that became stale.
+const STALE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Chains listed in the feed, with their display name.
+const CHAINS: [(ChainId, &str); 2] = [
+    (ChainId::Mainnet, "NEAR Mainnet"),
+    (ChainId::Testnet, "NEAR Testnet"),
+];
+
+/// Codes of the feed messages.
+#[derive(Clone, Copy, Debug)]
+enum Action {
+    Version = 0,
+    BestBlock = 1,
+    AddedNode = 3,
+    RemovedNode = 4,
+    ImportedBlock = 6,
+    NodeStatsUpdate = 8,
+    Hardware = 9,
+    AddedChain = 11,
+    SubscribedTo = 13,
+    UnsubscribedFrom = 14,
+    Pong = 15,
+}
+
+/// Messages sent together in a single WebSocket message.
+#[derive(Default)]
+struct FeedMessages(Vec<Value>);
+
+impl FeedMessages {
+    fn push(&mut self, action: Action, payload: Value) {
+        self.0.push(Value::from(action as u8));
+        self.0.push(payload);
+    }
+
+    fn into_message(self) -> Option<Message> {
+        (!self.0.is_empty()).then(|| Message::Text(Value::Array(self.0).to_string()))
+    }
+}
+
+/// Node listed in the feed of a client.
+struct FeedNode {
+    /// Identifier of the node in the feed.
+    id: usize,
+    last_seen: NaiveDateTime,
+}
+
+/// State of the feed of a client.
+struct Feed {
+    state: ServerState,
+    chain: Option<ChainId>,
+    subscription: Option<Subscription>,
+    nodes: HashMap<String, FeedNode>,
+    next_id: usize,
+    best_height: i64,
+    /// Number of nodes of the chain last sent to the client.
+    node_count: usize,
+}
+
+impl Feed {
+    fn new(state: ServerState) -> Self {
+        Self {
+            state,
+            chain: None,
+            subscription: None,
+            nodes: HashMap::new(),
+            next_id: 0,
+            best_height: 0,
+            node_count: 0,
+        }
+    }
+
+    fn stale_after(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.state.network_stats.stale_after as i64)
+    }
+
+    /// Lists the chains with their number of active nodes.
+    async fn chains(&self, messages: &mut FeedMessages) -> Result<(), Error> {
+        messages.push(Action::Version, json!(FEED_VERSION));
+        for (chain, name) in CHAINS {
+            let Some(db) = self.state.read_database(&chain) else {
+                continue;
+            };
+            let count = recent_nodes(db, self.stale_after()).await?.len();
+            messages.push(Action::AddedChain, json!([name, chain.to_string(), count]));
+        }
+        Ok(())
+    }
+
+    /// Handles a message of the client.
+    async fn command(&mut self, command: &str, messages: &mut FeedMessages) -> Result<(), Error> {
+        match command.split_once(':') {
+            Some(("subscribe", hash)) => self.subscribe(hash, messages).await,
+            Some(("ping", id)) => {
+                messages.push(Action::Pong, json!(id));
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Switches to the chain of `hash`, sending its active nodes.
+    async fn subscribe(&mut self, hash: &str, messages: &mut FeedMessages) -> Result<(), Error> {
+        let Some(chain) = CHAINS
+            .into_iter()
+            .map(|(chain, _)| chain)
+            .find(|chain| chain.to_string() == hash)
+        else {
+            return Ok(());
+        };
+        let Some(db) = self.state.read_database(&chain).cloned() else {
+            return Ok(());
+        };
+        if let Some(previous) = self.chain.take() {
+            messages.push(Action::UnsubscribedFrom, json!(previous.to_string()));
+        }
+        self.nodes.clear();
+        self.best_height = 0;
+        messages.push(Action::SubscribedTo, json!(hash));
+
+        // Subscribe first not to miss the updates accepted while loading the nodes.
+        self.subscription = Some(Subscription::new(
+            &self.state,
+            &chain,
+            StreamFilter::default(),
+        ));
+        for node in recent_nodes(&db, self.stale_after()).await? {
+            self.update(NodeUpdate::new(&chain, node), messages);
+        }
+        self.node_count = self.nodes.len();
+        self.chain = Some(chain);
+        Ok(())
+    }
+
+    /// Sends again the nodes updated since their last update sent, after the client skipped some
+    /// updates by falling behind.
+    async fn resync(&mut self, messages: &mut FeedMessages) -> Result<(), Error> {
+        let Some(chain) = self.chain.clone() else {
+            return Ok(());
+        };
+        let Some(db) = self.state.read_database(&chain).cloned() else {
+            return Ok(());
+        };
+        for node in recent_nodes(&db, self.stale_after()).await? {
+            let changed = self
+                .nodes
+                .get(&node.id)
+                .is_none_or(|listed| listed.last_seen < node.last_seen);
+            if changed {
+                self.update(NodeUpdate::new(&chain, node), messages);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the update of a node, listing it if new.
+    fn update(&mut self, update: NodeUpdate, messages: &mut FeedMessages) {
+        let timestamp = update.last_seen.and_utc().timestamp_millis();
+        let block = json!([update.height, update.hash, 0, timestamp, null]);
+        let stats = json!([update.peer_count, 0]);
+        let hardware = json!([
+            [update.bandwidth_upload],
+            [update.bandwidth_download],
+            [timestamp]
+        ]);
+        match self.nodes.get_mut(&update.id) {
+            Some(node) => {
+                node.last_seen = update.last_seen;
+                messages.push(Action::ImportedBlock, json!([node.id, block]));
+                messages.push(Action::NodeStatsUpdate, json!([node.id, stats]));
+                messages.push(Action::Hardware, json!([node.id, hardware]));
+            }
+            None => {
+                let id = self.next_id;
+                self.next_id += 1;
+                let version = if update.agent_build.is_empty() {
+                    update.agent_version.clone()
+                } else {
+                    format!("{}-{}", update.agent_version, update.agent_build)
+                };
+                let validator = update.account_id.as_ref().filter(|_| update.is_validator);
+                let details = json!([
+                    update.account_id.as_deref().unwrap_or(&update.id),
+                    update.agent_name,
+                    version,
+                    validator,
+                    update.id,
+                    null,
+                    null,
+                    null,
+                    null,
+                    null,
+                    null
+                ]);
+                messages.push(
+                    Action::AddedNode,
+                    json!([id, details, stats, [[]], hardware, block, null, null]),
+                );
+                self.nodes.insert(
+                    update.id,
+                    FeedNode {
+                        id,
+                        last_seen: update.last_seen,
+                    },
+                );
+            }
+        }
+        if update.height > self.best_height {
+            self.best_height = update.height;
+            messages.push(Action::BestBlock, json!([update.height, timestamp, null]));
+        }
+    }
+
+    /// Removes the nodes that became stale, and sends the new number of nodes of the chain.
+    fn remove_stale(&mut self, messages: &mut FeedMessages) {
+        let Some(chain) = &self.chain else {
+            return;
+        };
+        let cutoff = chrono::offset::Utc::now().naive_utc() - self.stale_after();
+        self.nodes.retain(|_, node| {
+            let active = node.last_seen > cutoff;
+            if !active {
+                messages.push(Action::RemovedNode, json!(node.id));
+            }
+            active
+        });
+        if self.nodes.len() != self.node_count {
+            self.node_count = self.nodes.len();
+            let name = CHAINS
+                .iter()
+                .find(|(listed, _)| listed == chain)
+                .map_or("", |(_, name)| name);
+            messages.push(
+                Action::AddedChain,
+                json!([name, chain.to_string(), self.node_count]),
+            );
+        }
+    }
+}
+
+pub(crate) async fn feed_handler(state: State<ServerState>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| feed_session(socket, state.0))
+}
+
+async fn feed_session(mut socket: WebSocket, state: ServerState) {
+    debug!("new feed client");
+    let heartbeat = heartbeat_interval(&state);
+    let mut heartbeat =
+        (!heartbeat.is_zero()).then(|| time::interval_at(Instant::now() + heartbeat, heartbeat));
+    let mut stale_check = time::interval(STALE_CHECK_INTERVAL);
+    let mut closing = state.updates.closing();
+    let mut feed = Feed::new(state);
+
+    let mut messages = FeedMessages::default();
+    if let Err(err) = feed.chains(&mut messages).await {
+        error!("error listing the feed chains: {err:#?}");
+    }
+    let mut pending = messages.into_message();
+    loop {
+        if let Some(message) = pending.take() {
+            if socket.send(message).await.is_err() {
+                break;
+            }
+        }
+        let mut messages = FeedMessages::default();
+        tokio::select! {
+            _ = async { closing.wait_for(|closing| *closing).await.is_ok() } => {
+                let _ = socket.send(Message::Close(None)).await;
+                break;
+            }
+            event = next_event(&mut feed.subscription) => match event {
+                Some(StreamEvent::Node(update)) => feed.update(*update, &mut messages),
+                Some(StreamEvent::Lagged { skipped }) => {
+                    debug!("feed client skipped {skipped} updates");
+                    if let Err(err) = feed.resync(&mut messages).await {
+                        error!("error reloading the feed nodes: {err:#?}");
+                    }
+                }
+                None => {
+                    let _ = socket.send(Message::Close(None)).await;
+                    break;
+                }
+            },
+            _ = stale_check.tick() => feed.remove_stale(&mut messages),
+            _ = tick(&mut heartbeat) => {
+                pending = Some(Message::Ping(Vec::new()));
+                continue;
+            }
+            received = socket.recv() => match received {
+                Some(Ok(Message::Text(command))) => {
+                    if let Err(err) = feed.command(&command, &mut messages).await {
+                        error!("error handling the feed command {command}: {err:#?}");
+                    }
+                }
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+        pending = messages.into_message();
+    }
+}
+
+async fn next_event(subscription: &mut Option<Subscription>) -> Option<StreamEvent> {
+    match subscription {
+        Some(subscription) => subscription.next().await,
+        None => future::pending().await,
+    }
+}
@@ -0,0 +1,206 @@
+//! Classification of nodes according to how well they follow the chain.
+//!
+//! The network head is estimated as the 95th percentile of the heights reported by online nodes.
+//! Every node is then classified as:
+//! - `offline`: not seen for too long
+//! - `stalled`: still reporting, but stuck at the same height for too long
+//! - `lagging`: too many blocks behind the network head
+//! - `synced`: everything else
+
+use std::{collections::HashMap, fmt};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use tracing::debug;
+
+use crate::{
+    config::SyncStatusConfig,
+    entities::node,
+    metrics::StatusLabels,
+    nodes::{all_nodes, ChainId},
+    server::ServerState,
+    Error,
+};
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SyncStatus {
+    Synced,
+    Lagging,
+    Stalled,
+    Offline,
+}
+
+impl SyncStatus {
+    const ALL: [SyncStatus; 4] = [
+        SyncStatus::Synced,
+        SyncStatus::Lagging,
+        SyncStatus::Stalled,
+        SyncStatus::Offline,
+    ];
+}
+
+impl fmt::Display for SyncStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyncStatus::Synced => write!(f, "synced"),
+            SyncStatus::Lagging => write!(f, "lagging"),
+            SyncStatus::Stalled => write!(f, "stalled"),
+            SyncStatus::Offline => write!(f, "offline"),
+        }
+    }
+}
+
+/// Result of the latest sync status run for a chain.
+#[derive(Serialize, Clone, Debug, Default)]
+pub(crate) struct SyncReport {
+    pub(crate) updated_at: Option<NaiveDateTime>,
+    /// Estimated height of the network head.
+    pub(crate) head: Option<i64>,
+    pub(crate) summary: HashMap<SyncStatus, usize>,
+    pub(crate) nodes: Vec<NodeSyncStatus>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct NodeSyncStatus {
+    pub(crate) id: String,
+    pub(crate) account_id: Option<String>,
+    pub(crate) height: i64,
+    pub(crate) last_seen: NaiveDateTime,
+    /// Time of the first report at the current height.
+    pub(crate) height_since: NaiveDateTime,
+    pub(crate) status: SyncStatus,
+}
+
+pub(crate) async fn sync_status_handler(
+    state: State<ServerState>,
+    Path(chain): Path<String>,
+) -> Response {
+    let chain = ChainId::from(chain.as_str());
+    if state.database(&chain).is_none() {
+        return (StatusCode::NOT_FOUND, format!("unknown chain: {chain}")).into_response();
+    }
+    let report = state
+        .sync_status
+        .read()
+        .expect("sync status lock poisoned")
+        .get(&chain)
+        .cloned()
+        .unwrap_or_default();
+    Json(report).into_response()
+}
+
+/// Classifies the nodes of every chain and updates the sync status gauges.
+pub(crate) async fn update_sync_status(state: &ServerState) -> Result<(), Error> {
+    let now = chrono::offset::Utc::now().naive_utc();
+    for (chain, db) in state.read_databases() {
+        let nodes = all_nodes(db).await?;
+        let previous = state
+            .sync_status
+            .read()
+            .expect("sync status lock poisoned")
+            .get(&chain)
+            .map(|report| {
+                report
+                    .nodes
+                    .iter()
+                    .map(|node| (node.id.clone(), (node.height, node.height_since)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let report = classify(&nodes, &previous, &state.sync_status_config, now);
+        debug!(
+            "{chain} head: {:?}, sync summary: {:?}",
+            report.head, report.summary
+        );
+
+        for status in SyncStatus::ALL {
+            let labels = StatusLabels::new(chain.to_string(), status.to_string());
+            let count = report.summary.get(&status).copied().unwrap_or_default();
+            state
+                .metrics
+                .nodes_by_sync_status
+                .get_or_create(&labels)
+                .set(count as i64);
+        }
+
+        state
+            .sync_status
+            .write()
+            .expect("sync status lock poisoned")
+            .insert(chain, report);
+    }
+    Ok(())
+}
+
+/// Classifies `nodes`, given the height and the height start time known from the previous run.
+fn classify(
+    nodes: &[node::Model],
+    previous: &HashMap<String, (i64, NaiveDateTime)>,
+    config: &SyncStatusConfig,
+    now: NaiveDateTime,
+) -> SyncReport {
+    let offline_after = chrono::Duration::seconds(config.offline_after as i64);
+    let stall_after = chrono::Duration::seconds(config.stall_after as i64);
+
+    let is_offline = |node: &node::Model| now - node.last_seen > offline_after;
+    let head = network_head(
+        nodes
+            .iter()
+            .filter(|node| !is_offline(node))
+            .map(|node| node.last_height)
+            .collect(),
+    );
+
+    let mut summary = HashMap::new();
+    let nodes: Vec<NodeSyncStatus> = nodes
+        .iter()
+        .map(|node| {
+            let height_since = match previous.get(&node.id) {
+                Some((height, since)) if *height == node.last_height => *since,
+                _ => node.last_seen,
+            };
+            let status = if is_offline(node) {
+                SyncStatus::Offline
+            } else if node.last_seen - height_since > stall_after {
+                SyncStatus::Stalled
+            } else if head.is_some_and(|head| head - node.last_height > config.lag_threshold) {
+                SyncStatus::Lagging
+            } else {
+                SyncStatus::Synced
+            };
+            *summary.entry(status).or_default() += 1;
+            NodeSyncStatus {
+                id: node.id.clone(),
+                account_id: node.account_id.clone(),
+                height: node.last_height,
+                last_seen: node.last_seen,
+                height_since,
+                status,
+            }
+        })
+        .collect();
+
+    SyncReport {
+        updated_at: Some(now),
+        head,
+        summary,
+        nodes,
+    }
+}
+
+/// Returns the 95th percentile of `heights`.
+pub(crate) fn network_head(mut heights: Vec<i64>) -> Option<i64> {
+    if heights.is_empty() {
+        return None;
+    }
+    heights.sort_unstable();
+    let index = (heights.len() * 95).div_ceil(100) - 1;
+    Some(heights[index])
+}
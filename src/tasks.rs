@@ -0,0 +1,122 @@
+//! Background tasks periodically executed by the server.
+
+use std::{fmt, time::Duration};
+
+use sea_orm::{EnumIter, Iterable};
+use tokio::{
+    task::JoinHandle,
+    time::{self, MissedTickBehavior},
+};
+use tracing::{debug, error};
+
+use crate::{
+    admin::refresh_bans, alerts::evaluate_alerts, forks::detect_forks,
+    history::maintain_partitions, network_stats::update_network_stats, replicas::check_replicas,
+    retention::prune, rollups::update_rollups, server::ServerState,
+    sync_status::update_sync_status, validators::check_validators, Error,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter)]
+pub enum Task {
+    /// Looks for nodes reporting different hashes at the same height.
+    ForkDetection,
+    /// Classifies nodes as synced, lagging, stalled or offline.
+    SyncStatus,
+    /// Alerts about validators that are offline or behind.
+    ValidatorCheck,
+    /// Evaluates the alert rules.
+    AlertEvaluation,
+    /// Prunes the nodes that stopped reporting.
+    Prune,
+    /// Updates the hourly and daily rollups.
+    Rollup,
+    /// Creates the upcoming history partitions and drops the expired ones.
+    HistoryMaintenance,
+    /// Checks the health of the read-only replicas.
+    ReplicaCheck,
+    /// Updates the network gauges: active nodes, validators, heights and stale nodes.
+    NetworkStats,
+    /// Reloads the bans from the databases.
+    BanRefresh,
+}
+
+impl fmt::Display for Task {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Task::ForkDetection => write!(f, "fork detection"),
+            Task::SyncStatus => write!(f, "sync status"),
+            Task::ValidatorCheck => write!(f, "validator check"),
+            Task::AlertEvaluation => write!(f, "alert evaluation"),
+            Task::Prune => write!(f, "prune"),
+            Task::Rollup => write!(f, "rollup"),
+            Task::HistoryMaintenance => write!(f, "history maintenance"),
+            Task::ReplicaCheck => write!(f, "replica check"),
+            Task::NetworkStats => write!(f, "network stats"),
+            Task::BanRefresh => write!(f, "ban refresh"),
+        }
+    }
+}
+
+impl Task {
+    /// Returns the time between two executions of the task.
+    fn period(&self, state: &ServerState) -> Duration {
+        match self {
+            Task::ForkDetection => {
+                Duration::from_secs(state.fork_detection.fork_detection_interval)
+            }
+            Task::SyncStatus => Duration::from_secs(state.sync_status_config.sync_status_interval),
+            Task::ValidatorCheck => {
+                Duration::from_secs(state.validators_config.validator_check_interval)
+            }
+            Task::AlertEvaluation => Duration::from_secs(state.alerts_config.alert_interval),
+            Task::Prune => Duration::from_secs(state.retention.prune_interval),
+            Task::Rollup => Duration::from_secs(state.rollups.rollup_interval),
+            Task::HistoryMaintenance => {
+                Duration::from_secs(state.history.history_maintenance_interval)
+            }
+            Task::ReplicaCheck => Duration::from_secs(state.replicas_config.replica_check_interval),
+            Task::NetworkStats => Duration::from_secs(state.network_stats.network_stats_interval),
+            Task::BanRefresh => Duration::from_secs(state.admin.ban_refresh_interval),
+        }
+    }
+}
+
+/// Executes a single run of `task`.
+pub(crate) async fn run_task(state: &ServerState, task: Task) -> Result<(), Error> {
+    debug!("running {task} task");
+    match task {
+        Task::ForkDetection => detect_forks(state).await,
+        Task::SyncStatus => update_sync_status(state).await,
+        Task::ValidatorCheck => check_validators(state).await,
+        Task::AlertEvaluation => evaluate_alerts(state).await,
+        Task::Prune => prune(state).await,
+        Task::Rollup => update_rollups(state).await,
+        Task::HistoryMaintenance => maintain_partitions(state).await,
+        Task::ReplicaCheck => check_replicas(state).await,
+        Task::NetworkStats => update_network_stats(state).await,
+        Task::BanRefresh => refresh_bans(state).await,
+    }
+}
+
+/// Spawns every background task, each in its own tokio task.
+pub(crate) fn spawn_tasks(state: &ServerState) -> Vec<JoinHandle<()>> {
+    Task::iter()
+        .map(|task| tokio::spawn(run_periodically(state.clone(), task)))
+        .collect()
+}
+
+async fn run_periodically(state: ServerState, task: Task) {
+    let period = task.period(&state);
+    if period.is_zero() {
+        debug!("{task} task is disabled");
+        return;
+    }
+    let mut interval = time::interval(period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        if let Err(err) = run_task(&state, task).await {
+            error!("{task} task failed: {err:#?}");
+        }
+    }
+}
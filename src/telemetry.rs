@@ -45,5 +45,6 @@ pub struct TelemetryInfo {
     pub system: TelemetrySystemInfo,
     pub chain: TelemetryChainInfo,
     // Extra telemetry information that will be ignored by the explorer frontend.
+    #[allow(dead_code)]
     pub extra_info: String,
 }
@@ -0,0 +1,225 @@
+//! TLS termination, with optional verification of the client certificates.
+//!
+//! The certificate files are checked periodically and reloaded when modified, so that renewed
+//! certificates are served without a restart. When a client CA is configured, clients may present
+//! a certificate signed by it, and the read APIs are only served to those that did.
+
+use std::{
+    fs::{self, File},
+    future::Future,
+    io::{self, BufReader},
+    net::SocketAddr,
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router,
+};
+use axum_server::{
+    accept::Accept,
+    tls_rustls::{RustlsAcceptor, RustlsConfig},
+    Handle,
+};
+use futures::future::BoxFuture;
+use rustls::{
+    crypto::{ring, CryptoProvider},
+    pki_types::CertificateDer,
+    server::WebPkiClientVerifier,
+    RootCertStore, ServerConfig,
+};
+use tokio::{
+    net::TcpStream,
+    time::{self, MissedTickBehavior},
+};
+use tokio_rustls::server::TlsStream;
+use tower_http::add_extension::AddExtension;
+use tracing::{error, info};
+
+use crate::{config::TlsConfig, server::ServerState, Error};
+
+/// Whether the client of a connection presented a certificate signed by the client CA.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ClientCertificate {
+    verified: bool,
+}
+
+/// Acceptor terminating TLS and recording in the requests whether the client was verified.
+#[derive(Clone)]
+struct TlsAcceptor {
+    inner: RustlsAcceptor,
+}
+
+impl<S: Send + 'static> Accept<TcpStream, S> for TlsAcceptor {
+    type Stream = TlsStream<TcpStream>;
+    type Service = AddExtension<S, ClientCertificate>;
+    type Future = BoxFuture<'static, io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, stream: TcpStream, service: S) -> Self::Future {
+        let accept = self.inner.accept(stream, service);
+        Box::pin(async move {
+            let (stream, service) = accept.await?;
+            // The verifier rejects the handshake of invalid certificates.
+            let verified = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .is_some_and(|certs| !certs.is_empty());
+            let service = AddExtension::new(service, ClientCertificate { verified });
+            Ok((stream, service))
+        })
+    }
+}
+
+/// Serves `app` over TLS until `shutdown` completes.
+pub(crate) async fn serve_tls(
+    address: SocketAddr,
+    app: Router,
+    config: &TlsConfig,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<(), Error> {
+    let rustls_config = RustlsConfig::from_config(server_config(config)?);
+    let watcher = tokio::spawn(watch_certificates(config.clone(), rustls_config.clone()));
+
+    let handle = Handle::new();
+    tokio::spawn({
+        let handle = handle.clone();
+        async move {
+            shutdown.await;
+            handle.graceful_shutdown(None);
+        }
+    });
+    let result = axum_server::bind(address)
+        .acceptor(TlsAcceptor {
+            inner: RustlsAcceptor::new(rustls_config),
+        })
+        .handle(handle)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .await;
+    watcher.abort();
+    Ok(result?)
+}
+
+/// Returns whether TLS is enabled, checking that the certificate and the key are set together.
+pub(crate) fn is_enabled(config: &TlsConfig) -> Result<bool, Error> {
+    match (&config.tls_cert, &config.tls_key, &config.tls_client_ca) {
+        (Some(_), Some(_), _) => Ok(true),
+        (None, None, None) => Ok(false),
+        (None, None, Some(_)) => Err(Error::ConfigError(
+            "--tls-client-ca requires --tls-cert and --tls-key".to_string(),
+        )),
+        _ => Err(Error::ConfigError(
+            "--tls-cert and --tls-key must be set together".to_string(),
+        )),
+    }
+}
+
+/// Loads the certificates and key of the server, and the client CAs if any.
+fn server_config(config: &TlsConfig) -> Result<Arc<ServerConfig>, Error> {
+    let (Some(cert_path), Some(key_path)) = (&config.tls_cert, &config.tls_key) else {
+        return Err(Error::TlsError("missing certificate or key".to_string()));
+    };
+    let certs = read_certs(cert_path)?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(File::open(key_path)?))?
+        .ok_or_else(|| Error::TlsError(format!("no private key in {}", key_path.display())))?;
+
+    let provider = Arc::new(ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|err| Error::TlsError(err.to_string()))?;
+    let builder = match &config.tls_client_ca {
+        Some(ca_path) => builder.with_client_cert_verifier(client_verifier(ca_path, provider)?),
+        None => builder.with_no_client_auth(),
+    };
+    let mut server_config = builder
+        .with_single_cert(certs, key)
+        .map_err(|err| Error::TlsError(err.to_string()))?;
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Arc::new(server_config))
+}
+
+/// Verifies the certificates of the clients presenting one, which are optional.
+fn client_verifier(
+    ca_path: &Path,
+    provider: Arc<CryptoProvider>,
+) -> Result<Arc<dyn rustls::server::danger::ClientCertVerifier>, Error> {
+    let mut roots = RootCertStore::empty();
+    for cert in read_certs(ca_path)? {
+        roots
+            .add(cert)
+            .map_err(|err| Error::TlsError(format!("{}: {err}", ca_path.display())))?;
+    }
+    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+        .allow_unauthenticated()
+        .build()
+        .map_err(|err| Error::TlsError(err.to_string()))
+}
+
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, Error> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(path)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(Error::TlsError(format!(
+            "no certificate in {}",
+            path.display()
+        )));
+    }
+    Ok(certs)
+}
+
+/// Reloads the certificates when their files are modified.
+async fn watch_certificates(config: TlsConfig, rustls_config: RustlsConfig) {
+    let period = Duration::from_secs(config.tls_reload_interval);
+    if period.is_zero() {
+        return;
+    }
+    let mut modified = modification_times(&config);
+    let mut interval = time::interval(period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let current = modification_times(&config);
+        if current == modified {
+            continue;
+        }
+        // On failure, such as a certificate written before its key, retry on the next check.
+        match server_config(&config) {
+            Ok(server_config) => {
+                rustls_config.reload_from_config(server_config);
+                modified = current;
+                info!("reloaded the TLS certificates");
+            }
+            Err(err) => error!("failed to reload the TLS certificates: {err}"),
+        }
+    }
+}
+
+fn modification_times(config: &TlsConfig) -> Vec<Option<SystemTime>> {
+    [&config.tls_cert, &config.tls_key, &config.tls_client_ca]
+        .into_iter()
+        .flatten()
+        .map(|path| fs::metadata(path).and_then(|meta| meta.modified()).ok())
+        .collect()
+}
+
+/// Rejects the requests of unverified clients when client certificates are required.
+pub(crate) async fn client_certificate_middleware(
+    state: State<ServerState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let verified = request
+        .extensions()
+        .get::<ClientCertificate>()
+        .is_some_and(|cert| cert.verified);
+    if state.tls.tls_client_ca.is_some() && !verified {
+        return (StatusCode::FORBIDDEN, "client certificate required").into_response();
+    }
+    next.run(request).await
+}
@@ -0,0 +1,224 @@
+//! Validator-focused view of the nodes and alerts for validators that stop keeping up.
+
+use std::collections::{BTreeMap, HashMap};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::NaiveDateTime;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use serde::Serialize;
+use tracing::{error, info, warn};
+
+use crate::{
+    entities::node,
+    nodes::{all_nodes, ChainId},
+    request_id::{error_body, RequestId},
+    server::ServerState,
+    sync_status::network_head,
+    Error,
+};
+
+/// Why a validator is considered unhealthy.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ValidatorAlert {
+    /// None of the validator nodes reported recently.
+    Offline,
+    /// The validator nodes are too far behind the network head.
+    Behind,
+}
+
+#[derive(Serialize, Debug)]
+struct Validator {
+    account_id: Option<String>,
+    last_seen: NaiveDateTime,
+    alert: Option<ValidatorAlert>,
+    nodes: Vec<ValidatorNode>,
+}
+
+#[derive(Serialize, Debug)]
+struct ValidatorNode {
+    id: String,
+    agent_name: String,
+    agent_version: String,
+    agent_build: String,
+    protocol_version: Option<i32>,
+    height: i64,
+    last_seen: NaiveDateTime,
+}
+
+/// Notification sent to the webhook when a validator becomes unhealthy.
+#[derive(Serialize, Debug)]
+struct ValidatorNotification<'a> {
+    chain: String,
+    account_id: &'a str,
+    alert: ValidatorAlert,
+    last_seen: NaiveDateTime,
+    height: i64,
+    head: Option<i64>,
+}
+
+pub(crate) async fn validators_handler(
+    state: State<ServerState>,
+    request_id: Option<Extension<RequestId>>,
+    Path(chain): Path<String>,
+) -> Response {
+    let chain = ChainId::from(chain.as_str());
+    let Some(db) = state.read_database(&chain) else {
+        return (StatusCode::NOT_FOUND, format!("unknown chain: {chain}")).into_response();
+    };
+    let nodes = match validator_nodes(db).await {
+        Ok(nodes) => nodes,
+        Err(err) => {
+            error!("error loading {chain} validators: {err:#?}");
+            let request_id = request_id.map(|Extension(RequestId(id))| id);
+            let message = format!("error loading validators: {err}");
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                error_body(&message, request_id.as_deref()),
+            )
+                .into_response();
+        }
+    };
+
+    let alerts = state
+        .validator_alerts
+        .read()
+        .expect("validator alerts lock poisoned")
+        .get(&chain)
+        .cloned()
+        .unwrap_or_default();
+    let validators: Vec<Validator> = group_by_account(nodes)
+        .into_iter()
+        .map(|(account_id, nodes)| Validator {
+            alert: account_id
+                .as_ref()
+                .and_then(|account_id| alerts.get(account_id).copied()),
+            account_id,
+            last_seen: nodes
+                .iter()
+                .map(|node| node.last_seen)
+                .max()
+                .unwrap_or_default(),
+            nodes: nodes
+                .into_iter()
+                .map(|node| ValidatorNode {
+                    id: node.id,
+                    agent_name: node.agent_name,
+                    agent_version: node.agent_version,
+                    agent_build: node.agent_build,
+                    protocol_version: node.protocol_version,
+                    height: node.last_height,
+                    last_seen: node.last_seen,
+                })
+                .collect(),
+        })
+        .collect();
+    Json(validators).into_response()
+}
+
+/// Checks the health of every validator and notifies the webhook about those becoming unhealthy.
+pub(crate) async fn check_validators(state: &ServerState) -> Result<(), Error> {
+    let config = &state.validators_config;
+    let now = chrono::offset::Utc::now().naive_utc();
+    let offline_after = chrono::Duration::seconds(config.validator_offline_after as i64);
+
+    for (chain, db) in state.read_databases() {
+        let nodes = all_nodes(db).await?;
+        let head = network_head(
+            nodes
+                .iter()
+                .filter(|node| now - node.last_seen <= offline_after)
+                .map(|node| node.last_height)
+                .collect(),
+        );
+
+        let validators = group_by_account(nodes.into_iter().filter(|node| node.is_validator));
+        let mut alerts = HashMap::new();
+        for (account_id, nodes) in validators {
+            let Some(account_id) = account_id else {
+                continue;
+            };
+            let last_seen = nodes.iter().map(|node| node.last_seen).max();
+            let height = nodes.iter().map(|node| node.last_height).max();
+            let (Some(last_seen), Some(height)) = (last_seen, height) else {
+                continue;
+            };
+            let alert = if now - last_seen > offline_after {
+                ValidatorAlert::Offline
+            } else if head.is_some_and(|head| head - height > config.validator_lag_threshold) {
+                ValidatorAlert::Behind
+            } else {
+                continue;
+            };
+
+            let previous = state
+                .validator_alerts
+                .read()
+                .expect("validator alerts lock poisoned")
+                .get(&chain)
+                .and_then(|alerts| alerts.get(&account_id).copied());
+            if previous != Some(alert) {
+                warn!("{chain} validator {account_id} is unhealthy: {alert:?}");
+                let notification = ValidatorNotification {
+                    chain: chain.to_string(),
+                    account_id: &account_id,
+                    alert,
+                    last_seen,
+                    height,
+                    head,
+                };
+                notify(state, &notification).await;
+            }
+            alerts.insert(account_id, alert);
+        }
+
+        state
+            .validator_alerts
+            .write()
+            .expect("validator alerts lock poisoned")
+            .insert(chain, alerts);
+    }
+    Ok(())
+}
+
+async fn notify(state: &ServerState, notification: &ValidatorNotification<'_>) {
+    let Some(webhook) = &state.validators_webhook else {
+        return;
+    };
+    match webhook.send(notification).await {
+        Ok(()) => info!(
+            "notified webhook about validator {}",
+            notification.account_id
+        ),
+        Err(err) => error!(
+            "failed to notify webhook about validator {}: {err:#?}",
+            notification.account_id
+        ),
+    }
+}
+
+async fn validator_nodes(db: &DatabaseConnection) -> Result<Vec<node::Model>, Error> {
+    let nodes = node::Entity::find()
+        .filter(node::Column::IsValidator.eq(true))
+        .all(db)
+        .await?;
+    Ok(nodes)
+}
+
+fn group_by_account(
+    nodes: impl IntoIterator<Item = node::Model>,
+) -> BTreeMap<Option<String>, Vec<node::Model>> {
+    let mut validators: BTreeMap<Option<String>, Vec<node::Model>> = BTreeMap::new();
+    for node in nodes {
+        validators
+            .entry(node.account_id.clone())
+            .or_default()
+            .push(node);
+    }
+    validators
+}
@@ -0,0 +1,63 @@
+//! Delivery of alerts to HTTP webhooks.
+
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::time;
+use tracing::{debug, warn};
+
+use crate::Error;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Number of times a failed notification is retried.
+const WEBHOOK_RETRIES: u32 = 3;
+/// Delay before the first retry, doubled at every subsequent attempt.
+const WEBHOOK_BACKOFF: Duration = Duration::from_millis(500);
+
+/// An HTTP endpoint receiving JSON notifications through POST requests.
+#[derive(Debug, Clone)]
+pub(crate) struct Webhook {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl Webhook {
+    pub(crate) fn new(url: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+
+    /// Posts `payload` to the webhook, retrying with exponential backoff until the endpoint
+    /// answers with a success.
+    pub(crate) async fn send<T: Serialize>(&self, payload: &T) -> Result<(), Error> {
+        let mut backoff = WEBHOOK_BACKOFF;
+        for _ in 0..WEBHOOK_RETRIES {
+            match self.try_send(payload).await {
+                Ok(()) => return Ok(()),
+                Err(err) => {
+                    warn!(
+                        "webhook {} failed, retrying in {backoff:?}: {err}",
+                        self.url
+                    );
+                    time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+            }
+        }
+        self.try_send(payload).await
+    }
+
+    async fn try_send<T: Serialize>(&self, payload: &T) -> Result<(), Error> {
+        debug!("sending notification to webhook {}", self.url);
+        self.client
+            .post(&self.url)
+            .timeout(WEBHOOK_TIMEOUT)
+            .json(payload)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
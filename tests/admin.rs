@@ -0,0 +1,273 @@
+mod common;
+
+use std::{collections::BTreeMap, fs, net::SocketAddr};
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::ConnectInfo,
+    http::{Request, StatusCode},
+    Router,
+};
+use chrono::Utc;
+use common::{get, post, MOCK_SOCKET_ADDRESS};
+use sea_orm::{DatabaseBackend, DatabaseConnection, DbErr, MockDatabase, MockExecResult, Value};
+use serde_json::json;
+use telemetry_service::{
+    config::{AdminConfig, ApiKey, AuthConfig, Scope},
+    Server, Task,
+};
+use test_log::test;
+use tower::ServiceExt;
+
+const NODE_ID: &str = "ed25519:6Hat46Wuxrk1czrhENjJrS3GuYUXYDmMgFtGLFyWGWNq";
+
+fn exec_result(rows_affected: u64) -> MockExecResult {
+    MockExecResult {
+        last_insert_id: 0,
+        rows_affected,
+    }
+}
+
+fn server(db_mainnet: DatabaseConnection) -> Server {
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<BTreeMap<String, Value>>::new()])
+        .into_connection();
+    Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap()
+}
+
+/// Sends a request from `peer` with an optional JSON body and returns the response status and body.
+async fn send(
+    app: Router,
+    method: &str,
+    uri: &str,
+    body: Option<serde_json::Value>,
+    peer: &str,
+) -> (StatusCode, String) {
+    let peer: SocketAddr = peer.parse().unwrap();
+    let mut request = Request::builder().method(method).uri(uri);
+    let body = match body {
+        Some(body) => {
+            request = request.header("content-type", "application/json");
+            Body::from(body.to_string())
+        }
+        None => Body::empty(),
+    };
+    let mut request = request.body(body).unwrap();
+    request.extensions_mut().insert(ConnectInfo(peer));
+    let response = app.oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+// Banned nodes should be rejected at ingestion and counted.
+#[test(tokio::test)]
+async fn ban_node() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results([exec_result(1), exec_result(1)])
+        .into_connection();
+    let server = server(db_mainnet);
+    let telemetry = fs::read_to_string("res/example_telemetry_payload_v2_mainnet").unwrap();
+
+    let ban = json!({"node_id": NODE_ID, "reason": "spam"});
+    let (status, body) = send(
+        server.app(),
+        "POST",
+        "/admin/bans/mainnet",
+        Some(ban),
+        "10.0.0.1:1234",
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert!(body.contains(r#""kind":"node""#), "{body}");
+    let (status, body) = post(server.app(), "/nodes", telemetry.clone()).await;
+    assert_eq!((status, body.as_str()), (StatusCode::FORBIDDEN, "banned"));
+
+    let (status, _) = send(
+        server.app(),
+        "DELETE",
+        &format!("/admin/bans/mainnet/node/{NODE_ID}"),
+        None,
+        "10.0.0.1:1234",
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = post(server.app(), "/nodes", telemetry).await;
+    assert_ne!(status, StatusCode::FORBIDDEN);
+
+    let (_, metrics) = get(server.app(), "/metrics").await;
+    let line =
+        r#"telemetry_service_failed_requests_total{network="mainnet",reason="banned_node"} 1"#;
+    assert!(metrics.contains(line), "missing {line}");
+
+    let ban = json!({"node_id": NODE_ID, "ip": "10.0.0.1"});
+    let (status, _) = send(
+        server.app(),
+        "POST",
+        "/admin/bans/mainnet",
+        Some(ban),
+        "10.0.0.1:1234",
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+// The bans stored in the databases should be loaded, and IP addresses read from the configured
+// header when set.
+#[test(tokio::test)]
+async fn ban_ip() {
+    let ban = BTreeMap::from([
+        ("kind", Value::from("ip")),
+        ("value", Value::from("10.0.0.2")),
+        ("reason", Value::from(None::<String>)),
+        ("created_at", Value::from(Utc::now().naive_utc())),
+    ]);
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![ban.clone()], vec![ban]])
+        .into_connection();
+    let server = server(db_mainnet);
+    server.run_task(Task::BanRefresh).await.unwrap();
+    let telemetry = json!({"chain": {"chain_id": "mainnet"}});
+
+    let (status, _) = send(
+        server.app(),
+        "POST",
+        "/nodes/mainnet",
+        Some(telemetry.clone()),
+        "10.0.0.2:1234",
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = send(
+        server.app(),
+        "POST",
+        "/nodes/mainnet",
+        Some(telemetry.clone()),
+        "10.0.0.3:1234",
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = send(
+        server.app(),
+        "GET",
+        "/admin/bans/mainnet",
+        None,
+        "10.0.0.1:1",
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#""value":"10.0.0.2""#), "{body}");
+
+    // Behind a proxy, the address it appended is used, not the ones sent by the client.
+    let server = server.with_admin(AdminConfig {
+        client_ip_header: Some("x-forwarded-for".to_string()),
+        ..Default::default()
+    });
+    let forwarded = |forwarded_for: &str| {
+        Request::builder()
+            .method("POST")
+            .uri("/nodes/mainnet")
+            .header("x-forwarded-for", forwarded_for)
+            .body(Body::from(telemetry.to_string()))
+            .unwrap()
+    };
+    let response = server
+        .app()
+        .oneshot(forwarded("10.0.0.3, 10.0.0.2"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = server
+        .app()
+        .oneshot(forwarded("10.0.0.2, 10.0.0.3"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+// Nodes should be deleted, and annotations stored and listed.
+#[test(tokio::test)]
+async fn nodes_and_annotations() {
+    let annotation = BTreeMap::from([
+        ("node_id", Value::from(NODE_ID)),
+        ("note", Value::from("operated by us")),
+        ("tags", Value::from(json!(["rpc", "eu"]))),
+        ("updated_at", Value::from(Utc::now().naive_utc())),
+    ]);
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results([exec_result(1), exec_result(0), exec_result(1)])
+        .append_query_results([vec![annotation]])
+        .into_connection();
+    let server = server(db_mainnet);
+    let peer = "10.0.0.1:1234";
+
+    let uri = format!("/admin/nodes/mainnet/{NODE_ID}");
+    let (status, _) = send(server.app(), "DELETE", &uri, None, peer).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = send(server.app(), "DELETE", &uri, None, peer).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = send(server.app(), "DELETE", "/admin/nodes/other/id", None, peer).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let uri = format!("/admin/annotations/mainnet/{NODE_ID}");
+    let annotation = json!({"note": "operated by us", "tags": ["rpc", "eu"]});
+    let (status, body) = send(server.app(), "PUT", &uri, Some(annotation), peer).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#""tags":["rpc","eu"]"#), "{body}");
+    let (status, body) = send(
+        server.app(),
+        "GET",
+        "/admin/annotations/mainnet",
+        None,
+        peer,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#""note":"operated by us""#), "{body}");
+
+    let (db_mainnet, _) = server.into_db_connections();
+    let log = db_mainnet.unwrap().into_transaction_log();
+    assert!(format!("{:?}", log[0]).contains(r#"DELETE FROM \"node\""#));
+    assert!(format!("{:?}", log[2]).contains(r#"INSERT INTO \"node_annotation\""#));
+}
+
+// The details of a database error should only be logged.
+#[test(tokio::test)]
+async fn database_error() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_errors([DbErr::Custom("admin error".to_string())])
+        .into_connection();
+    let server = server(db_mainnet);
+
+    let uri = format!("/admin/nodes/mainnet/{NODE_ID}");
+    let (status, body) = send(server.app(), "DELETE", &uri, None, "10.0.0.1:1234").await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(body.starts_with("error deleting node: "), "{body}");
+    assert!(body.contains("\nrequest id: "), "{body}");
+    assert!(!body.contains("DBError"), "{body}");
+}
+
+// The admin APIs should require the admin scope once authentication is enabled.
+#[test(tokio::test)]
+async fn admin_scope() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let app = server(db_mainnet)
+        .with_auth(AuthConfig {
+            api_keys: vec![ApiKey {
+                key: "reader".to_string(),
+                scopes: vec![Scope::Read],
+            }],
+            ..Default::default()
+        })
+        .app();
+    let request = Request::builder()
+        .uri("/admin/bans/mainnet")
+        .header("x-api-key", "reader")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let (status, _) = get(app, "/admin/bans/mainnet").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
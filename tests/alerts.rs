@@ -0,0 +1,230 @@
+mod common;
+
+use std::{env, fs, path::PathBuf, time::Duration};
+
+use axum::http::StatusCode;
+use chrono::Utc;
+use common::{flaky_webhook, get, mock_node, webhook, MOCK_SOCKET_ADDRESS};
+use sea_orm::{DatabaseBackend, MockDatabase};
+use serde_json::{json, Value};
+use telemetry_service::{config::AlertsConfig, entities::node, Server, Task};
+use test_log::test;
+use tokio::{sync::mpsc::UnboundedReceiver, time};
+
+const RULES: &str = r#"
+[[rule]]
+name = "low-peers"
+severity = "warning"
+when = ["peer_count < 5"]
+
+[[rule]]
+name = "high-cpu"
+when = ["cpu_usage > 90"]
+for = "10m"
+
+[[rule]]
+name = "outdated-validators"
+chain = "mainnet"
+validators_only = true
+when = ["protocol_version < 68"]
+"#;
+
+fn rules_file(name: &str, content: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("telemetry-service-{name}.toml"));
+    fs::write(&path, content).unwrap();
+    path
+}
+
+/// Returns the next notification, sent apart from the evaluation.
+async fn next_notification(notifications: &mut UnboundedReceiver<Value>) -> Value {
+    time::timeout(Duration::from_secs(5), notifications.recv())
+        .await
+        .expect("no notification")
+        .unwrap()
+}
+
+fn mock_peers_node(id: &str, peer_count: i64, cpu_usage: f32) -> node::Model {
+    node::Model {
+        peer_count,
+        cpu_usage,
+        ..mock_node(id, 0, "", Utc::now().naive_utc())
+    }
+}
+
+// Alerts should be notified once when firing and once when resolved.
+#[test(tokio::test)]
+async fn firing_and_resolved() {
+    let validator = node::Model {
+        is_validator: true,
+        protocol_version: Some(67),
+        ..mock_peers_node("b", 10, 0.0)
+    };
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([
+            vec![mock_peers_node("a", 2, 95.0), validator.clone()],
+            vec![mock_peers_node("a", 10, 95.0), validator],
+        ])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([
+            vec![mock_peers_node("c", 1, 0.0)],
+            vec![mock_peers_node("c", 1, 0.0)],
+        ])
+        .into_connection();
+    let (url, mut notifications) = webhook().await;
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_alerts(AlertsConfig {
+            alert_rules: Some(rules_file("firing-and-resolved", RULES)),
+            alert_webhook_url: Some(url),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let summary = |notification: Value| {
+        (
+            notification["rule"].as_str().unwrap().to_string(),
+            notification["node_id"].as_str().unwrap().to_string(),
+            notification["status"].as_str().unwrap().to_string(),
+        )
+    };
+
+    server.run_task(Task::AlertEvaluation).await.unwrap();
+    let mut fired = vec![];
+    for _ in 0..3 {
+        let notification = next_notification(&mut notifications).await;
+        if notification["rule"] == json!("low-peers") {
+            assert_eq!(notification["severity"], json!("warning"));
+        }
+        fired.push(summary(notification));
+    }
+    fired.sort();
+    assert_eq!(
+        fired,
+        [
+            ("low-peers".into(), "a".into(), "firing".into()),
+            ("low-peers".into(), "c".into(), "firing".into()),
+            ("outdated-validators".into(), "b".into(), "firing".into()),
+        ]
+    );
+
+    server.run_task(Task::AlertEvaluation).await.unwrap();
+    let notification = next_notification(&mut notifications).await;
+    assert_eq!(
+        summary(notification),
+        ("low-peers".into(), "a".into(), "resolved".into())
+    );
+    assert!(notifications.try_recv().is_err());
+
+    let (status, body) = get(server.app(), "/alerts").await;
+    assert_eq!(status, StatusCode::OK);
+    let alerts: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(alerts["rules"].as_array().unwrap().len(), 3);
+    assert_eq!(alerts["rules"][1]["for"], json!("10m"));
+    assert_eq!(alerts["rules"][1]["when"], json!(["cpu_usage > 90"]));
+    let active: Vec<(&str, &str, &str)> = alerts["alerts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|alert| {
+            (
+                alert["rule"].as_str().unwrap(),
+                alert["node_id"].as_str().unwrap(),
+                alert["status"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        active,
+        [
+            ("high-cpu", "a", "pending"),
+            ("low-peers", "c", "firing"),
+            ("outdated-validators", "b", "firing"),
+        ]
+    );
+
+    let (_, metrics) = get(server.app(), "/metrics").await;
+    assert!(metrics.contains("telemetry_service_alerts_firing{rule=\"low-peers\"} 1"));
+    assert!(metrics.contains("telemetry_service_alerts_firing{rule=\"high-cpu\"} 0"));
+}
+
+// Notifications failing after their retries should be sent again at the next evaluations.
+#[test(tokio::test)]
+async fn undelivered_notifications() {
+    let nodes = vec![mock_peers_node("a", 2, 0.0)];
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results(vec![nodes; 20])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results(vec![Vec::<node::Model>::new(); 20])
+        .into_connection();
+    // Fails the first attempt and its 3 retries.
+    let (url, mut notifications) = flaky_webhook(4).await;
+    let rules =
+        format!("[[rule]]\nname = \"low-peers\"\nwhen = [\"peer_count < 5\"]\nwebhook = \"{url}\"");
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_alerts(AlertsConfig {
+            alert_rules: Some(rules_file("undelivered", &rules)),
+            ..Default::default()
+        })
+        .unwrap();
+
+    server.run_task(Task::AlertEvaluation).await.unwrap();
+    let notification = loop {
+        time::sleep(Duration::from_millis(500)).await;
+        if let Ok(notification) = notifications.try_recv() {
+            break notification;
+        }
+        server.run_task(Task::AlertEvaluation).await.unwrap();
+    };
+    assert_eq!(notification["node_id"], json!("a"));
+    assert_eq!(notification["status"], json!("firing"));
+    time::sleep(Duration::from_millis(500)).await;
+    assert!(notifications.try_recv().is_err());
+}
+
+// Rules with invalid conditions should be rejected when loaded.
+#[test(tokio::test)]
+async fn invalid_rules() {
+    for (name, rules) in [
+        (
+            "unknown-field",
+            "[[rule]]\nname = \"x\"\nwhen = [\"foo > 1\"]",
+        ),
+        (
+            "bad-operator",
+            "[[rule]]\nname = \"x\"\nwhen = [\"peer_count ~ 1\"]",
+        ),
+        (
+            "bad-value",
+            "[[rule]]\nname = \"x\"\nwhen = [\"peer_count < abc\"]",
+        ),
+        (
+            "text-order",
+            "[[rule]]\nname = \"x\"\nwhen = [\"status < abc\"]",
+        ),
+        (
+            "bad-duration",
+            "[[rule]]\nname = \"x\"\nwhen = []\nfor = \"soon\"",
+        ),
+        (
+            "bad-chain",
+            "[[rule]]\nname = \"x\"\nwhen = []\nchain = \"foo\"",
+        ),
+        (
+            "duplicated",
+            "[[rule]]\nname = \"x\"\nwhen = []\n[[rule]]\nname = \"x\"\nwhen = []",
+        ),
+    ] {
+        let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+        let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+        let result = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+            .unwrap()
+            .with_alerts(AlertsConfig {
+                alert_rules: Some(rules_file(name, rules)),
+                ..Default::default()
+            });
+        assert!(result.is_err(), "{name} rules should be rejected");
+    }
+}
@@ -0,0 +1,141 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use common::MOCK_SOCKET_ADDRESS;
+use hmac::{Hmac, Mac};
+use sea_orm::{DatabaseBackend, MockDatabase};
+use sha2::Sha256;
+use telemetry_service::{
+    config::{ApiKey, AuthConfig, Scope},
+    Server,
+};
+use test_log::test;
+use tower::ServiceExt;
+
+const SECRET: &str = "token-secret";
+
+fn app(config: AuthConfig) -> Router {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_auth(config)
+        .app()
+}
+
+fn config() -> AuthConfig {
+    AuthConfig {
+        api_keys: vec![
+            ApiKey {
+                key: "reader".to_string(),
+                scopes: vec![Scope::Read],
+            },
+            ApiKey {
+                key: "admin".to_string(),
+                scopes: vec![Scope::Admin],
+            },
+        ],
+        auth_token_secret: Some(SECRET.to_string()),
+        ..Default::default()
+    }
+}
+
+/// Returns a JWT signed with `secret`.
+fn token(secret: &str, claims: &str) -> String {
+    let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256","typ":"JWT"}"#);
+    let signed = format!("{header}.{}", URL_SAFE_NO_PAD.encode(claims));
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(signed.as_bytes());
+    let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+    format!("{signed}.{signature}")
+}
+
+/// Sends a request with an optional header and returns the response status.
+async fn send(app: Router, method: &str, uri: &str, header: Option<(&str, &str)>) -> StatusCode {
+    let mut request = Request::builder().method(method).uri(uri);
+    if let Some((name, value)) = header {
+        request = request.header(name, value);
+    }
+    let response = app
+        .oneshot(request.body(Body::from("{")).unwrap())
+        .await
+        .unwrap();
+    response.status()
+}
+
+// Every API should stay public until credentials are configured.
+#[test(tokio::test)]
+async fn auth_disabled() {
+    let app = app(AuthConfig::default());
+    assert_eq!(send(app, "GET", "/alerts", None).await, StatusCode::OK);
+}
+
+// The read APIs should require an API key granting the read scope.
+#[test(tokio::test)]
+async fn api_keys() {
+    let app = app(config());
+    let cases = [
+        (None, StatusCode::UNAUTHORIZED),
+        (Some(("x-api-key", "reader")), StatusCode::OK),
+        (Some(("authorization", "Bearer reader")), StatusCode::OK),
+        (Some(("x-api-key", "admin")), StatusCode::OK),
+        (Some(("x-api-key", "wrong")), StatusCode::UNAUTHORIZED),
+    ];
+    for (header, status) in cases {
+        assert_eq!(
+            send(app.clone(), "GET", "/alerts", header).await,
+            status,
+            "{header:?}"
+        );
+    }
+    // Ingestion stays anonymous.
+    let status = send(app, "POST", "/nodes", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+// Signed tokens should grant the scopes of their claims until they expire.
+#[test(tokio::test)]
+async fn tokens() {
+    let app = app(config());
+    let read = format!("Bearer {}", token(SECRET, r#"{"scope":"ingest read"}"#));
+    let ingest = format!("Bearer {}", token(SECRET, r#"{"scope":"ingest"}"#));
+    let expired = format!(
+        "Bearer {}",
+        token(SECRET, r#"{"scope":"read","exp":1700000000}"#)
+    );
+    let forged = format!("Bearer {}", token("other", r#"{"scope":"read"}"#));
+    let cases = [
+        (read, StatusCode::OK),
+        (ingest, StatusCode::FORBIDDEN),
+        (expired, StatusCode::UNAUTHORIZED),
+        (forged, StatusCode::UNAUTHORIZED),
+    ];
+    for (authorization, status) in cases {
+        let header = Some(("authorization", authorization.as_str()));
+        assert_eq!(
+            send(app.clone(), "GET", "/alerts", header).await,
+            status,
+            "{authorization}"
+        );
+    }
+}
+
+// Ingestion should require credentials once removed from the anonymous scopes.
+#[test(tokio::test)]
+async fn authenticated_ingestion() {
+    let app = app(AuthConfig {
+        anonymous_scopes: Vec::new(),
+        ..config()
+    });
+    let status = send(app.clone(), "POST", "/nodes", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let status = send(app.clone(), "POST", "/nodes", Some(("x-api-key", "reader"))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let status = send(app, "POST", "/nodes", Some(("x-api-key", "admin"))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
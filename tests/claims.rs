@@ -0,0 +1,167 @@
+mod common;
+
+use std::{collections::BTreeMap, fs, net::SocketAddr};
+
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{Request, StatusCode},
+};
+use chrono::{Duration, Utc};
+use common::{get, post, upserted_node, MOCK_SOCKET_ADDRESS};
+use sea_orm::{DatabaseBackend, DbErr, MockDatabase, MockExecResult, Value};
+use serde_json::json;
+use telemetry_service::{config::ClaimsConfig, Server};
+use test_log::test;
+use tower::ServiceExt;
+
+fn claims_config() -> ClaimsConfig {
+    ClaimsConfig {
+        track_claims: true,
+        ..Default::default()
+    }
+}
+
+fn claim(
+    account_id: &str,
+    node_id: &str,
+    ip: &str,
+    first_seen: i64,
+    last_seen: i64,
+) -> BTreeMap<&'static str, Value> {
+    let now = Utc::now().naive_utc();
+    BTreeMap::from([
+        ("account_id", Value::from(account_id)),
+        ("node_id", Value::from(node_id)),
+        ("ip", Value::from(ip)),
+        ("first_seen", Value::from(now - Duration::hours(first_seen))),
+        ("last_seen", Value::from(now - Duration::hours(last_seen))),
+    ])
+}
+
+// The account claimed by a report should be recorded with the node id and the sender address.
+#[test(tokio::test)]
+async fn record_claims() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![upserted_node(true)]])
+        .append_exec_results([MockExecResult {
+            last_insert_id: 0,
+            rows_affected: 1,
+        }])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_claims(claims_config());
+
+    let json = fs::read_to_string("res/example_telemetry_payload_v2_mainnet").unwrap();
+    let mut request = Request::builder()
+        .method("POST")
+        .uri("/nodes")
+        .body(Body::from(json))
+        .unwrap();
+    let peer: SocketAddr = "10.0.0.1:1234".parse().unwrap();
+    request.extensions_mut().insert(ConnectInfo(peer));
+    let response = server.app().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let (db_mainnet, _) = server.into_db_connections();
+    let log = db_mainnet.unwrap().into_transaction_log();
+    assert_eq!(log.len(), 2);
+    let claim = format!("{:?}", log[1]);
+    assert!(claim.contains(r#"INSERT INTO \"node_claim\""#), "{claim}");
+    assert!(
+        claim.contains("test.near") && claim.contains("10.0.0.1"),
+        "{claim}"
+    );
+}
+
+// Accounts reporting from a new node id or from several node ids should be listed.
+#[test(tokio::test)]
+async fn suspicious_claims() {
+    let claims = vec![
+        // Reporting from a second node id since an hour ago.
+        claim("spoofed.near", "node-1", "10.0.0.1", 240, 0),
+        claim("spoofed.near", "node-2", "10.0.0.1", 1, 0),
+        // Moved to a new node id, the old one stopped reporting.
+        claim("moved.near", "node-3", "10.0.0.1", 240, 72),
+        claim("moved.near", "node-4", "10.0.0.1", 1, 0),
+        // Moved to a new node id a while ago, which recently changed address.
+        claim("stable.near", "node-5", "10.0.0.1", 240, 72),
+        claim("stable.near", "node-6", "10.0.0.1", 120, 2),
+        claim("stable.near", "node-6", "10.0.0.2", 1, 0),
+        claim("single.near", "node-7", "", 240, 0),
+    ];
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([claims])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_claims(claims_config());
+
+    let (status, body) = get(server.app(), "/claims/mainnet").await;
+    assert_eq!(status, StatusCode::OK);
+    let accounts: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let reasons: Vec<_> = accounts
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|account| (account["account_id"].clone(), account["reasons"].clone()))
+        .collect();
+    assert_eq!(
+        reasons,
+        [
+            (json!("moved.near"), json!(["new_node"])),
+            (json!("spoofed.near"), json!(["new_node", "multiple_nodes"])),
+        ]
+    );
+    assert_eq!(accounts[1]["claims"][1]["node_id"], json!("node-2"));
+    assert_eq!(accounts[1]["claims"][1]["ip"], json!("10.0.0.1"));
+
+    let (status, _) = get(server.app(), "/claims/other").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Only the accounts reported within the window are loaded.
+    let (db_mainnet, _) = server.into_db_connections();
+    let log = format!("{:?}", db_mainnet.unwrap().into_transaction_log());
+    assert!(
+        log.contains(r#"WHERE \"node_claim\".\"account_id\" IN (SELECT DISTINCT"#),
+        "{log}"
+    );
+}
+
+// A failure to record the claim shouldn't reject the report.
+#[test(tokio::test)]
+async fn claim_error() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![upserted_node(true)]])
+        .append_exec_errors([DbErr::Custom("claim error".to_string())])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_claims(claims_config());
+
+    let json = fs::read_to_string("res/example_telemetry_payload_v2_mainnet").unwrap();
+    let (status, _) = post(server.app(), "/nodes", json).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+}
+
+// The details of a failure to load the claims should only be logged.
+#[test(tokio::test)]
+async fn claims_query_error() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_errors([DbErr::Custom("claims error".to_string())])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_claims(claims_config());
+
+    let (status, body) = get(server.app(), "/claims/mainnet").await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(body.starts_with("error loading claims: "), "{body}");
+    assert!(body.contains("\nrequest id: "), "{body}");
+    assert!(!body.contains("DBError"), "{body}");
+}
@@ -0,0 +1,119 @@
+// Helpers are shared by several test crates, each using only a subset of them.
+#![allow(dead_code)]
+
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    routing, Json, Router,
+};
+use sea_orm::{prelude::DateTime, MockExecResult};
+use serde_json::Value;
+use telemetry_service::entities::node;
+use tokio::{net::TcpListener, sync::mpsc};
+use tower::ServiceExt;
+
+pub const MOCK_SOCKET_ADDRESS: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8080);
+
+pub fn mock_node(id: &str, height: i64, hash: &str, last_seen: DateTime) -> node::Model {
+    node::Model {
+        id: id.to_string(),
+        account_id: None,
+        last_seen,
+        last_height: height,
+        last_hash: hash.to_string(),
+        agent_name: String::new(),
+        agent_version: String::new(),
+        agent_build: String::new(),
+        peer_count: 0,
+        is_validator: false,
+        status: String::new(),
+        bandwidth_download: 0,
+        bandwidth_upload: 0,
+        cpu_usage: 0.0,
+        memory_usage: 0,
+        boot_time_seconds: 0,
+        block_production_tracking_delay: 0.0,
+        min_block_production_delay: 0.0,
+        max_block_production_delay: 0.0,
+        max_block_wait_delay: 0.0,
+        chain_id: None,
+        protocol_version: None,
+    }
+}
+
+/// Returns the row returned by the upsert of a node, inserted for the first time or updated.
+pub fn upserted_node(inserted: bool) -> BTreeMap<&'static str, sea_orm::Value> {
+    BTreeMap::from([("inserted", sea_orm::Value::from(inserted))])
+}
+
+/// Returns the result of a statement affecting a single row.
+pub fn exec_result() -> MockExecResult {
+    MockExecResult {
+        last_insert_id: 0,
+        rows_affected: 1,
+    }
+}
+
+/// Sends a GET request to `app` and returns the response status and body.
+pub async fn get(app: Router, uri: &str) -> (StatusCode, String) {
+    let response = app
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+/// Sends a POST request with `body` to `app` and returns the response status and body.
+pub async fn post(app: Router, uri: &str, body: String) -> (StatusCode, String) {
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri(uri)
+                .method("POST")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+/// Starts a local webhook, returning its URL and a receiver of the JSON payloads posted to it.
+pub async fn webhook() -> (String, mpsc::UnboundedReceiver<Value>) {
+    flaky_webhook(0).await
+}
+
+/// Starts a local webhook failing its first `failures` requests, returning its URL and a receiver
+/// of the JSON payloads accepted.
+pub async fn flaky_webhook(failures: usize) -> (String, mpsc::UnboundedReceiver<Value>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let requests = Arc::new(AtomicUsize::new(0));
+    let app = Router::new().route(
+        "/",
+        routing::post(move |Json(payload): Json<Value>| async move {
+            if requests.fetch_add(1, Ordering::Relaxed) < failures {
+                return StatusCode::SERVICE_UNAVAILABLE;
+            }
+            sender.send(payload).unwrap();
+            StatusCode::OK
+        }),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, receiver)
+}
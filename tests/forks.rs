@@ -0,0 +1,75 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{get, mock_node, MOCK_SOCKET_ADDRESS};
+use sea_orm::{prelude::DateTime, DatabaseBackend, MockDatabase};
+use serde_json::{json, Value};
+use telemetry_service::{entities::node, Server, Task};
+use test_log::test;
+
+// Nodes reporting different hashes at the same height should be reported as a divergence.
+#[test(tokio::test)]
+async fn divergence_detected() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![
+            mock_node("a", 100, "hash1", DateTime::default()),
+            mock_node("b", 100, "hash1", DateTime::default()),
+            mock_node("c", 100, "hash2", DateTime::default()),
+            mock_node("d", 101, "hash3", DateTime::default()),
+        ]])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<node::Model>::new()])
+        .into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    server.run_task(Task::ForkDetection).await.unwrap();
+
+    let (status, body) = get(server.app(), "/forks/mainnet").await;
+    assert_eq!(status, StatusCode::OK);
+    let report: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        report["divergences"],
+        json!([{
+            "height": 100,
+            "branches": [
+                { "hash": "hash1", "nodes": ["a", "b"] },
+                { "hash": "hash2", "nodes": ["c"] },
+            ],
+        }])
+    );
+
+    let (status, body) = get(server.app(), "/forks/testnet").await;
+    assert_eq!(status, StatusCode::OK);
+    let report: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(report["divergences"], json!([]));
+
+    let (_, metrics) = get(server.app(), "/metrics").await;
+    assert!(metrics.contains("telemetry_service_divergent_heights{network=\"mainnet\"} 1"));
+    assert!(metrics.contains("telemetry_service_diverging_nodes{network=\"mainnet\"} 1"));
+    assert!(metrics.contains("telemetry_service_divergent_heights{network=\"testnet\"} 0"));
+}
+
+// Before the first run of the detector, no divergence is reported.
+#[test(tokio::test)]
+async fn no_report_yet() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    let (status, body) = get(server.app(), "/forks/mainnet").await;
+    assert_eq!(status, StatusCode::OK);
+    let report: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(report, json!({ "updated_at": null, "divergences": [] }));
+}
+
+// Chains without a database are not analyzed.
+#[test(tokio::test)]
+async fn unknown_chain() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    let (status, _) = get(server.app(), "/forks/localnet").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -1,15 +1,17 @@
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+mod common;
+
+use std::collections::BTreeMap;
 
 use axum::{body::Body, extract::Request, http::StatusCode};
-use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult};
-use telemetry_service::Server;
+use common::{exec_result, get, MOCK_SOCKET_ADDRESS};
+use sea_orm::{DatabaseBackend, DbErr, MockDatabase, MockExecResult, Value};
+use sea_orm_migration::MigratorTrait;
+use serde_json::json;
+use telemetry_service::{config::HealthConfig, migrator::Migrator, Server};
 use tower::ServiceExt;
 
 use test_log::test;
 
-const MOCK_SOCKET_ADDRESS: SocketAddr =
-    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8080);
-
 #[test(tokio::test)]
 async fn health_ok() {
     let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
@@ -90,3 +92,86 @@ async fn health_ko_testnet() {
         .unwrap();
     assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
 }
+
+#[test(tokio::test)]
+async fn liveness() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    let (status, _) = get(server.app(), "/livez").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[test(tokio::test)]
+async fn readiness_required_chains() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results([exec_result(), exec_result()])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_errors([DbErr::Custom("down".to_string())])
+        .into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_health(HealthConfig {
+            ready_chains: vec!["mainnet".to_string()],
+        });
+
+    // Testnet is not required.
+    let (status, _) = get(server.app(), "/readyz").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[test(tokio::test)]
+async fn readiness_ko() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results([exec_result()])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_errors([DbErr::Custom("down".to_string())])
+        .into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    let (status, body) = get(server.app(), "/readyz").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body, "testnet database unavailable\n");
+}
+
+#[test(tokio::test)]
+async fn health_verbose() {
+    let migrations = |versions: &[String]| -> Vec<BTreeMap<String, Value>> {
+        versions
+            .iter()
+            .map(|version| BTreeMap::from([("version".to_string(), Value::from(version.clone()))]))
+            .collect()
+    };
+    let names: Vec<String> = Migrator::migrations()
+        .iter()
+        .map(|migration| migration.name().to_string())
+        .collect();
+    let (applied, pending) = names.split_at(4);
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results([exec_result()])
+        .append_query_results([[BTreeMap::from([("exists".to_string(), Value::from(true))])]])
+        .append_query_results([migrations(applied)])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_errors([DbErr::Custom("down".to_string())])
+        .into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    let (status, body) = get(server.app(), "/healthz?verbose").await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    let report: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(report["healthy"], json!(false));
+    assert_eq!(report["ingestion_in_flight"], json!(0));
+    let mainnet = &report["databases"][0];
+    assert_eq!(mainnet["chain"], json!("mainnet"));
+    assert_eq!(mainnet["role"], json!("primary"));
+    assert_eq!(mainnet["healthy"], json!(true));
+    assert_eq!(mainnet["pending_migrations"], json!(pending));
+    let testnet = &report["databases"][1];
+    assert_eq!(testnet["healthy"], json!(false));
+    assert_eq!(testnet["error"], json!("Custom Error: down"));
+    assert_eq!(testnet["pending_migrations"], json!(null));
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use std::{collections::BTreeMap, fs};
+
+use axum::http::StatusCode;
+use chrono::Utc;
+use common::{exec_result, get, post, upserted_node, MOCK_SOCKET_ADDRESS};
+use sea_orm::{DatabaseBackend, DbErr, MockDatabase, Value};
+use telemetry_service::{config::HistoryConfig, Server, Task};
+use test_log::test;
+
+fn partitions(names: &[&str]) -> Vec<BTreeMap<String, Value>> {
+    names
+        .iter()
+        .map(|name| BTreeMap::from([("relname".to_string(), Value::from(*name))]))
+        .collect()
+}
+
+fn history_config() -> HistoryConfig {
+    HistoryConfig {
+        store_history: true,
+        history_retention_days: 7,
+        history_partitions_ahead: 2,
+        ..Default::default()
+    }
+}
+
+// Every report should be appended to the history when enabled.
+#[test(tokio::test)]
+async fn store_history() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![upserted_node(true)]])
+        .append_exec_results([exec_result()])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_history(history_config());
+
+    let json = fs::read_to_string("res/example_telemetry_payload_v2_mainnet").unwrap();
+    let (status, _) = post(server.app(), "/nodes", json).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (db_mainnet, _) = server.into_db_connections();
+    let log = db_mainnet.unwrap().into_transaction_log();
+    assert_eq!(log.len(), 2);
+    assert!(format!("{:?}", log[0]).contains(r#"INSERT INTO \"node\""#));
+    assert!(format!("{:?}", log[1]).contains(r#"INSERT INTO \"node_history\""#));
+}
+
+// A failure to append the history, such as a missing partition, shouldn't reject the report.
+#[test(tokio::test)]
+async fn history_error() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![upserted_node(true)]])
+        .append_exec_errors([DbErr::Custom("no partition of relation".to_string())])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_history(history_config());
+
+    let json = fs::read_to_string("res/example_telemetry_payload_v2_mainnet").unwrap();
+    let (status, _) = post(server.app(), "/nodes", json).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (_, body) = get(server.app(), "/metrics").await;
+    assert!(body.contains(r#"successful_requests_total{network="mainnet"} 1"#));
+}
+
+// Upcoming partitions should be created and expired ones dropped.
+#[test(tokio::test)]
+async fn maintain_partitions() {
+    let today = Utc::now().date_naive();
+    let partition = |days_ago| {
+        format!(
+            "node_history_{}",
+            (today - chrono::Duration::days(days_ago)).format("%Y%m%d")
+        )
+    };
+    let (expired, kept, current, next) = (partition(8), partition(7), partition(0), partition(-1));
+    let default = "node_history_default";
+    let existing = [default, expired.as_str(), kept.as_str(), current.as_str()];
+    let remaining = [default, kept.as_str(), current.as_str(), next.as_str()];
+
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results(vec![exec_result(); 3])
+        .append_query_results([
+            partitions(&existing),
+            partitions(&existing),
+            partitions(&remaining),
+        ])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results(vec![exec_result(); 2])
+        .append_query_results([partitions(&[]), partitions(&[]), partitions(&[])])
+        .into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_history(history_config());
+
+    server.run_task(Task::HistoryMaintenance).await.unwrap();
+
+    let (_, body) = get(server.app(), "/metrics").await;
+    assert!(body.contains(r#"history_partitions{network="mainnet"} 4"#));
+    assert!(body.contains(r#"history_partitions{network="testnet"} 0"#));
+
+    let (db_mainnet, _) = server.into_db_connections();
+    let log = format!("{:?}", db_mainnet.unwrap().into_transaction_log());
+    for name in [&current, &next] {
+        assert!(log.contains(&format!(
+            r#"CREATE TABLE IF NOT EXISTS \"{name}\" PARTITION OF \"node_history\""#
+        )));
+    }
+    // The reports of the new partition's day are moved out of the default partition.
+    assert!(log.contains(&format!(
+        r#"DELETE FROM \"{default}\" WHERE \"reported_at\" >= '{}"#,
+        (today + chrono::Duration::days(1)).format("%Y-%m-%d")
+    )));
+    assert_eq!(log.matches("DELETE FROM").count(), 1);
+    assert!(log.contains(&format!(r#"DROP TABLE IF EXISTS \"{expired}\""#)));
+    assert!(!log.contains(&format!(r#"DROP TABLE IF EXISTS \"{kept}\""#)));
+}
+
+// Partitions should be left untouched when the history is disabled.
+#[test(tokio::test)]
+async fn history_disabled() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    server.run_task(Task::HistoryMaintenance).await.unwrap();
+
+    let (db_mainnet, db_testnet) = server.into_db_connections();
+    assert!(db_mainnet.unwrap().into_transaction_log().is_empty());
+    assert!(db_testnet.unwrap().into_transaction_log().is_empty());
+}
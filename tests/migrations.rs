@@ -0,0 +1,142 @@
+use clap::Parser;
+use std::collections::BTreeMap;
+
+use sea_orm::{DatabaseBackend, MockDatabase, Value};
+use sea_orm_migration::seaql_migrations;
+use telemetry_service::{
+    config::{Command, MigrateAction},
+    database::check_schema,
+    Config, Error,
+};
+use test_log::test;
+
+const MIGRATIONS: [&str; 7] = [
+    "m_20240508_000001_create_tables",
+    "m_20240603_000002_node_v2",
+    "m_20261018_000003_node_archive",
+    "m_20261018_000004_rollups",
+    "m_20261018_000005_node_history",
+    "m_20261018_000006_admin",
+    "m_20261018_000007_node_claims",
+];
+
+fn parse(args: &[&str]) -> Result<Config, clap::Error> {
+    Config::try_parse_from(
+        ["telemetry-service", "postgresql://localhost"]
+            .iter()
+            .chain(args),
+    )
+}
+
+// The migrate subcommands should be parsed with their arguments.
+#[test]
+fn migrate_command() {
+    let config = parse(&["migrate", "status"]).unwrap();
+    assert!(matches!(
+        config.command,
+        Some(Command::Migrate {
+            chain: None,
+            action: MigrateAction::Status
+        })
+    ));
+
+    let config = parse(&["migrate", "--chain", "testnet", "down", "2"]).unwrap();
+    assert!(matches!(
+        config.command,
+        Some(Command::Migrate {
+            chain: Some(chain),
+            action: MigrateAction::Down { n: 2 }
+        }) if chain == "testnet"
+    ));
+
+    let config = parse(&["migrate", "down"]).unwrap();
+    assert!(matches!(
+        config.command,
+        Some(Command::Migrate {
+            action: MigrateAction::Down { n: 1 },
+            ..
+        })
+    ));
+
+    let config = parse(&["migrate", "--chain", "testnet", "fresh"]).unwrap();
+    assert!(matches!(
+        config.command,
+        Some(Command::Migrate {
+            chain: Some(chain),
+            action: MigrateAction::Fresh { all: false }
+        }) if chain == "testnet"
+    ));
+
+    let config = parse(&["migrate", "fresh", "--all"]).unwrap();
+    assert!(matches!(
+        config.command,
+        Some(Command::Migrate {
+            chain: None,
+            action: MigrateAction::Fresh { all: true }
+        })
+    ));
+
+    assert!(parse(&["migrate", "--chain", "localnet", "up"]).is_err());
+    assert!(parse(&["migrate", "sideways"]).is_err());
+}
+
+// Automatic migrations can be disabled, but not while generating the schema.
+#[test]
+fn no_auto_migrate() {
+    assert!(!parse(&[]).unwrap().no_auto_migrate);
+    assert!(parse(&["--no-auto-migrate"]).unwrap().no_auto_migrate);
+    assert!(parse(&["--no-auto-migrate", "--generate-schema"]).is_err());
+}
+
+fn applied(versions: &[&str]) -> Vec<seaql_migrations::Model> {
+    versions
+        .iter()
+        .map(|version| seaql_migrations::Model {
+            version: version.to_string(),
+            applied_at: 0,
+        })
+        .collect()
+}
+
+fn mock_database(versions: &[&str]) -> sea_orm::DatabaseConnection {
+    MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([[BTreeMap::from([("exists", Value::from(true))])]])
+        .append_query_results([applied(versions)])
+        .into_connection()
+}
+
+// An up to date schema should pass the check.
+#[test(tokio::test)]
+async fn schema_up_to_date() {
+    let db = mock_database(&MIGRATIONS);
+    check_schema(&db, "mainnet").await.unwrap();
+}
+
+// Pending migrations should be reported.
+#[test(tokio::test)]
+async fn pending_migrations() {
+    let db = mock_database(&MIGRATIONS[..3]);
+    match check_schema(&db, "mainnet").await {
+        Err(Error::PendingMigrations(db_name, pending)) => {
+            assert_eq!(db_name, "mainnet");
+            assert_eq!(pending, MIGRATIONS[3..]);
+        }
+        result => panic!("unexpected result: {result:?}"),
+    }
+}
+
+// The check should only read the database, and report every migration as pending without the
+// migration table.
+#[test(tokio::test)]
+async fn check_schema_read_only() {
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([[BTreeMap::from([("exists", Value::from(false))])]])
+        .into_connection();
+    match check_schema(&db, "mainnet").await {
+        Err(Error::PendingMigrations(_, pending)) => assert_eq!(pending, MIGRATIONS),
+        result => panic!("unexpected result: {result:?}"),
+    }
+    let log = db.into_transaction_log();
+    assert_eq!(log.len(), 1);
+    assert!(format!("{:?}", log[0]).contains("SELECT EXISTS"));
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use chrono::{Duration, Utc};
+use common::{get, mock_node, MOCK_SOCKET_ADDRESS};
+use sea_orm::{DatabaseBackend, MockDatabase};
+use telemetry_service::{entities::node, Server, Task};
+use test_log::test;
+
+// The network gauges should summarise the active nodes and count the stale ones.
+#[test(tokio::test)]
+async fn network_stats() {
+    let now = Utc::now().naive_utc();
+    let node = |id: &str, height, protocol_version| node::Model {
+        agent_version: "1.40.0".to_string(),
+        status: "NoSync".to_string(),
+        protocol_version,
+        ..mock_node(id, height, "", now)
+    };
+    let nodes = vec![
+        node("a", 100, Some(68)),
+        node("b", 104, Some(68)),
+        node::Model {
+            is_validator: true,
+            ..node("c", 110, None)
+        },
+        node("d", 90, Some(68)),
+        // Stale node, ignored by the active gauges.
+        mock_node("e", 200, "", now - Duration::hours(1)),
+    ];
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([nodes])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<node::Model>::new()])
+        .into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    server.run_task(Task::NetworkStats).await.unwrap();
+
+    let (_, metrics) = get(server.app(), "/metrics").await;
+    assert!(metrics.contains(
+        r#"telemetry_service_active_nodes{network="mainnet",agent_version="1.40.0",protocol_version="68",status="NoSync"} 3"#
+    ));
+    assert!(metrics.contains(
+        r#"telemetry_service_active_nodes{network="mainnet",agent_version="1.40.0",protocol_version="unknown",status="NoSync"} 1"#
+    ));
+    assert!(metrics.contains(r#"telemetry_service_active_validators{network="mainnet"} 1"#));
+    assert!(metrics.contains(r#"telemetry_service_max_height{network="mainnet"} 110"#));
+    assert!(metrics.contains(r#"telemetry_service_median_height{network="mainnet"} 102"#));
+    assert!(metrics.contains(r#"telemetry_service_stale_nodes{network="mainnet"} 1"#));
+    // Heights are not reported without active nodes.
+    assert!(metrics.contains(r#"telemetry_service_active_validators{network="testnet"} 0"#));
+    assert!(!metrics.contains(r#"telemetry_service_max_height{network="testnet"}"#));
+}
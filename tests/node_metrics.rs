@@ -0,0 +1,110 @@
+mod common;
+
+use axum::http::StatusCode;
+use chrono::Utc;
+use common::{get, mock_node, MOCK_SOCKET_ADDRESS};
+use sea_orm::{DatabaseBackend, DbErr, MockDatabase};
+use telemetry_service::{config::NodeMetricsConfig, entities::node, Server};
+use test_log::test;
+
+fn config() -> NodeMetricsConfig {
+    NodeMetricsConfig {
+        node_metrics: true,
+        ..Default::default()
+    }
+}
+
+// The endpoint should not be served unless enabled.
+#[test(tokio::test)]
+async fn node_metrics_disabled() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    let (status, _) = get(server.app(), "/metrics/nodes").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+// The metrics of every node should be exported with the node labels.
+#[test(tokio::test)]
+async fn node_metrics() {
+    let now = Utc::now().naive_utc();
+    let node = node::Model {
+        account_id: Some("node0.pool.near".to_string()),
+        agent_version: "1.40.0".to_string(),
+        peer_count: 30,
+        cpu_usage: 12.5,
+        max_block_wait_delay: 1.5,
+        ..mock_node("a", 100, "", now)
+    };
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![node]])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![mock_node("b", 50, "", now)]])
+        .into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_node_metrics(config());
+
+    let (status, body) = get(server.app(), "/metrics/nodes").await;
+    assert_eq!(status, StatusCode::OK);
+    let labels =
+        r#"network="mainnet",node_id="a",account_id="node0.pool.near",agent_version="1.40.0""#;
+    assert!(body.contains(&format!("telemetry_service_node_height{{{labels}}} 100")));
+    assert!(body.contains(&format!("telemetry_service_node_peer_count{{{labels}}} 30")));
+    assert!(body.contains(&format!(
+        "telemetry_service_node_cpu_usage{{{labels}}} 12.5"
+    )));
+    assert!(body.contains(&format!(
+        "telemetry_service_node_max_block_wait_delay{{{labels}}} 1.5"
+    )));
+    assert!(body.contains(
+        r#"telemetry_service_node_height{network="testnet",node_id="b",account_id="",agent_version=""} 50"#
+    ));
+}
+
+// The query should be restricted to the allowlist and capped.
+#[test(tokio::test)]
+async fn node_metrics_allowlist() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<node::Model>::new()])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<node::Model>::new()])
+        .into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_node_metrics(NodeMetricsConfig {
+            node_metrics_max_nodes: 10,
+            node_metrics_allowlist: vec!["a".to_string(), "node0.pool.near".to_string()],
+            ..config()
+        });
+
+    let (status, _) = get(server.app(), "/metrics/nodes").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (db_mainnet, _) = server.into_db_connections();
+    let log = format!("{:?}", db_mainnet.unwrap().into_transaction_log());
+    assert!(log.contains(r#"\"node\".\"id\" IN ($2, $3) OR \"node\".\"account_id\" IN ($4, $5)"#));
+    assert!(log.contains(r#"ORDER BY \"node\".\"last_seen\" DESC LIMIT $6"#));
+    assert!(log.contains("BigUnsigned(Some(10))"));
+}
+
+// The details of a failure to load the nodes should only be logged.
+#[test(tokio::test)]
+async fn node_metrics_query_error() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_errors([DbErr::Custom("nodes error".to_string())])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_node_metrics(config());
+
+    let (status, body) = get(server.app(), "/metrics/nodes").await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(body.starts_with("error loading nodes: "), "{body}");
+    assert!(body.contains("\nrequest id: "), "{body}");
+    assert!(!body.contains("DBError"), "{body}");
+}
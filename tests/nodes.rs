@@ -1,3 +1,5 @@
+mod common;
+
 use std::{
     fs,
     net::{IpAddr, Ipv4Addr, SocketAddr},
@@ -7,8 +9,9 @@ use axum::{
     body::Body,
     http::{Request, StatusCode},
 };
-use sea_orm::{prelude::DateTime, DatabaseBackend, MockDatabase, MockExecResult};
-use telemetry_service::{entities::node, Server};
+use common::{get, post, upserted_node};
+use sea_orm::{DatabaseBackend, MockDatabase};
+use telemetry_service::Server;
 use tower::ServiceExt;
 
 use test_log::test;
@@ -117,51 +120,14 @@ async fn wrong_input_error_impl(uri: &str) {
     assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 }
 
-fn mock_node_and_exec() -> (node::Model, MockExecResult) {
-    // Values for mock node and exec don't really matter.
-    let mock_node = node::Model {
-        id: String::new(),
-        account_id: None,
-        last_seen: DateTime::default(),
-        last_height: 0,
-        last_hash: String::new(),
-        agent_name: String::new(),
-        agent_version: String::new(),
-        agent_build: String::new(),
-        peer_count: 0,
-        is_validator: false,
-        status: String::new(),
-        bandwidth_download: 0,
-        bandwidth_upload: 0,
-        cpu_usage: 0.0,
-        memory_usage: 0,
-        boot_time_seconds: 0,
-        block_production_tracking_delay: 0.0,
-        min_block_production_delay: 0.0,
-        max_block_production_delay: 0.0,
-        max_block_wait_delay: 0.0,
-        chain_id: None,
-        protocol_version: None,
-    };
-    let mock_exec = MockExecResult {
-        last_insert_id: 1,
-        rows_affected: 1,
-    };
-    (mock_node, mock_exec)
-}
-
 // Verify happy path for ingestion of telemetry data v1.
 #[test(tokio::test)]
 async fn entity_insert_v1() {
-    let (mock_node, mock_exec) = mock_node_and_exec();
-
     let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
-        .append_query_results([vec![mock_node.clone()]])
-        .append_exec_results([mock_exec.clone()])
+        .append_query_results([vec![upserted_node(true)]])
         .into_connection();
     let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
-        .append_query_results([vec![mock_node.clone()]])
-        .append_exec_results([mock_exec.clone()])
+        .append_query_results([vec![upserted_node(true)]])
         .into_connection();
 
     let json = fs::read_to_string("res/example_telemetry_payload_v1").unwrap();
@@ -190,12 +156,9 @@ async fn entity_insert_v1() {
 // Verify happy path for ingestion of telemetry data v2, with chain-id: testnet.
 #[test(tokio::test)]
 async fn entity_insert_v2_testnet() {
-    let (mock_node, mock_exec) = mock_node_and_exec();
-
     let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
     let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
-        .append_query_results([vec![mock_node; 3]])
-        .append_exec_results([mock_exec.clone(), mock_exec.clone(), mock_exec.clone()])
+        .append_query_results(vec![vec![upserted_node(true)]; 3])
         .into_connection();
 
     let json = fs::read_to_string("res/example_telemetry_payload_v2_testnet").unwrap();
@@ -224,11 +187,8 @@ async fn entity_insert_v2_testnet() {
 // Verify happy path for ingestion of telemetry data v2, with chain-id: mainnet.
 #[test(tokio::test)]
 async fn entity_insert_v2_mainnet() {
-    let (mock_node, mock_exec) = mock_node_and_exec();
-
     let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
-        .append_query_results([vec![mock_node; 3]])
-        .append_exec_results([mock_exec.clone(), mock_exec.clone(), mock_exec.clone()])
+        .append_query_results(vec![vec![upserted_node(true)]; 3])
         .into_connection();
     let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
 
@@ -258,15 +218,11 @@ async fn entity_insert_v2_mainnet() {
 // Verify happy path for ingestion of telemetry data v2, with chain-id: other.
 #[test(tokio::test)]
 async fn entity_insert_v2_other() {
-    let (mock_node, mock_exec) = mock_node_and_exec();
-
     let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
-        .append_query_results([vec![mock_node.clone()]])
-        .append_exec_results([mock_exec.clone()])
+        .append_query_results([vec![upserted_node(true)]])
         .into_connection();
     let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
-        .append_query_results([vec![mock_node.clone()]])
-        .append_exec_results([mock_exec.clone()])
+        .append_query_results([vec![upserted_node(true)]])
         .into_connection();
 
     let json = fs::read_to_string("res/example_telemetry_payload_v2_other").unwrap();
@@ -295,15 +251,11 @@ async fn entity_insert_v2_other() {
 // Verify corner cases for ingestion of telemetry data v2, when chain-id is missing.
 #[test(tokio::test)]
 async fn entity_insert_v2_backward_compatibility() {
-    let (mock_node, mock_exec) = mock_node_and_exec();
-
     let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
-        .append_query_results([vec![mock_node.clone()]])
-        .append_exec_results([mock_exec.clone()])
+        .append_query_results([vec![upserted_node(true)]])
         .into_connection();
     let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
-        .append_query_results([vec![mock_node.clone()]])
-        .append_exec_results([mock_exec.clone()])
+        .append_query_results([vec![upserted_node(true)]])
         .into_connection();
 
     let json = fs::read_to_string("res/example_telemetry_payload_v1").unwrap();
@@ -328,3 +280,43 @@ async fn entity_insert_v2_backward_compatibility() {
     assert_eq!(db_mainnet.unwrap().into_transaction_log().len(), 1);
     assert_eq!(db_testnet.unwrap().into_transaction_log().len(), 1);
 }
+
+// Ingestion metrics should tell failure reasons, including chains without database, new from
+// updated nodes and telemetry versions.
+#[test(tokio::test)]
+async fn ingestion_metrics() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![upserted_node(true)], vec![upserted_node(false)]])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    let json = fs::read_to_string("res/example_telemetry_payload_v2_mainnet").unwrap();
+    for _ in 0..2 {
+        let (status, _) = post(server.app(), "/nodes", json.clone()).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+    }
+    let (status, _) = post(server.app(), "/nodes/mainnet", "{".to_string()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = post(server.app(), "/nodes/mainnet", "{}".to_string()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let json = fs::read_to_string("res/example_telemetry_payload_v2_other").unwrap();
+    let (status, _) = post(server.app(), "/nodes", json).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (_, metrics) = get(server.app(), "/metrics").await;
+    assert!(!metrics.contains(r#"telemetry_service_successful_requests_total{network="other"}"#));
+    for line in [
+        r#"telemetry_service_upserted_nodes_total{network="mainnet",action="inserted"} 1"#,
+        r#"telemetry_service_upserted_nodes_total{network="mainnet",action="updated"} 1"#,
+        r#"telemetry_service_failed_requests_total{network="mainnet",reason="parse"} 1"#,
+        r#"telemetry_service_failed_requests_total{network="mainnet",reason="validation"} 1"#,
+        r#"telemetry_service_failed_requests_total{network="other",reason="unknown_chain"} 1"#,
+        r#"telemetry_service_ingestion_requests_total{route="/nodes",telemetry_version="v2"} 3"#,
+        r#"telemetry_service_ingestion_requests_total{route="/nodes/mainnet",telemetry_version="unknown"} 2"#,
+        r#"telemetry_service_db_upsert_latency_seconds_count{network="mainnet"} 2"#,
+        r#"telemetry_service_payload_size_bytes_count{network="mainnet"} 4"#,
+    ] {
+        assert!(metrics.contains(line), "missing {line}");
+    }
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use std::{env, fs, path::PathBuf};
+
+use axum::http::StatusCode;
+use common::{get, MOCK_SOCKET_ADDRESS};
+use sea_orm::{DatabaseBackend, MockDatabase};
+use serde_json::Value;
+use telemetry_service::{entities::node, reload::Reloader, Config, Server};
+use test_log::test;
+
+fn temp_file(name: &str, content: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("telemetry-service-reload-{name}"));
+    fs::write(&path, content).unwrap();
+    path
+}
+
+fn rules(names: &[&str]) -> String {
+    names
+        .iter()
+        .map(|name| format!("[[rule]]\nname = \"{name}\"\nwhen = [\"peer_count < 5\"]\n"))
+        .collect()
+}
+
+async fn rule_names(server: &Server) -> Vec<String> {
+    let (_, body) = get(server.app(), "/alerts").await;
+    let body: Value = serde_json::from_str(&body).unwrap();
+    body["rules"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|rule| rule["name"].as_str().unwrap().to_string())
+        .collect()
+}
+
+// The alert rules and node metrics settings should be applied on reload, unless invalid.
+#[test(tokio::test)]
+async fn reload_config() {
+    let rules_path = temp_file("rules.toml", &rules(&["low-peers"]));
+    let content = format!("alert_rules = {rules_path:?}\n");
+    let config_path = temp_file("config.toml", &content);
+    let args = [
+        "telemetry-service",
+        "--config",
+        config_path.to_str().unwrap(),
+        "postgresql://localhost",
+    ];
+    let (config, effective_config) = Config::load(args).unwrap();
+
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<node::Model>::new(), Vec::new()])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<node::Model>::new(), Vec::new()])
+        .into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_alerts(config.alerts)
+        .unwrap()
+        .with_node_metrics(config.node_metrics)
+        .with_reloader(Reloader::new(args, effective_config));
+    assert_eq!(rule_names(&server).await, ["low-peers"]);
+    let (status, _) = get(server.app(), "/metrics/nodes").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    fs::write(&rules_path, rules(&["low-peers", "no-peers"])).unwrap();
+    fs::write(&config_path, format!("{content}node_metrics = true\n")).unwrap();
+    server.reload_config();
+    assert_eq!(rule_names(&server).await, ["low-peers", "no-peers"]);
+    let (status, _) = get(server.app(), "/metrics/nodes").await;
+    assert_eq!(status, StatusCode::OK);
+
+    // An invalid rules file should be rejected, keeping the current configuration.
+    fs::write(&rules_path, "[[rule]]\nname = \"broken\"\n").unwrap();
+    fs::write(&config_path, &content).unwrap();
+    server.reload_config();
+    assert_eq!(rule_names(&server).await, ["low-peers", "no-peers"]);
+    let (status, _) = get(server.app(), "/metrics/nodes").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, metrics) = get(server.app(), "/metrics").await;
+    assert!(metrics.contains(r#"telemetry_service_config_reloads_total{status="success"} 1"#));
+    assert!(metrics.contains(r#"telemetry_service_config_reloads_total{status="failure"} 1"#));
+}
@@ -0,0 +1,159 @@
+mod common;
+
+use axum::http::StatusCode;
+use chrono::Utc;
+use clap::Parser;
+use common::{exec_result, get, mock_node, MOCK_SOCKET_ADDRESS};
+use sea_orm::{DatabaseBackend, DbErr, MockDatabase};
+use serde_json::Value;
+use telemetry_service::{config::ReplicasConfig, entities::node, Config, Server, Task};
+use test_log::test;
+
+fn validator(id: &str) -> node::Model {
+    node::Model {
+        account_id: Some("validator.near".to_string()),
+        is_validator: true,
+        ..mock_node(id, 100, "hash", Utc::now().naive_utc())
+    }
+}
+
+fn node_ids(body: &str) -> Vec<String> {
+    let validators: Value = serde_json::from_str(body).unwrap();
+    validators[0]["nodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|node| node["id"].as_str().unwrap().to_string())
+        .collect()
+}
+
+// Read queries should be served by the replica while it is healthy.
+#[test(tokio::test)]
+async fn queries_served_by_replica() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let replica_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results([exec_result()])
+        .append_query_results([vec![validator("replica")]])
+        .into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_replicas(ReplicasConfig::default(), Some(replica_mainnet), None);
+
+    server.run_task(Task::ReplicaCheck).await.unwrap();
+    let (status, body) = get(server.app(), "/validators/mainnet").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(node_ids(&body), ["replica"]);
+
+    let (_, body) = get(server.app(), "/metrics").await;
+    assert!(body.contains(r#"replica_healthy{network="mainnet"} 1"#));
+
+    let (db_mainnet, _) = server.into_db_connections();
+    assert!(db_mainnet.unwrap().into_transaction_log().is_empty());
+}
+
+// Read queries should fall back to the primary while the replica is unhealthy.
+#[test(tokio::test)]
+async fn fallback_to_primary() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![validator("primary")]])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let replica_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_errors([DbErr::Custom("replica down".to_string())])
+        .into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_replicas(ReplicasConfig::default(), Some(replica_mainnet), None);
+
+    server.run_task(Task::ReplicaCheck).await.unwrap();
+    let (status, body) = get(server.app(), "/validators/mainnet").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(node_ids(&body), ["primary"]);
+
+    let (_, body) = get(server.app(), "/metrics").await;
+    assert!(body.contains(r#"replica_healthy{network="mainnet"} 0"#));
+}
+
+// An unhealthy replica should be reported without failing the health check.
+#[test(tokio::test)]
+async fn replica_health() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results([exec_result(), exec_result()])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results([exec_result(), exec_result()])
+        .into_connection();
+    let replica_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results([exec_result()])
+        .into_connection();
+    let replica_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_errors([DbErr::Custom("replica down".to_string())])
+        .into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_replicas(
+            ReplicasConfig::default(),
+            Some(replica_mainnet),
+            Some(replica_testnet),
+        );
+
+    server.run_task(Task::ReplicaCheck).await.unwrap();
+    let (status, body) = get(server.app(), "/healthz").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        "mainnet replica: healthy\ntestnet replica: unhealthy\n"
+    );
+
+    // The health check reports the last recorded health without checking the replicas again.
+    let (status, body) = get(server.app(), "/healthz").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        "mainnet replica: healthy\ntestnet replica: unhealthy\n"
+    );
+}
+
+// A replica unreachable at startup should be reported unhealthy, and checked again later.
+#[test(tokio::test)]
+async fn replica_unreachable() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results([exec_result(), exec_result()])
+        .append_query_results([vec![validator("primary")]])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results([exec_result(), exec_result()])
+        .into_connection();
+    let args = [
+        "telemetry-service",
+        "postgresql://localhost",
+        "--db-acquire-timeout",
+        "1",
+    ];
+    let database = Config::try_parse_from(args).unwrap().database;
+    let replicas = ReplicasConfig {
+        mainnet_replica_url: Some("postgresql://127.0.0.1:1".to_string()),
+        ..Default::default()
+    };
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_replica_urls(replicas, &database);
+
+    // Replicas start unhealthy.
+    let (_, body) = get(server.app(), "/healthz").await;
+    assert_eq!(body, "mainnet replica: unhealthy\n");
+    let (_, body) = get(server.app(), "/validators/mainnet").await;
+    assert_eq!(node_ids(&body), ["primary"]);
+
+    server.run_task(Task::ReplicaCheck).await.unwrap();
+    let (status, body) = get(server.app(), "/healthz?verbose").await;
+    assert_eq!(status, StatusCode::OK);
+    let report: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(report["databases"][2]["role"], "replica");
+    assert_eq!(report["databases"][2]["healthy"], false);
+    assert_eq!(report["databases"][2]["error"], "not connected");
+
+    let (_, body) = get(server.app(), "/metrics").await;
+    assert!(body.contains(r#"replica_healthy{network="mainnet"} 0"#));
+}
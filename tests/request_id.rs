@@ -0,0 +1,82 @@
+mod common;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    Router,
+};
+use common::MOCK_SOCKET_ADDRESS;
+use sea_orm::{DatabaseBackend, MockDatabase};
+use telemetry_service::Server;
+use test_log::test;
+use tower::ServiceExt;
+
+fn app() -> Router {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .app()
+}
+
+/// Sends a POST request to `/nodes` with an optional request id and returns the response status
+/// and request id.
+async fn post_nodes(request_id: Option<&str>) -> (StatusCode, String) {
+    let mut request = Request::builder().uri("/nodes").method("POST");
+    if let Some(request_id) = request_id {
+        request = request.header("x-request-id", request_id);
+    }
+    let response = app()
+        .oneshot(request.body(Body::from("{")).unwrap())
+        .await
+        .unwrap();
+    let request_id = response.headers()["x-request-id"].to_str().unwrap();
+    (response.status(), request_id.to_string())
+}
+
+// The request id sent by the client should be returned, including in error responses.
+#[test(tokio::test)]
+async fn request_id_propagated() {
+    let (status, request_id) = post_nodes(Some("abc-123")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(request_id, "abc-123");
+}
+
+// A request id should be generated when missing or invalid.
+#[test(tokio::test)]
+async fn request_id_generated() {
+    let (_, first) = post_nodes(None).await;
+    let (_, second) = post_nodes(Some("not a valid id")).await;
+    assert_eq!(first.len(), 36);
+    assert_eq!(second.len(), 36);
+    assert_ne!(first, second);
+}
+
+// Unknown routes should get a request id too.
+#[test(tokio::test)]
+async fn request_id_not_found() {
+    let response = app()
+        .oneshot(Request::builder().uri("/foo").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert!(response.headers().contains_key("x-request-id"));
+}
+
+// Error responses should hold the error message and the request id, without the request body.
+#[test(tokio::test)]
+async fn error_response_redacted() {
+    let request = Request::builder()
+        .uri("/nodes")
+        .method("POST")
+        .header("x-request-id", "abc-123")
+        .body(Body::from(r#"{"chain": {"account_id": "secret.near"}}"#))
+        .unwrap();
+    let response = app().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    assert!(body.starts_with("invalid request (validation): "), "{body}");
+    assert!(body.ends_with("\nrequest id: abc-123\n"), "{body}");
+    assert!(!body.contains("secret.near"), "{body}");
+}
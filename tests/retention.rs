@@ -0,0 +1,195 @@
+mod common;
+
+use chrono::{Duration, Utc};
+use std::collections::BTreeMap;
+
+use common::{get, mock_node, MOCK_SOCKET_ADDRESS};
+use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult, Value};
+use telemetry_service::{
+    config::{RetentionConfig, RetentionMode},
+    entities::node,
+    Server, Task,
+};
+use test_log::test;
+
+fn exec_result(rows_affected: u64) -> MockExecResult {
+    MockExecResult {
+        last_insert_id: 0,
+        rows_affected,
+    }
+}
+
+// Stale nodes should be deleted in a single statement, along with the stale claims.
+#[test(tokio::test)]
+async fn prune_delete() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results([exec_result(2), exec_result(3)])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results([exec_result(0), exec_result(0)])
+        .into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_retention(RetentionConfig {
+            retention_days: Some(30),
+            ..Default::default()
+        });
+
+    server.run_task(Task::Prune).await.unwrap();
+
+    let (_, metrics) = get(server.app(), "/metrics").await;
+    assert!(metrics.contains(
+        "telemetry_service_pruned_nodes_total{network=\"mainnet\",action=\"deleted\"} 2"
+    ));
+    assert!(metrics.contains(
+        "telemetry_service_pruned_nodes_total{network=\"testnet\",action=\"deleted\"} 0"
+    ));
+
+    let (db_mainnet, _) = server.into_db_connections();
+    let log = db_mainnet.unwrap().into_transaction_log();
+    assert_eq!(log.len(), 2);
+    let log = format!("{log:?}");
+    assert!(log.contains(r#"DELETE FROM \"node\" WHERE \"node\".\"last_seen\" < $1"#));
+    assert!(log.contains(r#"DELETE FROM \"node_claim\" WHERE \"node_claim\".\"last_seen\" < $1"#));
+}
+
+// Stale nodes should be moved to the archive, and old archived nodes deleted.
+#[test(tokio::test)]
+async fn prune_archive() {
+    let stale = mock_node("stale", 0, "", Utc::now().naive_utc() - Duration::days(40));
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![stale]])
+        .append_exec_results([
+            exec_result(1),
+            exec_result(1),
+            exec_result(0),
+            exec_result(3),
+        ])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<node::Model>::new()])
+        .append_exec_results([exec_result(0), exec_result(0)])
+        .into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_retention(RetentionConfig {
+            retention_days: Some(30),
+            retention_mode: RetentionMode::Archive,
+            archive_retention_days: Some(365),
+            ..Default::default()
+        });
+
+    server.run_task(Task::Prune).await.unwrap();
+
+    let (_, metrics) = get(server.app(), "/metrics").await;
+    assert!(metrics.contains(
+        "telemetry_service_pruned_nodes_total{network=\"mainnet\",action=\"archived\"} 1"
+    ));
+    assert!(metrics.contains(
+        "telemetry_service_pruned_nodes_total{network=\"mainnet\",action=\"archive_deleted\"} 3"
+    ));
+
+    let (db_mainnet, db_testnet) = server.into_db_connections();
+    let log = db_mainnet.unwrap().into_transaction_log();
+    // Archival transaction, deletion of the stale claims and of expired archived nodes.
+    assert_eq!(log.len(), 3);
+    let log = format!("{log:?}");
+    assert!(log.contains("FOR UPDATE"));
+    assert!(log.contains(r#"INSERT INTO \"node_archive\""#));
+    assert!(log.contains(r#"DELETE FROM \"node\" WHERE \"node\".\"id\" IN ($1)"#));
+    assert!(log.contains(r#"DELETE FROM \"node_archive\""#));
+    // Nothing to archive on testnet.
+    assert_eq!(db_testnet.unwrap().into_transaction_log().len(), 3);
+}
+
+// Stale nodes should be archived in batches, within the bind parameter limit of Postgres.
+#[test(tokio::test)]
+async fn prune_archive_batches() {
+    let last_seen = Utc::now().naive_utc() - Duration::days(40);
+    let stale: Vec<node::Model> = (0..1500)
+        .map(|i| mock_node(&format!("stale-{i}"), 0, "", last_seen))
+        .collect();
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([stale])
+        .append_exec_results([
+            exec_result(1000),
+            exec_result(1000),
+            exec_result(500),
+            exec_result(500),
+            exec_result(0),
+        ])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<node::Model>::new()])
+        .append_exec_results([exec_result(0)])
+        .into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_retention(RetentionConfig {
+            retention_days: Some(30),
+            retention_mode: RetentionMode::Archive,
+            ..Default::default()
+        });
+
+    server.run_task(Task::Prune).await.unwrap();
+
+    let (_, metrics) = get(server.app(), "/metrics").await;
+    assert!(metrics.contains(
+        "telemetry_service_pruned_nodes_total{network=\"mainnet\",action=\"archived\"} 1500"
+    ));
+
+    let (db_mainnet, _) = server.into_db_connections();
+    let log = format!("{:?}", db_mainnet.unwrap().into_transaction_log());
+    assert_eq!(log.matches(r#"INSERT INTO \"node_archive\""#).count(), 2);
+    assert_eq!(log.matches(r#"DELETE FROM \"node\""#).count(), 2);
+}
+
+// A dry run should not modify the database, nor load every stale node.
+#[test(tokio::test)]
+async fn prune_dry_run() {
+    let count = |count: i64| [BTreeMap::from([("num_items", Value::from(count))])];
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([count(1)])
+        .append_query_results([[BTreeMap::from([("id", Value::from("stale"))])]])
+        .append_query_results([count(2)])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([count(0)])
+        .append_query_results([Vec::<BTreeMap<&str, Value>>::new()])
+        .append_query_results([count(0)])
+        .into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_retention(RetentionConfig {
+            retention_days: Some(30),
+            prune_dry_run: true,
+            ..Default::default()
+        });
+
+    server.run_task(Task::Prune).await.unwrap();
+
+    let (_, metrics) = get(server.app(), "/metrics").await;
+    assert!(!metrics.contains("telemetry_service_pruned_nodes_total{"));
+
+    let (db_mainnet, _) = server.into_db_connections();
+    let log = format!("{:?}", db_mainnet.unwrap().into_transaction_log());
+    assert!(log.contains("COUNT(*)"));
+    assert!(log.contains(r#"SELECT \"node\".\"id\" FROM \"node\""#));
+    assert!(log.contains("LIMIT"));
+    assert!(log.contains(r#"FROM \"node_claim\""#));
+    assert!(!log.contains("DELETE"));
+}
+
+// Without a retention period, nothing is pruned.
+#[test(tokio::test)]
+async fn prune_disabled() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    server.run_task(Task::Prune).await.unwrap();
+
+    let (db_mainnet, db_testnet) = server.into_db_connections();
+    assert!(db_mainnet.unwrap().into_transaction_log().is_empty());
+    assert!(db_testnet.unwrap().into_transaction_log().is_empty());
+}
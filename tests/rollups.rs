@@ -0,0 +1,185 @@
+mod common;
+
+use axum::http::StatusCode;
+use chrono::{NaiveDate, Utc};
+use common::{get, mock_node, MOCK_SOCKET_ADDRESS};
+use sea_orm::{DatabaseBackend, DbErr, MockDatabase, MockExecResult};
+use serde_json::{json, Value};
+use telemetry_service::{
+    entities::{node, rollup, rollup_version},
+    Server, Task,
+};
+use test_log::test;
+
+fn exec_result() -> MockExecResult {
+    MockExecResult {
+        last_insert_id: 0,
+        rows_affected: 1,
+    }
+}
+
+// The current hour and day rollups should summarise the nodes.
+#[test(tokio::test)]
+async fn update_rollups() {
+    let now = Utc::now().naive_utc();
+    let node = |id: &str, peer_count, cpu_usage, protocol_version| node::Model {
+        peer_count,
+        cpu_usage,
+        agent_version: "1.40.0".to_string(),
+        protocol_version,
+        ..mock_node(id, 0, "", now)
+    };
+    let nodes = vec![
+        node("a", 10, 10.0, Some(68)),
+        node("b", 20, 20.0, Some(68)),
+        node::Model {
+            is_validator: true,
+            ..node("c", 30, 60.0, None)
+        },
+    ];
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([nodes])
+        .append_exec_results(vec![exec_result(); 6])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<node::Model>::new()])
+        .append_exec_results(vec![exec_result(); 4])
+        .into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    server.run_task(Task::Rollup).await.unwrap();
+
+    let (db_mainnet, db_testnet) = server.into_db_connections();
+    let log = db_mainnet.unwrap().into_transaction_log();
+    // One query for the nodes, one transaction per granularity.
+    assert_eq!(log.len(), 3);
+    let hour = format!("{:?}", log[1]);
+    assert!(hour.contains(r#"INSERT INTO \"rollup\""#));
+    assert!(hour.contains(r#"String(Some("hour"))"#));
+    // Node and validator counts.
+    assert!(hour.contains("BigInt(Some(3)), BigInt(Some(1))"));
+    // Peer count min, avg and max.
+    assert!(hour.contains("Double(Some(10.0)), Double(Some(20.0)), Double(Some(30.0))"));
+    // CPU usage min, avg and max.
+    assert!(hour.contains("Double(Some(10.0)), Double(Some(30.0)), Double(Some(60.0))"));
+    assert!(hour.contains(r#"DELETE FROM \"rollup_version\""#));
+    assert!(hour.contains(r#"INSERT INTO \"rollup_version\""#));
+    assert!(hour.contains(r#"String(Some("1.40.0")), Int(Some(0)), BigInt(Some(1))"#));
+    assert!(hour.contains(r#"String(Some("1.40.0")), Int(Some(68)), BigInt(Some(2))"#));
+    assert!(format!("{:?}", log[2]).contains(r#"String(Some("day"))"#));
+
+    // Empty buckets have no versions.
+    let log = db_testnet.unwrap().into_transaction_log();
+    assert_eq!(log.len(), 3);
+    let hour = format!("{:?}", log[1]);
+    assert!(hour.contains("BigInt(Some(0)), BigInt(Some(0)), Double(None)"));
+    assert!(!hour.contains(r#"INSERT INTO \"rollup_version\""#));
+}
+
+// Rollups should be returned with their version counts.
+#[test(tokio::test)]
+async fn query_rollups() {
+    let bucket_start = |day| {
+        NaiveDate::from_ymd_opt(2024, 6, day)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    };
+    let rollup = |day, node_count| rollup::Model {
+        granularity: "day".to_string(),
+        bucket_start: bucket_start(day),
+        node_count,
+        validator_count: 1,
+        peer_count_min: Some(1.0),
+        peer_count_avg: Some(2.0),
+        peer_count_max: Some(3.0),
+        cpu_usage_min: None,
+        cpu_usage_avg: None,
+        cpu_usage_max: None,
+        memory_usage_min: None,
+        memory_usage_avg: None,
+        memory_usage_max: None,
+        bandwidth_download_min: None,
+        bandwidth_download_avg: None,
+        bandwidth_download_max: None,
+        bandwidth_upload_min: None,
+        bandwidth_upload_avg: None,
+        bandwidth_upload_max: None,
+    };
+    let version = |day, agent_version: &str, protocol_version, node_count| rollup_version::Model {
+        granularity: "day".to_string(),
+        bucket_start: bucket_start(day),
+        agent_version: agent_version.to_string(),
+        protocol_version,
+        node_count,
+    };
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![rollup(1, 3), rollup(2, 1)]])
+        .append_query_results([vec![
+            version(1, "1.39.0", 0, 1),
+            version(1, "1.40.0", 68, 2),
+            version(2, "1.40.0", 68, 1),
+        ]])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    let (status, body) = get(
+        server.app(),
+        "/rollups/mainnet?granularity=day&from=2024-06-01T00:00:00&to=2024-06-03T00:00:00",
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let rollups: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(rollups.as_array().unwrap().len(), 2);
+    assert_eq!(rollups[0]["bucket_start"], json!("2024-06-01T00:00:00"));
+    assert_eq!(rollups[0]["node_count"], json!(3));
+    assert_eq!(
+        rollups[0]["peer_count"],
+        json!({ "min": 1.0, "avg": 2.0, "max": 3.0 })
+    );
+    assert_eq!(
+        rollups[0]["cpu_usage"],
+        json!({ "min": null, "avg": null, "max": null })
+    );
+    assert_eq!(
+        rollups[0]["versions"],
+        json!([
+            { "agent_version": "1.39.0", "protocol_version": null, "node_count": 1 },
+            { "agent_version": "1.40.0", "protocol_version": 68, "node_count": 2 },
+        ])
+    );
+    assert_eq!(
+        rollups[1]["versions"],
+        json!([{ "agent_version": "1.40.0", "protocol_version": 68, "node_count": 1 }])
+    );
+}
+
+// Invalid queries should be rejected.
+#[test(tokio::test)]
+async fn invalid_query() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    let (status, _) = get(server.app(), "/rollups/mainnet?granularity=week").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = get(server.app(), "/rollups/localnet").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+// The details of a failure to load the rollups should only be logged.
+#[test(tokio::test)]
+async fn rollups_query_error() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_errors([DbErr::Custom("rollups error".to_string())])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    let (status, body) = get(server.app(), "/rollups/mainnet").await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(body.starts_with("error loading rollups: "), "{body}");
+    assert!(body.contains("\nrequest id: "), "{body}");
+    assert!(!body.contains("DBError"), "{body}");
+}
@@ -0,0 +1,150 @@
+mod common;
+
+use std::{fs, time::Duration};
+
+use axum::http::StatusCode;
+use common::{get, upserted_node, MOCK_SOCKET_ADDRESS};
+use futures::StreamExt;
+use sea_orm::{DatabaseBackend, MockDatabase};
+use serde_json::{json, Value};
+use telemetry_service::{config::StreamConfig, Server};
+use test_log::test;
+use tokio::{net::TcpListener, time::timeout};
+use tokio_tungstenite::tungstenite::Message;
+
+const NODE_ID: &str = "ed25519:6Hat46Wuxrk1czrhENjJrS3GuYUXYDmMgFtGLFyWGWNq";
+
+/// Serves a server accepting `upserts` node reports, returning its address.
+async fn serve(upserts: usize) -> String {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results(vec![vec![upserted_node(true)]; upserts])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let app = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_stream(StreamConfig {
+            stream_heartbeat_interval: 1,
+            ..Default::default()
+        })
+        .app();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    address
+}
+
+/// Reports the example telemetry under `node_id`.
+async fn report(address: &str, node_id: &str) {
+    let json = fs::read_to_string("res/example_telemetry_payload_v2_mainnet")
+        .unwrap()
+        .replace(NODE_ID, node_id);
+    let response = reqwest::Client::new()
+        .post(format!("http://{address}/nodes"))
+        .body(json)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+}
+
+// The accepted updates matching the filters should be pushed as Server-Sent Events.
+#[test(tokio::test)]
+async fn server_sent_events() {
+    let address = serve(2).await;
+    let mut response = reqwest::get(format!(
+        "http://{address}/nodes/mainnet/stream?validators=true&node_id={NODE_ID},other"
+    ))
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["content-type"].to_str().unwrap(),
+        "text/event-stream"
+    );
+
+    report(&address, "ed25519:filtered").await;
+    report(&address, NODE_ID).await;
+    let mut received = String::new();
+    while !received.ends_with("\n\n") {
+        let chunk = timeout(Duration::from_secs(5), response.chunk())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        received.push_str(std::str::from_utf8(&chunk).unwrap());
+    }
+    let (event, data) = received.trim_end().split_once('\n').unwrap();
+    assert_eq!(event, "event: node");
+    let update: Value = serde_json::from_str(data.strip_prefix("data: ").unwrap()).unwrap();
+    assert_eq!(update["id"], json!(NODE_ID));
+    assert_eq!(update["chain"], json!("mainnet"));
+    assert_eq!(update["is_validator"], json!(true));
+
+    // Heartbeat.
+    let chunk = timeout(Duration::from_secs(3), response.chunk())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(chunk.starts_with(b":"), "{chunk:?}");
+}
+
+// The updates should be pushed as WebSocket messages, with pings as heartbeat.
+#[test(tokio::test)]
+async fn websocket() {
+    let address = serve(1).await;
+    let (mut socket, _) =
+        tokio_tungstenite::connect_async(format!("ws://{address}/nodes/mainnet/ws?version=trunk"))
+            .await
+            .unwrap();
+
+    let (_, metrics) = get_metrics(&address).await;
+    let line = r#"telemetry_service_stream_clients{network="mainnet"} 1"#;
+    assert!(metrics.contains(line), "missing {line}");
+
+    report(&address, NODE_ID).await;
+    let mut ping = false;
+    let mut update = None;
+    while !ping || update.is_none() {
+        let message = timeout(Duration::from_secs(5), socket.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        match message {
+            Message::Text(text) => update = Some(serde_json::from_str::<Value>(&text).unwrap()),
+            Message::Ping(_) => ping = true,
+            message => panic!("unexpected message: {message:?}"),
+        }
+    }
+    let update = update.unwrap();
+    assert_eq!(update["event"], json!("node"));
+    assert_eq!(update["id"], json!(NODE_ID));
+    assert_eq!(update["agent_version"], json!("trunk"));
+
+    socket.close(None).await.unwrap();
+    drop(socket);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let (_, metrics) = get_metrics(&address).await;
+    let line = r#"telemetry_service_stream_clients{network="mainnet"} 0"#;
+    assert!(metrics.contains(line), "missing {line}");
+}
+
+// Streams of chains that aren't persisted should be rejected.
+#[test(tokio::test)]
+async fn unknown_chain() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let app = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .app();
+    let (status, _) = get(app, "/nodes/other/stream").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+async fn get_metrics(address: &str) -> (StatusCode, String) {
+    let response = reqwest::get(format!("http://{address}/metrics"))
+        .await
+        .unwrap();
+    (response.status(), response.text().await.unwrap())
+}
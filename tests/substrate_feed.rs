@@ -0,0 +1,106 @@
+mod common;
+
+use std::{fs, time::Duration};
+
+use chrono::Utc;
+use common::{mock_node, upserted_node, MOCK_SOCKET_ADDRESS};
+use futures::{SinkExt, StreamExt};
+use sea_orm::{DatabaseBackend, MockDatabase};
+use serde_json::{json, Value};
+use telemetry_service::{entities::node, Server};
+use test_log::test;
+use tokio::{net::TcpListener, time::timeout};
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+type Socket = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Returns the next feed messages, as pairs of action and payload.
+async fn receive(socket: &mut Socket) -> Vec<(u64, Value)> {
+    loop {
+        let message = timeout(Duration::from_secs(5), socket.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        if let Message::Text(text) = message {
+            let messages: Vec<Value> = serde_json::from_str(&text).unwrap();
+            return messages
+                .chunks(2)
+                .map(|pair| (pair[0].as_u64().unwrap(), pair[1].clone()))
+                .collect();
+        }
+    }
+}
+
+// The feed should list the chains, then the nodes of the subscribed chain and their updates.
+#[test(tokio::test)]
+async fn substrate_feed() {
+    let now = Utc::now().naive_utc();
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([
+            vec![mock_node("node-1", 100, "hash-100", now)],
+            vec![mock_node("node-1", 100, "hash-100", now)],
+        ])
+        .append_query_results([vec![upserted_node(true)]])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<node::Model>::new()])
+        .into_connection();
+    let app = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .app();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{address}/feed"))
+        .await
+        .unwrap();
+    assert_eq!(
+        receive(&mut socket).await,
+        [
+            (0, json!(32)),
+            (11, json!(["NEAR Mainnet", "mainnet", 1])),
+            (11, json!(["NEAR Testnet", "testnet", 0])),
+        ]
+    );
+
+    socket
+        .send(Message::Text("subscribe:mainnet".to_string()))
+        .await
+        .unwrap();
+    let messages = receive(&mut socket).await;
+    assert_eq!(messages[0], (13, json!("mainnet")));
+    let (action, node) = &messages[1];
+    assert_eq!(*action, 3);
+    assert_eq!(node[0], json!(0));
+    assert_eq!(node[1][4], json!("node-1"));
+    assert_eq!(
+        (&node[5][0], &node[5][1]),
+        (&json!(100), &json!("hash-100"))
+    );
+    assert_eq!(messages[2].0, 1);
+    assert_eq!(messages[2].1[0], json!(100));
+
+    let json = fs::read_to_string("res/example_telemetry_payload_v2_mainnet").unwrap();
+    let response = reqwest::Client::new()
+        .post(format!("http://{address}/nodes"))
+        .body(json)
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    let messages = receive(&mut socket).await;
+    let (action, node) = &messages[0];
+    assert_eq!(*action, 3);
+    assert_eq!(node[0], json!(1));
+    // Named after the account, listed as validator.
+    assert_eq!(node[1][0], json!("test.near"));
+    assert_eq!(node[1][3], json!("test.near"));
+
+    socket
+        .send(Message::Text("ping:7".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(receive(&mut socket).await, [(15, json!("7"))]);
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use axum::http::StatusCode;
+use chrono::{Duration, Utc};
+use common::{get, mock_node, MOCK_SOCKET_ADDRESS};
+use sea_orm::{DatabaseBackend, MockDatabase};
+use serde_json::{json, Value};
+use telemetry_service::{entities::node, Server, Task};
+use test_log::test;
+
+fn statuses(report: &Value) -> Vec<(String, String)> {
+    let mut statuses: Vec<(String, String)> = report["nodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|node| {
+            (
+                node["id"].as_str().unwrap().to_string(),
+                node["status"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    statuses.sort();
+    statuses
+}
+
+// Check the classification of synced, lagging, stalled and offline nodes.
+#[test(tokio::test)]
+async fn classification() {
+    let now = Utc::now().naive_utc();
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([
+            vec![
+                mock_node("synced", 1000, "", now),
+                mock_node("lagging", 900, "", now),
+                mock_node("offline", 1000, "", now - Duration::hours(1)),
+                mock_node("stuck", 500, "", now - Duration::seconds(200)),
+            ],
+            vec![
+                mock_node("synced", 1010, "", now),
+                mock_node("lagging", 900, "", now),
+                mock_node("offline", 1000, "", now - Duration::hours(1)),
+                mock_node("stuck", 500, "", now),
+            ],
+        ])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<node::Model>::new(), Vec::<node::Model>::new()])
+        .into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    // The stuck node is only behind the network head for now.
+    server.run_task(Task::SyncStatus).await.unwrap();
+    let (status, body) = get(server.app(), "/sync/mainnet").await;
+    assert_eq!(status, StatusCode::OK);
+    let report: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(report["head"], json!(1000));
+    assert_eq!(
+        statuses(&report),
+        [
+            ("lagging".to_string(), "lagging".to_string()),
+            ("offline".to_string(), "offline".to_string()),
+            ("stuck".to_string(), "lagging".to_string()),
+            ("synced".to_string(), "synced".to_string()),
+        ]
+    );
+
+    // The stuck node reported again at the same height.
+    server.run_task(Task::SyncStatus).await.unwrap();
+    let (_, body) = get(server.app(), "/sync/mainnet").await;
+    let report: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(report["head"], json!(1010));
+    assert_eq!(
+        statuses(&report),
+        [
+            ("lagging".to_string(), "lagging".to_string()),
+            ("offline".to_string(), "offline".to_string()),
+            ("stuck".to_string(), "stalled".to_string()),
+            ("synced".to_string(), "synced".to_string()),
+        ]
+    );
+    assert_eq!(
+        report["summary"],
+        json!({ "synced": 1, "lagging": 1, "stalled": 1, "offline": 1 })
+    );
+
+    let (_, metrics) = get(server.app(), "/metrics").await;
+    assert!(metrics.contains(
+        "telemetry_service_nodes_by_sync_status{network=\"mainnet\",status=\"stalled\"} 1"
+    ));
+    assert!(metrics.contains(
+        "telemetry_service_nodes_by_sync_status{network=\"testnet\",status=\"synced\"} 0"
+    ));
+}
+
+// Chains without a database are not classified.
+#[test(tokio::test)]
+async fn unknown_chain() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    let (status, _) = get(server.app(), "/sync/localnet").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use std::{
+    env, fs,
+    net::{SocketAddr, TcpListener},
+    path::PathBuf,
+    time::Duration,
+};
+
+use axum::http::StatusCode;
+use common::{get, post, MOCK_SOCKET_ADDRESS};
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa,
+    KeyPair,
+};
+use reqwest::tls::TlsInfo;
+use sea_orm::{DatabaseBackend, MockDatabase};
+use telemetry_service::{config::TlsConfig, Server};
+use test_log::test;
+
+struct Ca {
+    cert: Certificate,
+    key: KeyPair,
+}
+
+impl Ca {
+    fn new() -> Self {
+        let mut params = CertificateParams::new(Vec::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "test CA");
+        let key = KeyPair::generate().unwrap();
+        let cert = params.self_signed(&key).unwrap();
+        Self { cert, key }
+    }
+
+    /// Issues a certificate for `purpose`, returning it with its key in PEM.
+    fn issue(&self, purpose: ExtendedKeyUsagePurpose) -> (Certificate, String) {
+        let mut params = CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "localhost");
+        params.extended_key_usages = vec![purpose];
+        let key = KeyPair::generate().unwrap();
+        let cert = params.signed_by(&key, &self.cert, &self.key).unwrap();
+        (cert, key.serialize_pem())
+    }
+}
+
+fn temp_file(name: &str, content: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("telemetry-service-tls-{name}"));
+    fs::write(&path, content).unwrap();
+    path
+}
+
+fn server(address: SocketAddr, config: TlsConfig) -> Server {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    Server::new(address, db_mainnet, db_testnet)
+        .unwrap()
+        .with_tls(config)
+}
+
+/// Sends a GET request over TLS and returns the response status and the server certificate.
+async fn get_tls(
+    uri: &str,
+    ca: &Ca,
+    identity: Option<(&Certificate, &str)>,
+) -> (StatusCode, Vec<u8>) {
+    let ca = reqwest::Certificate::from_pem(ca.cert.pem().as_bytes()).unwrap();
+    let mut client = reqwest::Client::builder()
+        .add_root_certificate(ca)
+        .tls_info(true);
+    if let Some((cert, key)) = identity {
+        let identity =
+            reqwest::Identity::from_pkcs8_pem(cert.pem().as_bytes(), key.as_bytes()).unwrap();
+        client = client.identity(identity);
+    }
+    let response = client.build().unwrap().get(uri).send().await.unwrap();
+    let tls_info = response.extensions().get::<TlsInfo>().unwrap();
+    let server_cert = tls_info.peer_certificate().unwrap().to_vec();
+    (response.status(), server_cert)
+}
+
+// The read APIs should only be served to verified clients when client certificates are required.
+#[test(tokio::test)]
+async fn client_certificate_required() {
+    let config = TlsConfig {
+        tls_client_ca: Some(PathBuf::from("ca.pem")),
+        ..Default::default()
+    };
+    let app = server(MOCK_SOCKET_ADDRESS, config).app();
+    let (status, _) = get(app.clone(), "/alerts").await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = get(app.clone(), "/livez").await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = post(app, "/nodes", "{".to_string()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+// The server should verify the client certificates and reload its own when modified.
+#[test(tokio::test)]
+async fn tls_server() {
+    let ca = Ca::new();
+    let (server_cert, server_key) = ca.issue(ExtendedKeyUsagePurpose::ServerAuth);
+    let (client_cert, client_key) = ca.issue(ExtendedKeyUsagePurpose::ClientAuth);
+    let config = TlsConfig {
+        tls_cert: Some(temp_file("cert.pem", &server_cert.pem())),
+        tls_key: Some(temp_file("key.pem", &server_key)),
+        tls_client_ca: Some(temp_file("ca.pem", &ca.cert.pem())),
+        tls_reload_interval: 1,
+    };
+    let address = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let server = server(address, config);
+    tokio::spawn(async move { server.run().await });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let url = format!("https://localhost:{}", address.port());
+    let (status, cert) = get_tls(&format!("{url}/livez"), &ca, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(cert, server_cert.der().to_vec());
+    let (status, _) = get_tls(&format!("{url}/alerts"), &ca, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let identity = Some((&client_cert, client_key.as_str()));
+    let (status, _) = get_tls(&format!("{url}/alerts"), &ca, identity).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (renewed_cert, renewed_key) = ca.issue(ExtendedKeyUsagePurpose::ServerAuth);
+    temp_file("key.pem", &renewed_key);
+    temp_file("cert.pem", &renewed_cert.pem());
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    let (status, cert) = get_tls(&format!("{url}/livez"), &ca, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(cert, renewed_cert.der().to_vec());
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use std::fs;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use common::{upserted_node, MOCK_SOCKET_ADDRESS};
+use opentelemetry::{global, trace::TracerProvider as _};
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator, testing::trace::InMemorySpanExporter,
+    trace::TracerProvider,
+};
+use sea_orm::{DatabaseBackend, MockDatabase};
+use telemetry_service::{config::TracingConfig, otlp, Server};
+use tower::ServiceExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+// Traces should not be exported unless a collector is configured.
+#[tokio::test]
+async fn no_otlp_endpoint() {
+    assert!(otlp::tracer(&TracingConfig::default()).unwrap().is_none());
+}
+
+// Ingestion spans should continue the incoming trace and carry the chain and node id.
+#[tokio::test]
+async fn ingestion_spans() {
+    let exporter = InMemorySpanExporter::default();
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![upserted_node(true)]])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    let json = fs::read_to_string("res/example_telemetry_payload_v2_mainnet").unwrap();
+    let response = server
+        .app()
+        .oneshot(
+            Request::builder()
+                .uri("/nodes")
+                .method("POST")
+                .header("traceparent", format!("00-{TRACE_ID}-00f067aa0ba902b7-01"))
+                .body(Body::from(json))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let spans = exporter.get_finished_spans().unwrap();
+    let span = |name: &str| {
+        spans
+            .iter()
+            .find(|span| span.name == name)
+            .unwrap_or_else(|| panic!("missing {name} span"))
+    };
+    let (request, ingest) = (span("request"), span("ingest"));
+    let (decode, upsert) = (span("decode"), span("upsert"));
+    assert_eq!(request.span_context.trace_id().to_string(), TRACE_ID);
+    assert_eq!(ingest.parent_span_id, request.span_context.span_id());
+    assert_eq!(decode.parent_span_id, ingest.span_context.span_id());
+    assert_eq!(upsert.parent_span_id, ingest.span_context.span_id());
+    let attributes = format!("{:?}", ingest.attributes);
+    assert!(attributes.contains(r#"String(Owned("mainnet"))"#));
+    assert!(attributes.contains("ed25519:6Hat46Wuxrk1czrhENjJrS3GuYUXYDmMgFtGLFyWGWNq"));
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use axum::http::StatusCode;
+use chrono::{Duration, Utc};
+use common::{get, mock_node, webhook, MOCK_SOCKET_ADDRESS};
+use sea_orm::{prelude::DateTime, DatabaseBackend, DbErr, MockDatabase};
+use serde_json::{json, Value};
+use telemetry_service::{config::ValidatorsConfig, entities::node, Server, Task};
+use test_log::test;
+
+fn mock_validator(id: &str, account_id: &str, height: i64, last_seen: DateTime) -> node::Model {
+    node::Model {
+        account_id: Some(account_id.to_string()),
+        is_validator: true,
+        agent_version: "1.40.0".to_string(),
+        ..mock_node(id, height, "", last_seen)
+    }
+}
+
+// Validator nodes should be grouped by account.
+#[test(tokio::test)]
+async fn list_validators() {
+    let last_seen = DateTime::default();
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![
+            mock_validator("node1", "alice", 100, last_seen),
+            mock_validator("node2", "bob", 101, last_seen),
+            mock_validator("node3", "alice", 102, last_seen),
+        ]])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    let (status, body) = get(server.app(), "/validators/mainnet").await;
+    assert_eq!(status, StatusCode::OK);
+    let validators: Value = serde_json::from_str(&body).unwrap();
+    let accounts: Vec<(&str, Vec<&str>)> = validators
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|validator| {
+            (
+                validator["account_id"].as_str().unwrap(),
+                validator["nodes"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|node| node["id"].as_str().unwrap())
+                    .collect(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        accounts,
+        [("alice", vec!["node1", "node3"]), ("bob", vec!["node2"])]
+    );
+    assert_eq!(validators[0]["nodes"][0]["agent_version"], json!("1.40.0"));
+    assert_eq!(validators[0]["nodes"][1]["height"], json!(102));
+}
+
+// Offline and behind validators should be notified once to the webhook.
+#[test(tokio::test)]
+async fn unhealthy_validators_notified() {
+    let now = Utc::now().naive_utc();
+    let nodes = vec![
+        mock_node("node0", 1000, "", now),
+        mock_validator("node1", "alice", 1000, now),
+        mock_validator("node2", "bob", 1000, now - Duration::hours(1)),
+        mock_validator("node3", "carol", 800, now),
+    ];
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([nodes.clone(), nodes.clone(), nodes[1..].to_vec()])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([Vec::<node::Model>::new(), Vec::<node::Model>::new()])
+        .into_connection();
+    let (url, mut notifications) = webhook().await;
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet)
+        .unwrap()
+        .with_validators(ValidatorsConfig {
+            validator_webhook_url: Some(url),
+            ..Default::default()
+        });
+
+    server.run_task(Task::ValidatorCheck).await.unwrap();
+    server.run_task(Task::ValidatorCheck).await.unwrap();
+
+    let notification = notifications.try_recv().unwrap();
+    assert_eq!(notification["chain"], json!("mainnet"));
+    assert_eq!(notification["account_id"], json!("bob"));
+    assert_eq!(notification["alert"], json!("offline"));
+    let notification = notifications.try_recv().unwrap();
+    assert_eq!(notification["account_id"], json!("carol"));
+    assert_eq!(notification["alert"], json!("behind"));
+    assert_eq!(notification["height"], json!(800));
+    assert_eq!(notification["head"], json!(1000));
+    assert!(notifications.try_recv().is_err());
+
+    let (_, body) = get(server.app(), "/validators/mainnet").await;
+    let validators: Value = serde_json::from_str(&body).unwrap();
+    let alerts: Vec<&Value> = validators
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|validator| &validator["alert"])
+        .collect();
+    assert_eq!(alerts, [&json!(null), &json!("offline"), &json!("behind")]);
+}
+
+// Chains without a database have no validators.
+#[test(tokio::test)]
+async fn unknown_chain() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    let (status, _) = get(server.app(), "/validators/localnet").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+// The details of a failure to load the validators should only be logged.
+#[test(tokio::test)]
+async fn validators_query_error() {
+    let db_mainnet = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_errors([DbErr::Custom("validators error".to_string())])
+        .into_connection();
+    let db_testnet = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let server = Server::new(MOCK_SOCKET_ADDRESS, db_mainnet, db_testnet).unwrap();
+
+    let (status, body) = get(server.app(), "/validators/mainnet").await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(body.starts_with("error loading validators: "), "{body}");
+    assert!(body.contains("\nrequest id: "), "{body}");
+    assert!(!body.contains("DBError"), "{body}");
+}